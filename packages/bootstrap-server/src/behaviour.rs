@@ -23,6 +23,9 @@ pub struct AppBehaviour {
     #[getset(get_copy = "pub", get_mut = "pub", get = "pub")]
     relay: relay::Behaviour,
 
+    #[getset(get_copy = "pub", get_mut = "pub", get = "pub")]
+    autonat: autonat::Behaviour,
+
     #[getset(get_copy = "pub", get_mut = "pub", get = "pub")]
     rendezvous: rendezvous::server::Behaviour,
 
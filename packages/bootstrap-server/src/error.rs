@@ -48,10 +48,10 @@ mod tests {
     fn test_diesel_error_conversion() {
         // Simulate a Diesel error (this is just an example; you'd use an actual Diesel function
         // that produces an error in a real test):
-        let simulated_diesel_error: std::result::Result<String, BaseNoiseError::InvalidLength> = Err(BaseNoiseError::InvalidLength);
+        let simulated_diesel_error: std::result::Result<String, BaseNoiseError> = Err(BaseNoiseError::InvalidLength);
 
         // Convert it to your custom error type:
-        let custom_error = simulated_diesel_error.chain_err(|| "failed due to Diesel error");
+        let custom_error: Result<String> = simulated_diesel_error.map_err(Error::from);
 
         // Assert on the error type:
         match custom_error {
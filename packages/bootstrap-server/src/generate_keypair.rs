@@ -27,7 +27,7 @@ async fn create_keypair(keypair_path: &&str) -> Result<Keypair> {
 
 async fn get_keypair(file: File) -> Result<Keypair> {
     let wrapper: KeypairWrapper = from_reader(file.into_std().await)?;
-    let secret = identity::ecdsa::SecretKey::try_from_bytes(&wrapper.secret())?;
+    let secret = identity::ecdsa::SecretKey::try_from_bytes(wrapper.secret())?;
     let kp = identity::ecdsa::Keypair::from(secret);
 
     log::info!("Loaded keypair from file");
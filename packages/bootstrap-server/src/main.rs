@@ -1,7 +1,7 @@
 use std::net::Ipv4Addr;
 use std::time::Duration;
 
-use libp2p::{gossipsub, identify, kad, mdns, Multiaddr, noise, ping, relay, rendezvous, request_response, StreamProtocol, SwarmBuilder, tcp, tls, yamux};
+use libp2p::{autonat, gossipsub, identify, kad, mdns, Multiaddr, noise, ping, relay, rendezvous, request_response, StreamProtocol, SwarmBuilder, tcp, tls, yamux};
 use libp2p::futures::StreamExt;
 use libp2p::multiaddr::Protocol::{QuicV1, Tcp, Udp, P2p};
 use libp2p::request_response::ProtocolSupport;
@@ -100,7 +100,10 @@ async fn main() -> Result<()> {
                 request_response::Config::default(),
             );
 
-            return AppBehaviour::from((gossip_sub, mdns_behaviour, ping_behaviour, identify_behaviour, relay_behaviour, rendezvous_behaviour, kad_behaviour, request_response_behaviour));
+            // Probes the addresses peers observe us at, and answers the probes of our clients.
+            let autonat_behaviour = autonat::Behaviour::new(key.public().to_peer_id(), autonat::Config::default());
+
+            AppBehaviour::from((gossip_sub, mdns_behaviour, ping_behaviour, identify_behaviour, relay_behaviour, autonat_behaviour, rendezvous_behaviour, kad_behaviour, request_response_behaviour))
         })?
         .build();
 
@@ -180,10 +183,8 @@ async fn main() -> Result<()> {
                 log::info!("KAD pending-routable-peer");
             }
             SwarmEvent::Behaviour(AppBehaviourEvent::Identify(identify::Event::Received { info, peer_id })) => {
-                log::info!("Identity received {peer_id}");
-
-                // TODO: Add the observed address to the peerstore
-                swarm.add_external_address(info.observed_addr);
+                // The observed address is only a candidate; AutoNAT confirms it before it becomes external.
+                log::info!("Identity received {peer_id}, observed us at {}", info.observed_addr);
             }
             SwarmEvent::Behaviour(AppBehaviourEvent::Identify(identify::Event::Sent { .. })) => {
                 log::info!("Identity sent");
@@ -194,6 +195,13 @@ async fn main() -> Result<()> {
             SwarmEvent::Behaviour(AppBehaviourEvent::Identify(identify::Event::Error { peer_id, error })) => {
                 log::info!("Identity {peer_id} error: {:?}", error);
             }
+            SwarmEvent::Behaviour(AppBehaviourEvent::Autonat(autonat::Event::StatusChanged { old, new })) => {
+                // A public status means AutoNAT already confirmed the address as external.
+                log::info!("NAT status changed from {:?} to {:?}", old, new);
+            }
+            SwarmEvent::Behaviour(AppBehaviourEvent::Autonat(event)) => {
+                log::info!("AutoNAT event: {:?}", event);
+            }
             SwarmEvent::ConnectionEstablished { peer_id, .. } => {
                 log::info!("Connection established with: {peer_id}");
             }
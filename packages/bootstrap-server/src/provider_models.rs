@@ -37,18 +37,18 @@ impl From<ProviderRecord> for ProviderRecordSerializable {
     }
 }
 
-impl Into<ProviderRecord> for ProviderRecordSerializable {
-    fn into(self) -> ProviderRecord {
+impl From<ProviderRecordSerializable> for ProviderRecord {
+    fn from(record: ProviderRecordSerializable) -> Self {
         let mut addresses = Vec::new();
-        for x in self.addresses.iter() {
+        for x in record.addresses.iter() {
             let b = Multiaddr::from_str(String::from_utf8(x.to_vec()).unwrap().as_str()).unwrap();
             addresses.push(b);
         }
 
         ProviderRecord {
-            key: Key::new(&self.id),
-            provider: PeerId::from_bytes(&self.provider).unwrap(),
-            expires: self.expires.map(|e| convert_sec_to_instant(e)),
+            key: Key::new(&record.id),
+            provider: PeerId::from_bytes(&record.provider).unwrap(),
+            expires: record.expires.map(convert_sec_to_instant),
             addresses,
         }
     }
@@ -27,19 +27,19 @@ impl From<Record> for RecordSerializable {
             id: record.key.to_vec(),
             value: String::from_utf8(record.value).unwrap(),
             publisher: record.publisher.map(|p| String::from_utf8(p.to_bytes()).unwrap()),
-            expires: record.expires.map(|e| e.elapsed().as_secs_f64() as i64).or_else(|| Some(0.0 as i64)),
+            expires: record.expires.map(|e| e.elapsed().as_secs_f64() as i64).or(Some(0.0 as i64)),
         }
     }
 }
 
-impl Into<Record> for RecordSerializable {
-    fn into(self) -> Record {
+impl From<RecordSerializable> for Record {
+    fn from(record: RecordSerializable) -> Self {
         Record {
-            key: Key::new(&self.id),
-            value: self.value.into_bytes(),
-            publisher: self.publisher.map(|p| PeerId::from_bytes(&p.into_bytes()).unwrap()),
-            expires: self.expires
-                .map(|e| convert_sec_to_instant(e)),
+            key: Key::new(&record.id),
+            value: record.value.into_bytes(),
+            publisher: record.publisher.map(|p| PeerId::from_bytes(&p.into_bytes()).unwrap()),
+            expires: record.expires
+                .map(convert_sec_to_instant),
         }
     }
 }
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE calls
+(
+    id           VARCHAR PRIMARY KEY NOT NULL,
+    room_id      VARCHAR NOT NULL,
+    initiator    VARCHAR NOT NULL,
+    direction    VARCHAR NOT NULL,
+    state        VARCHAR NOT NULL,
+    participants TEXT    NOT NULL,
+    started_at   BIGINT  NOT NULL,
+    answered_at  BIGINT,
+    ended_at     BIGINT
+)
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE invites
+(
+    id         VARCHAR PRIMARY KEY NOT NULL,
+    room_id    VARCHAR NOT NULL,
+    expires_at BIGINT,
+    max_uses   INTEGER,
+    uses       INTEGER NOT NULL DEFAULT 0,
+    created_at BIGINT  NOT NULL
+)
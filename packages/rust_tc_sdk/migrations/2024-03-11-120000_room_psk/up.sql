@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE noise_keys ADD COLUMN psk BINARY
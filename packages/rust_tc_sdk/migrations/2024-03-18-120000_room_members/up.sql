@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE room_members
+(
+    room_id  VARCHAR NOT NULL,
+    peer_id  VARCHAR NOT NULL,
+    role     VARCHAR NOT NULL,
+    added_by VARCHAR NOT NULL,
+    added_at BIGINT  NOT NULL,
+    PRIMARY KEY (room_id, peer_id)
+)
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE room_members DROP COLUMN public_key;
+
+DROP TABLE group_keys;
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE group_keys
+(
+    room_id    VARCHAR NOT NULL,
+    epoch      BIGINT  NOT NULL,
+    key        BINARY  NOT NULL,
+    created_at BIGINT  NOT NULL,
+    PRIMARY KEY (room_id, epoch)
+);
+
+ALTER TABLE room_members ADD COLUMN public_key BINARY;
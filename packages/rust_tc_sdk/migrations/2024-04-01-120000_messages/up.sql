@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE messages
+(
+    id             VARCHAR NOT NULL,
+    room_id        VARCHAR NOT NULL,
+    sender_peer_id VARCHAR NOT NULL,
+    topic          VARCHAR NOT NULL,
+    payload        TEXT    NOT NULL,
+    sent_at        BIGINT  NOT NULL,
+    received_at    BIGINT  NOT NULL,
+    status         VARCHAR NOT NULL,
+    PRIMARY KEY (room_id, id)
+);
+
+CREATE INDEX messages_room_sent_at ON messages (room_id, sent_at);
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE files
+(
+    room_id    VARCHAR NOT NULL,
+    file_id    VARCHAR NOT NULL,
+    name       VARCHAR NOT NULL,
+    size       BIGINT  NOT NULL,
+    chunk_size BIGINT  NOT NULL,
+    chunk_root VARCHAR NOT NULL,
+    offered_by VARCHAR NOT NULL,
+    path       VARCHAR,
+    created_at BIGINT  NOT NULL,
+    PRIMARY KEY (room_id, file_id)
+);
+
+ALTER TABLE messages ADD COLUMN file_id VARCHAR;
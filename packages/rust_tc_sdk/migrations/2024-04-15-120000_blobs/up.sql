@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE blobs
+(
+    hash      VARCHAR NOT NULL PRIMARY KEY,
+    ref_count BIGINT  NOT NULL DEFAULT 0,
+    stored_at BIGINT
+);
+
+INSERT INTO blobs (hash, ref_count)
+SELECT file_id, COUNT(*)
+FROM messages
+WHERE file_id IS NOT NULL
+GROUP BY file_id;
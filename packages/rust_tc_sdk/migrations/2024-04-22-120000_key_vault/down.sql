@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE noise_keys DROP COLUMN encrypted;
+DROP TABLE key_vault;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE key_vault
+(
+    id         INTEGER NOT NULL PRIMARY KEY CHECK (id = 1),
+    kdf        VARCHAR NOT NULL,
+    salt       BINARY  NOT NULL,
+    verifier   BINARY  NOT NULL,
+    created_at BIGINT  NOT NULL
+);
+
+-- Keys written before the vault stay readable until the first unlock seals them.
+ALTER TABLE noise_keys ADD COLUMN encrypted BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Messages kept before this stay unsynced, there is nothing to prove their author with.
+ALTER TABLE messages ADD COLUMN envelope BINARY;
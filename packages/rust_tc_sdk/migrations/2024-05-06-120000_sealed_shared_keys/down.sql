@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE group_keys DROP COLUMN encrypted;
+ALTER TABLE noise_keys DROP COLUMN psk_encrypted;
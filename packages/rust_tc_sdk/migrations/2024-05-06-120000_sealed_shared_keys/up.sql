@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- Pre-shared and group keys written so far are in the clear; the next unlock seals them, as it did private keys.
+ALTER TABLE noise_keys ADD COLUMN psk_encrypted BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE group_keys ADD COLUMN encrypted BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Offers made before this are hashed once, the first time a member asks for their manifest.
+ALTER TABLE files ADD COLUMN manifest BINARY;
@@ -0,0 +1,24 @@
+use derive_more::From;
+use diesel::prelude::*;
+use getset::*;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::blobs;
+
+/// A file kept once in the blob store, however many messages of however many rooms share it.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[diesel(table_name = blobs)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Blob {
+    /// The hex SHA-256 of the content, which is also the `file_id` of the offers for it.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub hash: String,
+
+    /// How many stored messages offer it.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub ref_count: i64,
+
+    /// Milliseconds since the unix epoch; `None` while the content is not on this device.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub stored_at: Option<i64>,
+}
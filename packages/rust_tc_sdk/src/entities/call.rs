@@ -0,0 +1,66 @@
+use derive_more::From;
+use diesel::prelude::*;
+use getset::*;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::calls;
+
+#[derive(From, Getters, MutGetters, Setters, Debug, Clone, Serialize, Deserialize, Queryable, Insertable, Identifiable, AsChangeset)]
+#[diesel(table_name = calls)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Call {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub")]
+    pub id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    /// Peer ID of whoever started the call.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub initiator: String,
+
+    /// `outgoing` or `incoming`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub direction: String,
+
+    /// See `CallState`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub state: String,
+
+    /// JSON array of the peer IDs currently in the call.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub participants: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub started_at: i64,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub answered_at: Option<i64>,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub ended_at: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_initialization() {
+        let call = Call::from((
+            "call-id".to_string(),
+            "room-id".to_string(),
+            "peer-id".to_string(),
+            "outgoing".to_string(),
+            "ringing".to_string(),
+            "[]".to_string(),
+            1,
+            None,
+            None,
+        ));
+
+        assert_eq!(call.id, "call-id");
+        assert_eq!(call.room_id(), "room-id");
+        assert_eq!(call.answered_at(), &None);
+    }
+}
@@ -0,0 +1,64 @@
+use derive_more::From;
+use diesel::prelude::*;
+use getset::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::file_transfer::FileOffer;
+use crate::schema::files;
+
+/// A file shared in a room, offered by this node or by another member.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Debug, Clone, Serialize, Deserialize, Queryable, Selectable, Insertable)]
+#[diesel(table_name = files)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct SharedFile {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    /// The hex SHA-256 of the whole file.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub file_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub name: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub size: i64,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub chunk_size: i64,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub chunk_root: String,
+
+    /// The room identity of the member that offered it.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub offered_by: String,
+
+    /// Where the file is on this device; `None` until it is downloaded.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub path: Option<String>,
+
+    /// Milliseconds since the unix epoch.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub created_at: i64,
+}
+
+impl SharedFile {
+    pub fn offered(room_id: &str, offer: &FileOffer, offered_by: &str, path: Option<String>, created_at: i64) -> Self {
+        Self::from((
+            room_id.to_string(),
+            offer.file_id.clone(),
+            offer.name.clone(),
+            offer.size,
+            offer.chunk_size,
+            offer.chunk_root.clone(),
+            offered_by.to_string(),
+            path,
+            created_at,
+        ))
+    }
+
+    pub fn offer(&self) -> FileOffer {
+        FileOffer::from((self.file_id.clone(), self.name.clone(), self.size, self.chunk_size, self.chunk_root.clone()))
+    }
+}
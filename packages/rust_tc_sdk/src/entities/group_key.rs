@@ -0,0 +1,29 @@
+use derive_more::From;
+use diesel::prelude::*;
+use getset::*;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::group_keys;
+
+/// The group key of one of a room's epochs; a new epoch starts whenever a member is removed.
+#[derive(From, Getters, MutGetters, Setters, Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[diesel(table_name = group_keys)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct GroupKeyModel {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub epoch: i64,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub key: Vec<u8>,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub created_at: i64,
+
+    /// Whether `key` is sealed with the master key, see `KeyVault`.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub encrypted: bool,
+}
@@ -0,0 +1,32 @@
+use derive_more::From;
+use diesel::prelude::*;
+use getset::*;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::invites;
+
+/// An invitation this node issued; joiners redeem it with us before they count it as used.
+#[derive(From, Getters, MutGetters, Setters, Debug, Clone, Serialize, Deserialize, Queryable, Insertable, Identifiable, AsChangeset)]
+#[diesel(table_name = invites)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Invite {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub")]
+    pub id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    /// Milliseconds since the unix epoch; the invite never expires when missing.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub expires_at: Option<i64>,
+
+    /// How many joiners may redeem the invite; unlimited when missing.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub max_uses: Option<i32>,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub uses: i32,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub created_at: i64,
+}
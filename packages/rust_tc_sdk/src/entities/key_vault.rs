@@ -0,0 +1,31 @@
+use derive_more::From;
+use diesel::prelude::*;
+use getset::*;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::key_vault;
+
+/// How the master key is derived, and what tells whether it was derived right.
+#[derive(From, Getters, MutGetters, Setters, Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[diesel(table_name = key_vault)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct VaultModel {
+    /// Always 1, there is a single vault.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub id: i32,
+
+    /// `argon2id` for a passphrase, `host` for a key the host keeps.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub kdf: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub salt: Vec<u8>,
+
+    /// A known value sealed with the master key; only the right key opens it.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub verifier: Vec<u8>,
+
+    /// Milliseconds since the unix epoch.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub created_at: i64,
+}
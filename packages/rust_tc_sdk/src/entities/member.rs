@@ -0,0 +1,35 @@
+use derive_more::From;
+use diesel::prelude::*;
+use getset::*;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::room_members;
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[diesel(table_name = room_members)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Member {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    /// The member's room identity, the author of what it publishes in the room.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub peer_id: String,
+
+    /// See `MemberRole`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub role: String,
+
+    /// The owner who let the member in; the member itself for the room's creator.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub added_by: String,
+
+    /// Milliseconds since the unix epoch.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub added_at: i64,
+
+    /// The protobuf encoding of the member's room public key, once known; group keys are sealed to it.
+    #[serde(default, skip_serializing)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub public_key: Option<Vec<u8>>,
+}
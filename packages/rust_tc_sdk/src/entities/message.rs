@@ -0,0 +1,67 @@
+use derive_more::From;
+use diesel::prelude::*;
+use getset::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::callback_payload::MessagePayload;
+use crate::models::message::MessageStatus;
+use crate::schema::messages;
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[diesel(table_name = messages)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Message {
+    /// The gossipsub message id.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    /// The room identity of the author.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub sender_peer_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub topic: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub payload: String,
+
+    /// Milliseconds since the unix epoch, as seen by the sender.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub sent_at: i64,
+
+    /// Milliseconds since the unix epoch, as seen by this node.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub received_at: i64,
+
+    /// See `MessageStatus`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub status: String,
+
+    /// The file the message offers, see `getFiles`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub file_id: Option<String>,
+
+    /// The signed envelope the message was published in, which lets members it is synced to check its author.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub envelope: Option<Vec<u8>>,
+}
+
+impl Message {
+    pub fn stored(payload: &MessagePayload, status: MessageStatus, received_at: i64, envelope: Option<Vec<u8>>) -> Self {
+        Self::from((
+            payload.message_id.clone(),
+            payload.room_id.clone(),
+            payload.peer_id.clone(),
+            payload.topic.clone(),
+            payload.payload.clone(),
+            payload.timestamp,
+            received_at,
+            status.as_str().to_string(),
+            payload.file.as_ref().map(|file| file.file_id.clone()),
+            envelope,
+        ))
+    }
+}
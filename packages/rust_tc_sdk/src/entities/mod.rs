@@ -1,2 +1,10 @@
 pub(crate) mod room;
 pub(crate) mod noise;
+pub(crate) mod call;
+pub(crate) mod invite;
+pub(crate) mod member;
+pub(crate) mod group_key;
+pub(crate) mod message;
+pub(crate) mod file;
+pub(crate) mod blob;
+pub(crate) mod key_vault;
@@ -17,6 +17,19 @@ pub struct NoiseModel {
 
     #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
     pub public: Vec<u8>,
+
+    /// The 32-byte pre-shared key guarding the room's swarm, when it is a private network.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub psk: Option<Vec<u8>>,
+
+    /// Whether `private` is sealed with the master key, see `KeyVault`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub")]
+    pub encrypted: bool,
+
+    /// Whether `psk` is sealed with the master key.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub")]
+    pub psk_encrypted: bool,
 }
 
 #[cfg(test)]
@@ -29,10 +42,13 @@ mod tests {
         let private = vec![1, 2, 3];
         let public = vec![4, 5, 6];
 
-        let model = NoiseModel::from((id.to_string(), private.clone(), public.clone()));
+        let model = NoiseModel::from((id.to_string(), private.clone(), public.clone(), None, false, false));
 
         assert_eq!(model.id, id);
         assert_eq!(model.private, private);
         assert_eq!(model.public, public);
+        assert_eq!(model.psk, None);
+        assert!(!model.encrypted);
+        assert!(!model.psk_encrypted);
     }
 }
@@ -1,3 +1,5 @@
+#![recursion_limit = "256"]
+
 use neon::prelude::*;
 
 use crate::services::setup::*;
@@ -14,12 +16,41 @@ fn main(mut cx: ModuleContext) -> NeonResult<()> {
 
     cx.export_function("startSdk", start_sdk)?;
     cx.export_function("stopSdk", stop_sdk)?;
+    cx.export_function("unlockSdk", unlock_sdk)?;
+    cx.export_function("lockSdk", lock_sdk)?;
+    cx.export_function("rekeyDatabase", rekey_database)?;
+    cx.export_function("exportBackup", export_backup)?;
+    cx.export_function("importBackup", import_backup)?;
     cx.export_function("createRoom", create_room)?;
     cx.export_function("removeRoom", remove_room)?;
     cx.export_function("launchRoom", launch_room)?;
     cx.export_function("quitRoom", quit_room)?;
     cx.export_function("getRoom", get_room)?;
     cx.export_function("getRooms", get_rooms)?;
+    cx.export_function("sendMessage", send_message)?;
+    cx.export_function("getMessages", get_messages)?;
+    cx.export_function("offerFile", offer_file)?;
+    cx.export_function("downloadFile", download_file)?;
+    cx.export_function("getFiles", get_files)?;
+    cx.export_function("sendSignal", send_signal)?;
+    cx.export_function("startCall", start_call)?;
+    cx.export_function("acceptCall", accept_call)?;
+    cx.export_function("rejectCall", reject_call)?;
+    cx.export_function("hangUp", hang_up)?;
+    cx.export_function("getNetworkStatus", get_network_status)?;
+    cx.export_function("getListenAddresses", get_listen_addresses)?;
+    cx.export_function("dialPeer", dial_peer)?;
+    cx.export_function("disconnectPeer", disconnect_peer)?;
+    cx.export_function("getPeers", get_peers)?;
+    cx.export_function("subscribe", subscribe)?;
+    cx.export_function("unsubscribe", unsubscribe)?;
+    cx.export_function("addExternalAddress", add_external_address)?;
+    cx.export_function("createInvite", create_invite)?;
+    cx.export_function("acceptInvite", accept_invite)?;
+    cx.export_function("addMember", add_member)?;
+    cx.export_function("removeMember", remove_member)?;
+    cx.export_function("getMembers", get_members)?;
     cx.export_function("registerListener", register_listener)?;
+    cx.export_function("unregisterListener", unregister_listener)?;
     Ok(())
 }
@@ -0,0 +1,229 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use derive_more::From;
+use getset::*;
+use rand::RngCore;
+use serde::*;
+use zeroize::Zeroizing;
+
+use crate::entities::file::SharedFile;
+use crate::entities::group_key::GroupKeyModel;
+use crate::entities::member::Member;
+use crate::entities::message::Message;
+use crate::entities::noise::NoiseModel;
+use crate::entities::room::Room;
+use crate::models::error::*;
+use crate::models::master_key::{open_at_rest, seal_at_rest, MasterKey, MASTER_KEY_SIZE};
+
+/// What every archive starts with.
+const MAGIC: &[u8; 8] = b"VICHIZBK";
+
+/// The layout and content archives are written with; bump it whenever either changes.
+pub const BACKUP_VERSION: u16 = 1;
+
+const SALT_SIZE: usize = 16;
+
+/// The magic, the version padded to four bytes, the Argon2id costs and the salt; the whole header is authenticated.
+const HEADER_SIZE: usize = MAGIC.len() + 4 + 3 * 4 + SALT_SIZE;
+
+/// An archive asking for more memory than this, in KiB, is refused rather than derived.
+const MAX_MEMORY_COST: u32 = 1 << 20;
+
+/// What `exportBackup` and `importBackup` take.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Deserialize)]
+pub struct BackupData {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub path: String,
+
+    /// Stretched with Argon2id into the key the archive is sealed with.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub passphrase: String,
+
+    /// What an import does with a room this device already has; ignored on export.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub conflict: BackupConflict,
+}
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupConflict {
+    /// The room here stays as it is.
+    #[default]
+    Skip,
+    /// The room here, its identity and history included, is replaced with the backed up one.
+    Overwrite,
+    /// The room here keeps its name and identity and gains the members, group keys, messages and files it lacks.
+    Merge,
+}
+
+/// What `importBackup` did with each room of the archive.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone, Default)]
+pub struct RestoredBackup {
+    /// Rooms that were not here, and those overwritten.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub restored: Vec<String>,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub merged: Vec<String>,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub skipped: Vec<String>,
+}
+
+/// Everything an archive holds; private keys are in the clear, so it only exists sealed outside memory.
+#[derive(From, Getters, Serialize, Deserialize)]
+pub struct Backup {
+    /// Milliseconds since the unix epoch.
+    #[getset(get = "pub")]
+    pub created_at: i64,
+
+    #[getset(get = "pub")]
+    pub rooms: Vec<RoomBackup>,
+}
+
+#[derive(From, Getters, Serialize, Deserialize)]
+pub struct RoomBackup {
+    #[getset(get = "pub")]
+    pub room: Room,
+
+    /// The room identity and pre-shared key, never sealed with the master key.
+    #[getset(get = "pub")]
+    pub key: NoiseModel,
+
+    #[getset(get = "pub")]
+    pub members: Vec<BackupMember>,
+
+    /// In the clear as well.
+    #[getset(get = "pub")]
+    pub group_keys: Vec<GroupKeyModel>,
+
+    #[getset(get = "pub")]
+    pub messages: Vec<Message>,
+
+    /// The offers only; the files themselves are downloaded again.
+    #[getset(get = "pub")]
+    pub files: Vec<SharedFile>,
+}
+
+/// A member with its public key, which `Member` keeps away from JS.
+#[derive(Serialize, Deserialize)]
+pub struct BackupMember {
+    #[serde(flatten)]
+    pub member: Member,
+
+    pub public_key: Option<Vec<u8>>,
+}
+
+impl From<Member> for BackupMember {
+    fn from(member: Member) -> Self {
+        let public_key = member.public_key.clone();
+        BackupMember { member, public_key }
+    }
+}
+
+impl From<BackupMember> for Member {
+    fn from(backed_up: BackupMember) -> Self {
+        Member { public_key: backed_up.public_key, ..backed_up.member }
+    }
+}
+
+impl Backup {
+    /// The archive: a plain header, then the backup sealed under the passphrase and bound to that header.
+    pub fn seal(&self, passphrase: &str) -> Result<Vec<u8>> {
+        let mut salt = [0u8; SALT_SIZE];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let params = Params::default();
+
+        let header = header(BACKUP_VERSION, &params, &salt);
+        let key = derive(passphrase, params, &salt)?;
+        let plaintext = Zeroizing::new(serde_json::to_vec(self)?);
+
+        Ok([header.as_slice(), &seal_at_rest(&key, &header, &plaintext)?].concat())
+    }
+
+    pub fn open(archive: &[u8], passphrase: &str) -> Result<Self> {
+        if archive.len() < HEADER_SIZE || &archive[..MAGIC.len()] != MAGIC {
+            return Err(ErrorKind::InvalidBackup("not a backup archive".to_string()).into());
+        }
+
+        let (header, sealed) = archive.split_at(HEADER_SIZE);
+        let version = u16::from_be_bytes([header[8], header[9]]);
+        if version != BACKUP_VERSION {
+            return Err(ErrorKind::InvalidBackup(format!("version {version} is not supported, this SDK reads version {BACKUP_VERSION}")).into());
+        }
+
+        // See `header`.
+        let word = |at: usize| u32::from_be_bytes([header[at], header[at + 1], header[at + 2], header[at + 3]]);
+        let (memory, iterations, parallelism) = (word(12), word(16), word(20));
+        if memory > MAX_MEMORY_COST {
+            return Err(ErrorKind::InvalidBackup("the archive asks for too much memory to derive its key".to_string()).into());
+        }
+        let params = Params::new(memory, iterations, parallelism, Some(MASTER_KEY_SIZE))
+            .map_err(|e| ErrorKind::InvalidBackup(format!("malformed key derivation parameters: {e}")))?;
+
+        let key = derive(passphrase, params, &header[HEADER_SIZE - SALT_SIZE..])?;
+        let plaintext = open_at_rest(&key, header, sealed)
+            .map_err(|_| ErrorKind::InvalidBackup("the passphrase is wrong or the archive was altered".to_string()))?;
+
+        serde_json::from_slice(&plaintext)
+            .map_err(|e| ErrorKind::InvalidBackup(format!("malformed content: {e}")).into())
+    }
+}
+
+fn header(version: u16, params: &Params, salt: &[u8; SALT_SIZE]) -> Vec<u8> {
+    // The version takes two bytes, the rest of its four-byte slot is padding for the costs to line up.
+    [
+        MAGIC.as_slice(),
+        &version.to_be_bytes(),
+        &[0u8; 2],
+        &params.m_cost().to_be_bytes(),
+        &params.t_cost().to_be_bytes(),
+        &params.p_cost().to_be_bytes(),
+        salt,
+    ].concat()
+}
+
+fn derive(passphrase: &str, params: Params, salt: &[u8]) -> Result<MasterKey> {
+    if passphrase.is_empty() {
+        return Err(ErrorKind::InvalidArgument("the backup passphrase must not be empty".to_string()).into());
+    }
+
+    let mut key = Zeroizing::new([0u8; MASTER_KEY_SIZE]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .map_err(|e| ErrorKind::InvalidKey(format!("could not derive the backup key: {e}")))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backup() -> Backup {
+        let room = Room::from(("room".to_string(), "Room".to_string()));
+        let key = NoiseModel::from(("room".to_string(), vec![1, 2, 3], vec![4, 5, 6], None, false, false));
+        let member = Member::from(("room".to_string(), "peer".to_string(), "owner".to_string(), "peer".to_string(), 1, Some(vec![7])));
+        Backup::from((1, vec![RoomBackup::from((room, key, vec![member.into()], vec![], vec![], vec![]))]))
+    }
+
+    #[test]
+    fn test_archive_opens_with_its_passphrase_only() {
+        let archive = backup().seal("correct horse").unwrap();
+
+        let opened = Backup::open(&archive, "correct horse").unwrap();
+        let member = Member::from(opened.rooms.into_iter().next().unwrap().members.remove(0));
+        assert_eq!(member.public_key, Some(vec![7]));
+
+        assert_eq!(Backup::open(&archive, "battery staple").err().unwrap().code(), "INVALID_BACKUP");
+        assert_eq!(Backup::open(b"not an archive", "correct horse").err().unwrap().code(), "INVALID_BACKUP");
+    }
+
+    #[test]
+    fn test_header_is_authenticated() {
+        let mut archive = backup().seal("correct horse").unwrap();
+        // The padding after the version, which nothing but the authentication reads.
+        archive[10] ^= 1;
+
+        assert_eq!(Backup::open(&archive, "correct horse").err().unwrap().code(), "INVALID_BACKUP");
+    }
+}
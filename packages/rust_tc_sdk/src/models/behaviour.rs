@@ -3,6 +3,12 @@ use getset::*;
 use libp2p::*;
 use libp2p::swarm::*;
 
+use crate::models::file_transfer::{ChunkRequest, ChunkResponse};
+use crate::models::group_key::{GroupKeyRequest, GroupKeyResponse};
+use crate::models::invitation::{InviteRequest, InviteResponse};
+use crate::models::message_sync::{SyncRequest, SyncResponse};
+use crate::models::signal::{SignalRequest, SignalResponse};
+
 #[derive(From, NetworkBehaviour, Getters, MutGetters, Setters)]
 pub struct AppBehaviour {
     #[getset(get_copy = "pub", get_mut = "pub", get = "pub")]
@@ -20,6 +26,31 @@ pub struct AppBehaviour {
     #[getset(get_copy = "pub", get_mut = "pub", get = "pub")]
     relay: relay::Behaviour,
 
+    #[getset(get_copy = "pub", get_mut = "pub", get = "pub")]
+    relay_client: relay::client::Behaviour,
+
+    #[getset(get_copy = "pub", get_mut = "pub", get = "pub")]
+    dcutr: dcutr::Behaviour,
+
+    #[getset(get_copy = "pub", get_mut = "pub", get = "pub")]
+    autonat: autonat::Behaviour,
+
     #[getset(get_copy = "pub", get_mut = "pub", get = "pub")]
     rendezvous: rendezvous::client::Behaviour,
+
+    #[getset(get_copy = "pub", get_mut = "pub", get = "pub")]
+    signaling: request_response::json::Behaviour<SignalRequest, SignalResponse>,
+
+    #[getset(get_copy = "pub", get_mut = "pub", get = "pub")]
+    invites: request_response::json::Behaviour<InviteRequest, InviteResponse>,
+
+    #[getset(get_copy = "pub", get_mut = "pub", get = "pub")]
+    group_keys: request_response::json::Behaviour<GroupKeyRequest, GroupKeyResponse>,
+
+    #[getset(get_copy = "pub", get_mut = "pub", get = "pub")]
+    message_sync: request_response::json::Behaviour<SyncRequest, SyncResponse>,
+
+    /// CBOR rather than JSON, which would spell every chunk byte out as a number.
+    #[getset(get_copy = "pub", get_mut = "pub", get = "pub")]
+    file_transfer: request_response::cbor::Behaviour<ChunkRequest, ChunkResponse>,
 }
\ No newline at end of file
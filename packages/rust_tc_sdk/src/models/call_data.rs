@@ -0,0 +1,18 @@
+use derive_more::From;
+use getset::*;
+use serde::*;
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize)]
+pub struct StartCallData {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+}
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize)]
+pub struct CallId {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub call_id: String,
+}
@@ -0,0 +1,45 @@
+use serde::*;
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CallState {
+    Ringing,
+    Active,
+    Ended,
+    Rejected,
+    Missed,
+    Busy,
+}
+
+impl CallState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CallState::Ringing => "ringing",
+            CallState::Active => "active",
+            CallState::Ended => "ended",
+            CallState::Rejected => "rejected",
+            CallState::Missed => "missed",
+            CallState::Busy => "busy",
+        }
+    }
+
+    /// Whether the call is over and no longer accepts transitions.
+    pub fn is_final(&self) -> bool {
+        !matches!(self, CallState::Ringing | CallState::Active)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_state_serializes_as_str() {
+        for state in [CallState::Ringing, CallState::Active, CallState::Ended, CallState::Rejected, CallState::Missed, CallState::Busy] {
+            assert_eq!(serde_json::to_value(state).unwrap(), state.as_str());
+        }
+
+        assert!(!CallState::Ringing.is_final());
+        assert!(CallState::Missed.is_final());
+    }
+}
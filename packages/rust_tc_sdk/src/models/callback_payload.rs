@@ -1,9 +1,285 @@
-use derive_more::*;
+use derive_more::From;
 use getset::*;
 use serde::*;
 
+use crate::models::call_state::CallState;
+use crate::models::file_transfer::FileOffer;
+use crate::models::member::MemberRole;
+use crate::models::network_status::NetworkStatus;
+use crate::models::transport::Transport;
+
+/// Everything the SDK reports to the JS listeners registered with `registerListener`.
+///
+/// It is serialized as `{ type, data }` and handed to each callback as `(type, data)`.
+#[derive(PartialEq, Serialize, Debug, Deserialize, Clone)]
+#[serde(tag = "type", content = "data", rename_all = "kebab-case")]
+pub enum CallbackPayload {
+    PeerDiscovered(PeerPayload),
+    PeerExpired(PeerPayload),
+    ConnectionOpened(ConnectionPayload),
+    ConnectionClosed(ConnectionPayload),
+    Ping(PingPayload),
+    Message(MessagePayload),
+    Rendezvous(RendezvousPayload),
+    Signal(SignalPayload),
+    Call(CallPayload),
+    Reachability(NetworkStatus),
+    HolePunch(HolePunchPayload),
+    Member(MemberPayload),
+    File(FilePayload),
+}
+
+impl CallbackPayload {
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            CallbackPayload::PeerDiscovered(_) => "peer-discovered",
+            CallbackPayload::PeerExpired(_) => "peer-expired",
+            CallbackPayload::ConnectionOpened(_) => "connection-opened",
+            CallbackPayload::ConnectionClosed(_) => "connection-closed",
+            CallbackPayload::Ping(_) => "ping",
+            CallbackPayload::Message(_) => "message",
+            CallbackPayload::Rendezvous(_) => "rendezvous",
+            CallbackPayload::Signal(_) => "signal",
+            CallbackPayload::Call(_) => "call",
+            CallbackPayload::Reachability(_) => "reachability",
+            CallbackPayload::HolePunch(_) => "hole-punch",
+            CallbackPayload::Member(_) => "member",
+            CallbackPayload::File(_) => "file",
+        }
+    }
+}
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct PeerPayload {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub peer_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub address: String,
+}
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct ConnectionPayload {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub peer_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub address: String,
+
+    /// Whether the connection goes through a relay rather than straight to the peer.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub relayed: bool,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub transport: Option<Transport>,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub cause: Option<String>,
+}
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct PingPayload {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub peer_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub rtt_ms: Option<u64>,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub error: Option<String>,
+}
+
 #[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
-pub struct CallbackPayload {
+pub struct MessagePayload {
     #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
-    pub data: String,
+    pub room_id: String,
+
+    /// The author of the message, not the peer that relayed it.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub peer_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub message_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub topic: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub payload: String,
+
+    /// Milliseconds since the unix epoch, as seen by the sender.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub timestamp: i64,
+
+    /// Fetched from another member on reconnect rather than delivered live, so it may arrive late.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub synced: bool,
+
+    /// The file the message offers, which `downloadFile` fetches.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub file: Option<FileOffer>,
+}
+
+/// How upgrading a relayed connection to a direct one went.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct HolePunchPayload {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub peer_id: String,
+
+    /// Why it failed; `None` once the connection is direct.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub error: Option<String>,
+}
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct RendezvousPayload {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    /// One of `registered`, `register-failed`, `discovered`, `discover-failed` or `expired`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub status: String,
+
+    /// The rendezvous node, or the expired peer for `expired`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub peer_id: String,
+}
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct SignalPayload {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    /// The peer that sent the signal.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub peer_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub signal: serde_json::Value,
+}
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct CallPayload {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub call_id: String,
+
+    /// What happened: `invited`, `ringing`, `accepted`, `joined`, `left`, `rejected`, `busy`, `missed` or `ended`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub event: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub state: CallState,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub direction: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub initiator: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub participants: Vec<String>,
+
+    /// The peer whose action caused the event, if it was not us.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub peer_id: Option<String>,
+}
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct MemberPayload {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub peer_id: String,
+
+    /// `added` or `removed`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub event: String,
+
+    /// The member's role; `None` once it was removed.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub role: Option<MemberRole>,
+
+    /// The owner who made the change.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub changed_by: String,
+}
+
+impl MemberPayload {
+    pub fn added(room_id: &str, peer_id: &str, role: MemberRole, changed_by: &str) -> Self {
+        Self::from((room_id.to_string(), peer_id.to_string(), "added".to_string(), Some(role), changed_by.to_string()))
+    }
+
+    pub fn removed(room_id: &str, peer_id: &str, changed_by: &str) -> Self {
+        Self::from((room_id.to_string(), peer_id.to_string(), "removed".to_string(), None, changed_by.to_string()))
+    }
+}
+
+/// How a download is going.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct FilePayload {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub file_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub name: String,
+
+    /// `started`, `progress`, `completed`, `paused` or `failed`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub event: String,
+
+    /// Verified bytes so far.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub received: i64,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub size: i64,
+
+    /// Where the file landed, once `completed`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub path: Option<String>,
+
+    /// Why the download was `paused` or `failed`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_type_matches_serialized_tag() {
+        let payload = CallbackPayload::Ping(PingPayload::from((
+            "room".to_string(),
+            "peer".to_string(),
+            Some(12),
+            None,
+        )));
+
+        let value = serde_json::to_value(&payload).unwrap();
+
+        assert_eq!(value["type"], payload.event_type());
+        assert_eq!(value["data"]["rtt_ms"], 12);
+    }
 }
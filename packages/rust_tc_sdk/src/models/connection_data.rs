@@ -1,14 +1,40 @@
 use getset::*;
 use serde::{Deserialize, Serialize};
 
+use crate::models::transport::Transport;
+
 #[derive(PartialEq, Serialize, Deserialize, Getters, MutGetters, Setters, Clone, Debug)]
 pub struct ConnectionData {
   #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
   pub room_id: String,
 
+  #[serde(default)]
   #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
   pub room_multi_address: Vec<String>,
 
+  #[serde(default)]
   #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
   pub room_listen_on: Vec<String>,
+
+  /// Rendezvous servers to register on and discover members through, each ending with `/p2p/<peer id>`.
+  ///
+  /// They are the only relays the room reserves circuits on.
+  #[serde(default)]
+  #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+  pub rendezvous_nodes: Vec<String>,
+
+  /// Transports the room listens and dials on; all of them when empty.
+  #[serde(default)]
+  #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+  pub transports: Vec<Transport>,
+}
+
+impl ConnectionData {
+  pub fn enabled_transports(&self) -> Vec<Transport> {
+    if self.transports.is_empty() {
+      Transport::ALL.to_vec()
+    } else {
+      self.transports.clone()
+    }
+  }
 }
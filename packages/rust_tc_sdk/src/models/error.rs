@@ -24,6 +24,9 @@ error_chain! {
         MultiAddrError(libp2p::multiaddr::Error);
         TransportError(BaseTransportError<std::io::Error>);
         ControlMessageSendError(std::sync::mpsc::SendError<ControlMessage>);
+        PublishError(libp2p::gossipsub::PublishError);
+        SerdeJsonError(serde_json::Error);
+        PeerIdParseError(libp2p::identity::ParseError);
     }
 
     errors {
@@ -31,6 +34,171 @@ error_chain! {
             description("Key not found")
             display("Key not found")
         }
+
+        RoomNotFound(room_id: String) {
+            description("Room not found")
+            display("Room '{}' not found", room_id)
+        }
+
+        SdkNotStarted {
+            description("SDK not started")
+            display("The SDK has not been started, call startSdk first")
+        }
+
+        InvalidArgument(reason: String) {
+            description("Invalid argument")
+            display("Invalid argument: {}", reason)
+        }
+
+        DatabaseUnavailable(reason: String) {
+            description("Database unavailable")
+            display("Database unavailable: {}", reason)
+        }
+
+        RoomNotRunning(room_id: String) {
+            description("Room is not running")
+            display("Room '{}' is not running", room_id)
+        }
+
+        SwarmStopped {
+            description("Swarm stopped")
+            display("The room swarm has stopped")
+        }
+
+        CallNotFound(call_id: String) {
+            description("Call not found")
+            display("Call '{}' not found", call_id)
+        }
+
+        CallFailed(reason: String) {
+            description("Call failed")
+            display("Call failed: {}", reason)
+        }
+
+        SignalFailed(peer_id: String, reason: String) {
+            description("Signal could not be delivered")
+            display("Signal to '{}' could not be delivered: {}", peer_id, reason)
+        }
+
+        PeerNotConnected(peer_id: String) {
+            description("Peer not connected")
+            display("Peer '{}' is not connected", peer_id)
+        }
+
+        InvalidInvite(reason: String) {
+            description("Invalid invite")
+            display("Invalid invite: {}", reason)
+        }
+
+        InviteRejected(reason: String) {
+            description("Invite rejected")
+            display("The invite was rejected: {}", reason)
+        }
+
+        InvalidSignature(reason: String) {
+            description("Invalid signature")
+            display("Invalid signature: {}", reason)
+        }
+
+        InvalidKey(reason: String) {
+            description("Invalid key")
+            display("Invalid key: {}", reason)
+        }
+
+        GroupKeyMissing(room_id: String) {
+            description("Group key missing")
+            display("The group key of room '{}' has not been received yet", room_id)
+        }
+
+        PermissionDenied(reason: String) {
+            description("Permission denied")
+            display("Permission denied: {}", reason)
+        }
+
+        FileNotFound(file_id: String) {
+            description("File not found")
+            display("File '{}' not found", file_id)
+        }
+
+        TransferFailed(reason: String) {
+            description("File transfer failed")
+            display("File transfer failed: {}", reason)
+        }
+
+        SdkLocked {
+            description("SDK locked")
+            display("The SDK is locked, call unlockSdk first")
+        }
+
+        WrongMasterKey {
+            description("Wrong master key")
+            display("The passphrase or master key does not unlock the SDK")
+        }
+
+        WrongDatabaseKey {
+            description("Wrong database key")
+            display("The database key does not open the database")
+        }
+
+        InvalidBackup(reason: String) {
+            description("Invalid backup")
+            display("Invalid backup: {}", reason)
+        }
+    }
+}
+
+impl Error {
+    /// A stable identifier JS callers can branch on, unlike the message.
+    pub fn code(&self) -> &'static str {
+        match self.kind() {
+            ErrorKind::RoomNotFound(_) => "ROOM_NOT_FOUND",
+            ErrorKind::KeyNotFound => "KEY_NOT_FOUND",
+            ErrorKind::RoomNotRunning(_) => "ROOM_NOT_RUNNING",
+            ErrorKind::SwarmStopped => "SWARM_STOPPED",
+            ErrorKind::SdkNotStarted => "SDK_NOT_STARTED",
+            ErrorKind::CallNotFound(_) => "CALL_NOT_FOUND",
+            ErrorKind::CallFailed(_) => "CALL_FAILED",
+            ErrorKind::SignalFailed(_, _) => "SIGNAL_FAILED",
+            ErrorKind::PeerNotConnected(_) => "PEER_NOT_CONNECTED",
+            ErrorKind::InvalidInvite(_) => "INVALID_INVITE",
+            ErrorKind::InviteRejected(_) => "INVITE_REJECTED",
+            ErrorKind::PermissionDenied(_) => "PERMISSION_DENIED",
+            ErrorKind::GroupKeyMissing(_) => "GROUP_KEY_MISSING",
+            ErrorKind::FileNotFound(_) => "FILE_NOT_FOUND",
+            ErrorKind::TransferFailed(_) => "TRANSFER_FAILED",
+            ErrorKind::SdkLocked => "SDK_LOCKED",
+            ErrorKind::WrongMasterKey => "WRONG_MASTER_KEY",
+            ErrorKind::WrongDatabaseKey => "WRONG_DATABASE_KEY",
+            ErrorKind::InvalidBackup(_) => "INVALID_BACKUP",
+            ErrorKind::InvalidArgument(_)
+            | ErrorKind::MultiAddrError(_)
+            | ErrorKind::PeerIdParseError(_)
+            | ErrorKind::SerdeJsonError(_) => "INVALID_ARGUMENT",
+            ErrorKind::DatabaseUnavailable(_)
+            | ErrorKind::DieselError(_)
+            | ErrorKind::DieselR2d2Error(_)
+            | ErrorKind::R2d2Error(_) => "DB_ERROR",
+            ErrorKind::DialError(_) | ErrorKind::TransportError(_) => "DIAL_ERROR",
+            ErrorKind::PublishError(_) => "PUBLISH_ERROR",
+            ErrorKind::SubscriptionError(_) => "SUBSCRIPTION_ERROR",
+            ErrorKind::NoiseError(_)
+            | ErrorKind::EitherError(_)
+            | ErrorKind::TlsCertificate(_)
+            | ErrorKind::OtherVariant(_)
+            | ErrorKind::Decoding(_)
+            | ErrorKind::InvalidSignature(_)
+            | ErrorKind::InvalidKey(_) => "CRYPTO_ERROR",
+            ErrorKind::StdError(_) => "IO_ERROR",
+            _ => "INTERNAL_ERROR",
+        }
+    }
+
+    /// The message of this error followed by each of its causes.
+    pub fn chain_message(&self) -> String {
+        self.iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join(": ")
     }
 }
 
@@ -48,20 +216,36 @@ mod tests {
         }
     }
 
-    // TODO #[test]
-    fn test_diesel_error_conversion() {
-        // Simulate a Diesel error (this is just an example; you'd use an actual Diesel function
-        // that produces an error in a real test):
-        let simulated_diesel_error: std::result::Result<String, diesel::result::Error> = Err(diesel::result::Error::NotFound);
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(Error::from(ErrorKind::RoomNotFound("room".to_string())).code(), "ROOM_NOT_FOUND");
+        assert_eq!(Error::from(ErrorKind::KeyNotFound).code(), "KEY_NOT_FOUND");
+        assert_eq!(Error::from(diesel::result::Error::NotFound).code(), "DB_ERROR");
+        assert_eq!(Error::from("something else").code(), "INTERNAL_ERROR");
+    }
 
-        // Convert it to your custom error type:
-        let custom_error = simulated_diesel_error.chain_err(|| "failed due to Diesel error");
+    #[test]
+    fn test_chain_message_includes_causes() {
+        let error = Error::with_chain(diesel::result::Error::NotFound, "Failed to load room");
+
+        assert_eq!(error.chain_message(), "Failed to load room: Record not found");
+    }
 
-        // Assert on the error type:
-        match custom_error {
-            Err(Error(ErrorKind::DieselError(_), _)) => {}
+    #[test]
+    fn test_diesel_error_conversion() {
+        let simulated_diesel_error = || -> std::result::Result<String, diesel::result::Error> { Err(diesel::result::Error::NotFound) };
+
+        // `?` keeps the Diesel error as the kind.
+        let converted = simulated_diesel_error().map_err(Error::from);
+        match converted {
+            Err(Error(ErrorKind::DieselError(diesel::result::Error::NotFound), _)) => {}
             _ => panic!("Unexpected error type!"),
         }
+
+        // `chain_err` wraps it as the cause of a message instead.
+        let chained = simulated_diesel_error().chain_err(|| "failed due to Diesel error").unwrap_err();
+        assert!(matches!(chained.kind(), ErrorKind::Msg(_)));
+        assert_eq!(chained.chain_message(), "failed due to Diesel error: Record not found");
     }
 
     // Continue with similar tests for other error types.
@@ -0,0 +1,83 @@
+use derive_more::From;
+use getset::*;
+use serde::*;
+
+use crate::models::group_key::SealedPayload;
+
+/// What a room learns about a shared file; whoever holds it serves its chunks.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct FileOffer {
+    /// The hex SHA-256 of the whole file.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub file_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub name: String,
+
+    /// In bytes.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub size: i64,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub chunk_size: i64,
+
+    /// The hex SHA-256 of the concatenated chunk hashes, which vouches for the manifest.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub chunk_root: String,
+}
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize)]
+pub struct OfferFileData {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    /// The local file to share; it is copied to the blob store, unless the same content is there already.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub path: String,
+}
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize)]
+pub struct DownloadFileData {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub file_id: String,
+
+    /// The member to pull the file from; the one that offered it when missing.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub peer_id: Option<String>,
+}
+
+#[derive(PartialEq, Serialize, Debug, Deserialize, Clone)]
+pub enum ChunkRequest {
+    /// The hash of every chunk from chunk `from` on, a page at a time.
+    Manifest { room_id: String, file_id: String, from: u64 },
+    Chunk { room_id: String, file_id: String, index: u64 },
+}
+
+#[derive(PartialEq, Serialize, Debug, Deserialize, Clone)]
+pub enum ChunkResponse {
+    /// A page of the concatenated 32-byte chunk hashes; empty past the last chunk.
+    Manifest(Vec<u8>),
+    /// The chunk sealed with the room's group key, so only members can read it.
+    Chunk(SealedPayload),
+    Rejected(String),
+}
+
+/// What a sealed chunk is bound to, so it cannot pass for another chunk.
+pub fn chunk_scope(room_id: &str, file_id: &str, index: u64) -> Vec<u8> {
+    [room_id.as_bytes(), &[0], file_id.as_bytes(), &index.to_be_bytes()].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_scope_tells_chunks_apart() {
+        assert_ne!(chunk_scope("room", "file", 3), chunk_scope("room", "file", 4));
+        assert_ne!(chunk_scope("room", "file", 3), chunk_scope("other", "file", 3));
+    }
+}
@@ -0,0 +1,206 @@
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use derive_more::From;
+use getset::*;
+use libp2p::gossipsub::TopicHash;
+use libp2p::identity;
+use p256::ecdh::EphemeralSecret;
+use serde::*;
+use sha2::Sha256;
+
+use crate::models::error::*;
+
+pub const GROUP_KEY_SIZE: usize = 32;
+
+/// The symmetric key a room's messages are sealed with during one epoch.
+pub type GroupKey = [u8; GROUP_KEY_SIZE];
+
+/// Separates the keys derived to wrap group keys from any other use of the same secret.
+const WRAP_INFO: &[u8] = b"vichiz/group-key/1";
+
+/// A room message encrypted with the group key of `epoch`.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct SealedPayload {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub epoch: i64,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub nonce: Vec<u8>,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub ciphertext: Vec<u8>,
+}
+
+impl SealedPayload {
+    /// Binds the ciphertext to `topic`, so it cannot be replayed on another one.
+    pub fn seal(key: &GroupKey, epoch: i64, topic: &TopicHash, plaintext: &str) -> Result<Self> {
+        Self::seal_bytes(key, epoch, topic.as_str().as_bytes(), plaintext.as_bytes())
+    }
+
+    pub fn open(&self, key: &GroupKey, topic: &TopicHash) -> Result<String> {
+        let plaintext = self.open_bytes(key, topic.as_str().as_bytes())?;
+        String::from_utf8(plaintext).map_err(|_| ErrorKind::InvalidKey("the message is not text".to_string()).into())
+    }
+
+    /// Seals binary data, bound to whatever `aad` says it is.
+    pub fn seal_bytes(key: &GroupKey, epoch: i64, aad: &[u8], plaintext: &[u8]) -> Result<Self> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XChaCha20Poly1305::new(key.into())
+            .encrypt(&nonce, Payload { msg: plaintext, aad })
+            .map_err(|_| ErrorKind::InvalidKey("could not seal the message".to_string()))?;
+
+        Ok(Self::from((epoch, nonce.to_vec(), ciphertext)))
+    }
+
+    pub fn open_bytes(&self, key: &GroupKey, aad: &[u8]) -> Result<Vec<u8>> {
+        XChaCha20Poly1305::new(key.into())
+            .decrypt(nonce(&self.nonce)?, Payload { msg: &self.ciphertext, aad })
+            .map_err(|_| ErrorKind::InvalidKey(format!("could not open a message of epoch {}", self.epoch)).into())
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    pub fn from_json(data: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(data)
+    }
+}
+
+/// A group key encrypted to the room identity of one member.
+///
+/// The key is wrapped with a secret agreed between a throwaway key and the member's public key,
+/// so only the holder of the matching private key can unwrap it.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct SealedKey {
+    /// The member the key is sealed to.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub peer_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub epoch: i64,
+
+    /// The SEC1 encoding of the throwaway public key.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub ephemeral: Vec<u8>,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub nonce: Vec<u8>,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub ciphertext: Vec<u8>,
+}
+
+impl SealedKey {
+    pub fn seal(recipient: &identity::PublicKey, room_id: &str, epoch: i64, key: &GroupKey) -> Result<Self> {
+        let recipient_key = p256::PublicKey::from_sec1_bytes(&recipient.clone().try_into_ecdsa()?.to_bytes())
+            .map_err(|_| ErrorKind::InvalidKey("the member's public key is not a P-256 point".to_string()))?;
+        let peer_id = recipient.to_peer_id().to_string();
+
+        let ephemeral = EphemeralSecret::random(&mut OsRng);
+        let ephemeral_public = ephemeral.public_key().to_sec1_bytes().to_vec();
+        let wrap_key = wrap_key(&ephemeral.diffie_hellman(&recipient_key))?;
+
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = Self::associated_data(room_id, epoch, &peer_id);
+        let ciphertext = XChaCha20Poly1305::new(&wrap_key.into())
+            .encrypt(&nonce, Payload { msg: key, aad: &aad })
+            .map_err(|_| ErrorKind::InvalidKey("could not seal the group key".to_string()))?;
+
+        Ok(Self::from((peer_id, epoch, ephemeral_public, nonce.to_vec(), ciphertext)))
+    }
+
+    /// Unwraps the group key with the private key of the room identity it was sealed to.
+    pub fn open(&self, keypair: &identity::Keypair, room_id: &str) -> Result<GroupKey> {
+        let invalid = |reason: &str| Error::from(ErrorKind::InvalidKey(reason.to_string()));
+
+        let secret = p256::SecretKey::from_slice(&keypair.clone().try_into_ecdsa()?.secret().to_bytes())
+            .map_err(|_| invalid("the room key is not a P-256 scalar"))?;
+        let ephemeral = p256::PublicKey::from_sec1_bytes(&self.ephemeral)
+            .map_err(|_| invalid("malformed ephemeral key"))?;
+        let shared = p256::ecdh::diffie_hellman(secret.to_nonzero_scalar(), ephemeral.as_affine());
+        let wrap_key = wrap_key(&shared)?;
+
+        let aad = Self::associated_data(room_id, self.epoch, &self.peer_id);
+        XChaCha20Poly1305::new(&wrap_key.into())
+            .decrypt(nonce(&self.nonce)?, Payload { msg: &self.ciphertext, aad: &aad })
+            .map_err(|_| invalid("the group key was not sealed to this room identity"))?
+            .try_into()
+            .map_err(|_| invalid("malformed group key"))
+    }
+
+    fn associated_data(room_id: &str, epoch: i64, peer_id: &str) -> Vec<u8> {
+        [room_id.as_bytes(), &[0], &epoch.to_be_bytes(), peer_id.as_bytes()].concat()
+    }
+}
+
+fn wrap_key(shared: &p256::ecdh::SharedSecret) -> Result<GroupKey> {
+    let mut key = [0u8; GROUP_KEY_SIZE];
+    shared.extract::<Sha256>(None)
+        .expand(WRAP_INFO, &mut key)
+        .map_err(|_| ErrorKind::InvalidKey("could not derive the wrapping key".to_string()))?;
+    Ok(key)
+}
+
+fn nonce(bytes: &[u8]) -> Result<&XNonce> {
+    if bytes.len() != 24 {
+        return Err(ErrorKind::InvalidKey("malformed nonce".to_string()).into());
+    }
+    Ok(XNonce::from_slice(bytes))
+}
+
+/// Asks a member for the group key of a room, sealed to the room identity of `public_key`.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct GroupKeyRequest {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    /// The current epoch when `None`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub epoch: Option<i64>,
+
+    /// The protobuf encoding of the requester's room public key.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub public_key: Vec<u8>,
+}
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct GroupKeyResponse {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub key: Option<SealedKey>,
+
+    /// Why no key was handed out; `None` when `key` is set.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub rejection: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_round_trip() {
+        let key = [3u8; GROUP_KEY_SIZE];
+        let topic = TopicHash::from_raw("/vichiz/room/a/chat");
+
+        let sealed = SealedPayload::seal(&key, 2, &topic, "hello").unwrap();
+        let decoded = SealedPayload::from_json(&sealed.to_json().unwrap()).unwrap();
+
+        assert_eq!(decoded.open(&key, &topic).unwrap(), "hello");
+        assert!(decoded.open(&[4u8; GROUP_KEY_SIZE], &topic).is_err());
+        assert!(decoded.open(&key, &TopicHash::from_raw("/vichiz/room/b/chat")).is_err());
+    }
+
+    #[test]
+    fn test_key_only_opens_for_its_member() {
+        let member = identity::Keypair::generate_ecdsa();
+        let outsider = identity::Keypair::generate_ecdsa();
+        let key = [9u8; GROUP_KEY_SIZE];
+
+        let sealed = SealedKey::seal(&member.public(), "room", 1, &key).unwrap();
+
+        assert_eq!(sealed.open(&member, "room").unwrap(), key);
+        assert_eq!(sealed.open(&outsider, "room").unwrap_err().code(), "CRYPTO_ERROR");
+        assert!(sealed.open(&member, "other-room").is_err());
+    }
+}
@@ -0,0 +1,205 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use derive_more::From;
+use getset::*;
+use libp2p::identity;
+use serde::*;
+
+use crate::entities::member::Member;
+use crate::models::error::*;
+use crate::models::group_key::SealedKey;
+use crate::services::noise_key_service::PSK_SIZE;
+
+/// What every invite link starts with.
+pub const INVITE_PREFIX: &str = "vichiz://join/";
+
+/// What an invite link tells the joiner about the room.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct Invitation {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub invite_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_name: String,
+
+    /// The swarm that issued the invite, and redeems it.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub inviter: String,
+
+    /// Where the inviter can be dialed, each ending with `/p2p/<peer id>`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub addresses: Vec<String>,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub rendezvous_nodes: Vec<String>,
+
+    /// The room's pre-shared key in unpadded URL-safe base64, when it is a private network.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub secret: Option<String>,
+
+    /// Milliseconds since the unix epoch.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub expires_at: Option<i64>,
+}
+
+impl Invitation {
+    /// `vichiz://join/<payload>.<public key>.<signature>`, each part in unpadded URL-safe base64.
+    pub fn encode(&self, keypair: &identity::Keypair) -> Result<String> {
+        let payload = serde_json::to_vec(self)?;
+        let signature = keypair.sign(&payload)
+            .map_err(|e| ErrorKind::InvalidSignature(e.to_string()))?;
+
+        Ok(format!(
+            "{INVITE_PREFIX}{}.{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(keypair.public().encode_protobuf()),
+            URL_SAFE_NO_PAD.encode(signature),
+        ))
+    }
+
+    pub fn encode_secret(psk: &[u8; PSK_SIZE]) -> String {
+        URL_SAFE_NO_PAD.encode(psk)
+    }
+
+    /// The pre-shared key joining the room requires, if any.
+    pub fn psk(&self) -> Result<Option<[u8; PSK_SIZE]>> {
+        self.secret.as_ref()
+            .map(|secret| URL_SAFE_NO_PAD.decode(secret).ok()
+                .and_then(|key| key.try_into().ok())
+                .ok_or_else(|| ErrorKind::InvalidInvite("malformed room secret".to_string()).into()))
+            .transpose()
+    }
+
+    /// Checks the signature and the expiry of `token`.
+    pub fn decode(token: &str, now: i64) -> Result<Self> {
+        let invalid = |reason: &str| Error::from(ErrorKind::InvalidInvite(reason.to_string()));
+
+        let parts = token.trim()
+            .strip_prefix(INVITE_PREFIX)
+            .ok_or_else(|| invalid("not a vichiz invite link"))?
+            .split('.')
+            .map(|part| URL_SAFE_NO_PAD.decode(part))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| invalid("malformed link"))?;
+        let [payload, public_key, signature] = parts.as_slice() else {
+            return Err(invalid("malformed link"));
+        };
+
+        let public_key = identity::PublicKey::try_decode_protobuf(public_key)?;
+        if !public_key.verify(payload, signature) {
+            return Err(invalid("the signature does not match"));
+        }
+
+        let invitation: Self = serde_json::from_slice(payload).map_err(|_| invalid("malformed payload"))?;
+        if invitation.expires_at.is_some_and(|expires| expires <= now) {
+            return Err(invalid("the invite expired"));
+        }
+
+        Ok(invitation)
+    }
+}
+
+/// Asks the inviter to count one use of an invite.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct InviteRequest {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub invite_id: String,
+
+    /// The room identity of the joiner, which the inviter adds to the room's members.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub peer_id: String,
+
+    /// The protobuf encoding of the joiner's room public key, to seal the room's group key to.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub public_key: Vec<u8>,
+}
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct InviteResponse {
+    /// Why the invite was refused; `None` when it was accepted.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub rejection: Option<String>,
+
+    /// The room's members, the joiner included, when the invite was accepted.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub members: Vec<Member>,
+
+    /// The room's current group key, sealed to the joiner.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub group_key: Option<SealedKey>,
+}
+
+#[derive(PartialEq, Default, Getters, MutGetters, Setters, Serialize, Debug, Deserialize)]
+pub struct InviteOptions {
+    /// Milliseconds since the unix epoch after which the invite is refused.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub expires: Option<i64>,
+
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub max_uses: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invitation(expires_at: Option<i64>) -> Invitation {
+        Invitation::from((
+            "invite".to_string(),
+            "room".to_string(),
+            "Room".to_string(),
+            "12D3KooW".to_string(),
+            vec!["/ip4/127.0.0.1/tcp/4001".to_string()],
+            vec![],
+            None,
+            expires_at,
+        ))
+    }
+
+    #[test]
+    fn test_invite_round_trip() {
+        let keypair = identity::Keypair::generate_ecdsa();
+        let token = invitation(Some(2_000)).encode(&keypair).unwrap();
+
+        assert!(token.starts_with(INVITE_PREFIX));
+        assert_eq!(Invitation::decode(&token, 1_000).unwrap(), invitation(Some(2_000)));
+    }
+
+    #[test]
+    fn test_invite_rejects_tampering_and_expiry() {
+        let keypair = identity::Keypair::generate_ecdsa();
+        let token = invitation(Some(2_000)).encode(&keypair).unwrap();
+
+        let forged = invitation(None).encode(&keypair).unwrap();
+        let (payload, _) = forged[INVITE_PREFIX.len()..].split_once('.').unwrap();
+        let (_, signed) = token[INVITE_PREFIX.len()..].split_once('.').unwrap();
+        let tampered = format!("{INVITE_PREFIX}{payload}.{signed}");
+
+        assert_eq!(Invitation::decode(&tampered, 1_000).unwrap_err().code(), "INVALID_INVITE");
+        assert!(Invitation::decode(&token, 2_000).is_err());
+        assert!(Invitation::decode("https://example.com", 1_000).is_err());
+    }
+
+    #[test]
+    fn test_invite_carries_the_room_secret() {
+        let psk = [7u8; PSK_SIZE];
+        let mut invitation = invitation(None);
+        assert_eq!(invitation.psk().unwrap(), None);
+
+        invitation.secret = Some(Invitation::encode_secret(&psk));
+        assert_eq!(invitation.psk().unwrap(), Some(psk));
+
+        invitation.secret = Some("c2hvcnQ".to_string());
+        assert_eq!(invitation.psk().unwrap_err().code(), "INVALID_INVITE");
+    }
+}
@@ -0,0 +1,9 @@
+use derive_more::From;
+use getset::*;
+use serde::*;
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize)]
+pub struct ListenerId {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub id: String,
+}
@@ -0,0 +1,134 @@
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use derive_more::From;
+use getset::*;
+use serde::*;
+use zeroize::Zeroizing;
+
+use crate::models::error::*;
+
+pub const MASTER_KEY_SIZE: usize = 32;
+
+/// The key everything sealed at rest is sealed with; wiped from memory once dropped.
+pub type MasterKey = Zeroizing<[u8; MASTER_KEY_SIZE]>;
+
+/// Argon2id with the crate's default parameters; other parameters need another name.
+pub const PASSPHRASE_KDF: &str = "argon2id";
+
+/// The host hands the master key over as is.
+pub const HOST_KDF: &str = "host";
+
+const NONCE_SIZE: usize = 24;
+
+/// What `unlockSdk` takes: either a passphrase or the master key itself.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Deserialize, Default)]
+pub struct UnlockData {
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub passphrase: Option<String>,
+
+    /// 32 bytes in standard base64, kept by the host, for instance with Electron's `safeStorage`.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub master_key: Option<String>,
+}
+
+/// Where the master key comes from.
+pub enum MasterSecret {
+    Passphrase(Zeroizing<String>),
+    Host(MasterKey),
+}
+
+impl MasterSecret {
+    pub fn host(encoded: &str) -> Result<Self> {
+        let bytes = Zeroizing::new(STANDARD.decode(encoded)
+            .map_err(|_| ErrorKind::InvalidArgument("the master key is not base64".to_string()))?);
+        let key: [u8; MASTER_KEY_SIZE] = bytes.as_slice().try_into()
+            .map_err(|_| ErrorKind::InvalidArgument(format!("the master key must be {MASTER_KEY_SIZE} bytes")))?;
+
+        Ok(MasterSecret::Host(Zeroizing::new(key)))
+    }
+
+    pub fn kdf(&self) -> &'static str {
+        match self {
+            MasterSecret::Passphrase(_) => PASSPHRASE_KDF,
+            MasterSecret::Host(_) => HOST_KDF,
+        }
+    }
+
+    /// The master key; a passphrase is stretched with `salt`, so guessing it is slow.
+    pub fn derive(&self, salt: &[u8]) -> Result<MasterKey> {
+        match self {
+            MasterSecret::Host(key) => Ok(key.clone()),
+            MasterSecret::Passphrase(passphrase) => {
+                let mut key = Zeroizing::new([0u8; MASTER_KEY_SIZE]);
+                Argon2::default()
+                    .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+                    .map_err(|e| ErrorKind::InvalidKey(format!("could not derive the master key: {e}")))?;
+                Ok(key)
+            }
+        }
+    }
+}
+
+impl TryFrom<UnlockData> for MasterSecret {
+    type Error = Error;
+
+    fn try_from(data: UnlockData) -> Result<Self> {
+        match (data.passphrase, data.master_key) {
+            (Some(passphrase), None) if !passphrase.is_empty() => Ok(MasterSecret::Passphrase(Zeroizing::new(passphrase))),
+            (None, Some(master_key)) => MasterSecret::host(&master_key),
+            _ => Err(ErrorKind::InvalidArgument("pass either a non-empty passphrase or a master key".to_string()).into()),
+        }
+    }
+}
+
+/// The nonce followed by the ciphertext, bound to whatever `aad` says it is.
+pub fn seal_at_rest(key: &MasterKey, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+        .encrypt(&nonce, Payload { msg: plaintext, aad })
+        .map_err(|_| ErrorKind::InvalidKey("could not seal a key".to_string()))?;
+
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+pub fn open_at_rest(key: &MasterKey, aad: &[u8], sealed: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    if sealed.len() < NONCE_SIZE {
+        return Err(ErrorKind::InvalidKey("malformed sealed key".to_string()).into());
+    }
+
+    let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+    XChaCha20Poly1305::new(key.as_ref().into())
+        .decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+        .map(Zeroizing::new)
+        .map_err(|_| ErrorKind::InvalidKey("could not open a sealed key".to_string()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sealed_keys_only_open_where_they_were_sealed() {
+        let key = MasterSecret::host(&STANDARD.encode([7u8; MASTER_KEY_SIZE])).unwrap().derive(b"salt").unwrap();
+        let sealed = seal_at_rest(&key, b"room", b"secret").unwrap();
+
+        assert_eq!(open_at_rest(&key, b"room", &sealed).unwrap().as_slice(), b"secret");
+        assert!(open_at_rest(&key, b"other", &sealed).is_err());
+        assert!(open_at_rest(&Zeroizing::new([8u8; MASTER_KEY_SIZE]), b"room", &sealed).is_err());
+    }
+
+    #[test]
+    fn test_unlock_data_takes_one_secret() {
+        let both = UnlockData::from((Some("pass".to_string()), Some(STANDARD.encode([7u8; MASTER_KEY_SIZE]))));
+        let short = UnlockData::from((None, Some(STANDARD.encode([7u8; 4]))));
+
+        assert!(MasterSecret::try_from(UnlockData::from((Some("pass".to_string()), None))).is_ok());
+        assert_eq!(MasterSecret::try_from(both).err().unwrap().code(), "INVALID_ARGUMENT");
+        assert_eq!(MasterSecret::try_from(short).err().unwrap().code(), "INVALID_ARGUMENT");
+    }
+}
@@ -0,0 +1,50 @@
+use derive_more::From;
+use getset::*;
+use serde::*;
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MemberRole {
+    /// Adds and removes members.
+    Owner,
+    #[default]
+    Member,
+}
+
+impl MemberRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemberRole::Owner => "owner",
+            MemberRole::Member => "member",
+        }
+    }
+}
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize)]
+pub struct MemberData {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    /// The room identity of the member, as reported by `getPeers` and message events.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub peer_id: String,
+
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub role: MemberRole,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_member_role_serializes_as_str() {
+        for role in [MemberRole::Owner, MemberRole::Member] {
+            assert_eq!(serde_json::to_value(role).unwrap(), role.as_str());
+        }
+
+        let data: MemberData = serde_json::from_str(r#"{"room_id":"room","peer_id":"peer"}"#).unwrap();
+        assert_eq!(data.role, MemberRole::Member);
+    }
+}
@@ -0,0 +1,53 @@
+use derive_more::From;
+use getset::*;
+use serde::*;
+
+/// How a stored message reached this node.
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageStatus {
+    /// Published by this node.
+    Sent,
+    /// Delivered live over gossip.
+    Received,
+    /// Fetched from another member after this node missed it.
+    Synced,
+}
+
+impl MessageStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageStatus::Sent => "sent",
+            MessageStatus::Received => "received",
+            MessageStatus::Synced => "synced",
+        }
+    }
+}
+
+/// A page of a room's history; the latest messages when nothing is set.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Default)]
+pub struct MessageQuery {
+    /// Only messages sent strictly before this time, in milliseconds since the unix epoch.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub before: Option<i64>,
+
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub limit: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_status_serializes_as_str() {
+        for status in [MessageStatus::Sent, MessageStatus::Received, MessageStatus::Synced] {
+            assert_eq!(serde_json::to_value(status).unwrap(), status.as_str());
+        }
+
+        let query: MessageQuery = serde_json::from_str("{}").unwrap();
+        assert_eq!(query, MessageQuery::default());
+    }
+}
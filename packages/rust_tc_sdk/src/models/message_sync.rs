@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use derive_more::From;
+use getset::*;
+use libp2p::gossipsub::TopicHash;
+use libp2p::PeerId;
+use serde::*;
+
+use crate::models::error::*;
+
+use crate::models::group_key::SealedPayload;
+use crate::models::signed_envelope::SignedEnvelope;
+
+/// A place in the history of a room; messages sent in the same millisecond are told apart by id.
+#[derive(PartialEq, Eq, PartialOrd, Ord, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct SyncMark {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub sent_at: i64,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub id: String,
+}
+
+/// Asks a member for the messages of a room this node has not seen.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct SyncRequest {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    /// The latest message held from each sender; senders missing here are sent in full.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub marks: HashMap<String, SyncMark>,
+
+    /// Where the previous batch ended; `None` for the first one.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub after: Option<SyncMark>,
+}
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct SyncResponse {
+    /// The JSON list of `SyncedMessage`s, sealed with the room's current group key.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub batch: Option<SealedPayload>,
+
+    /// Where the next batch starts; `None` once nothing is left.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub next: Option<SyncMark>,
+
+    /// Why nothing was sent; `None` when `batch` is set.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub rejection: Option<String>,
+}
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct SyncedMessage {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub sender_peer_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub topic: String,
+
+    /// The envelope the author published the message in, which the requester checks the claimed sender against.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub envelope: SignedEnvelope,
+}
+
+impl SyncedMessage {
+    /// The author of the message, as long as it is the sender the member who served it claims.
+    pub fn author(&self, topic: &TopicHash) -> Result<PeerId> {
+        let author = self.envelope.open(topic)?;
+        if author.to_string() != self.sender_peer_id {
+            return Err(ErrorKind::InvalidSignature(format!("signed by {author}, not by {}", self.sender_peer_id)).into());
+        }
+        Ok(author)
+    }
+}
+
+/// What a sync batch is bound to, so it cannot pass for a message of any topic.
+pub fn sync_scope(room_id: &str) -> TopicHash {
+    TopicHash::from_raw(format!("/vichiz/room/{room_id}/sync"))
+}
+
+#[cfg(test)]
+mod tests {
+    use libp2p::identity::Keypair;
+
+    use crate::models::group_key::GROUP_KEY_SIZE;
+
+    use super::*;
+
+    #[test]
+    fn test_batch_only_opens_for_its_room() {
+        let key = [5u8; GROUP_KEY_SIZE];
+        let envelope = SignedEnvelope::from((vec![1], "sealed".to_string(), vec![2]));
+        let messages = vec![SyncedMessage::from(("id".to_string(), "peer".to_string(), "chat".to_string(), envelope))];
+        let json = serde_json::to_string(&messages).unwrap();
+
+        let batch = SealedPayload::seal(&key, 1, &sync_scope("room"), &json).unwrap();
+
+        let opened: Vec<SyncedMessage> = serde_json::from_str(&batch.open(&key, &sync_scope("room")).unwrap()).unwrap();
+        assert_eq!(opened, messages);
+        assert!(batch.open(&key, &sync_scope("other")).is_err());
+    }
+
+    #[test]
+    fn test_synced_message_proves_its_sender() {
+        let (author, impostor) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let topic = TopicHash::from_raw("/vichiz/room/room/chat");
+        let synced = |sender: &Keypair, envelope: SignedEnvelope| SyncedMessage::from((
+            "id".to_string(),
+            sender.public().to_peer_id().to_string(),
+            topic.to_string(),
+            envelope,
+        ));
+        let envelope = SignedEnvelope::seal(&author, &topic, "sealed".to_string()).unwrap();
+
+        assert_eq!(synced(&author, envelope.clone()).author(&topic).unwrap(), author.public().to_peer_id());
+        assert_eq!(synced(&impostor, envelope.clone()).author(&topic).unwrap_err().code(), "CRYPTO_ERROR");
+
+        let forged = SignedEnvelope { payload: "forged".to_string(), ..envelope };
+        assert_eq!(synced(&author, forged).author(&topic).unwrap_err().code(), "CRYPTO_ERROR");
+    }
+}
@@ -5,3 +5,24 @@ pub(crate) mod behaviour;
 pub(crate) mod rust_sdk_options;
 pub(crate) mod room_id;
 pub(crate) mod callback_payload;
+pub(crate) mod listener_id;
+pub(crate) mod room_message;
+pub(crate) mod send_message_data;
+pub(crate) mod room_topics;
+pub(crate) mod signal;
+pub(crate) mod call_state;
+pub(crate) mod call_data;
+pub(crate) mod room_control;
+pub(crate) mod network_status;
+pub(crate) mod transport;
+pub(crate) mod signed_envelope;
+pub(crate) mod peer_data;
+pub(crate) mod invitation;
+pub(crate) mod member;
+pub(crate) mod group_key;
+pub(crate) mod message;
+pub(crate) mod message_sync;
+pub(crate) mod file_transfer;
+pub(crate) mod master_key;
+pub(crate) mod rekey_data;
+pub(crate) mod backup;
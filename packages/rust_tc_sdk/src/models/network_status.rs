@@ -0,0 +1,56 @@
+use derive_more::From;
+use getset::*;
+use libp2p::autonat::NatStatus;
+use serde::*;
+
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Reachability {
+    Public,
+    Private,
+    Unknown,
+}
+
+impl From<&NatStatus> for Reachability {
+    fn from(status: &NatStatus) -> Self {
+        match status {
+            NatStatus::Public(_) => Reachability::Public,
+            NatStatus::Private => Reachability::Private,
+            NatStatus::Unknown => Reachability::Unknown,
+        }
+    }
+}
+
+/// How reachable a room's node is, as last determined by AutoNAT.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct NetworkStatus {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub reachability: Reachability,
+
+    /// The address AutoNAT confirmed when the node is public.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub public_address: Option<String>,
+
+    /// Every address other peers can dial us at, relayed ones included.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub external_addresses: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use libp2p::Multiaddr;
+
+    use super::*;
+
+    #[test]
+    fn test_reachability_from_nat_status() {
+        let address: Multiaddr = "/ip4/1.2.3.4/tcp/4000".parse().unwrap();
+
+        assert_eq!(Reachability::from(&NatStatus::Public(address)), Reachability::Public);
+        assert_eq!(Reachability::from(&NatStatus::Private), Reachability::Private);
+        assert_eq!(serde_json::to_value(Reachability::Unknown).unwrap(), "unknown");
+    }
+}
@@ -0,0 +1,10 @@
+use derive_more::From;
+use getset::*;
+use serde::*;
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Deserialize)]
+pub struct RekeyData {
+    /// The SQLCipher key the database is encrypted with from now on.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub key: String,
+}
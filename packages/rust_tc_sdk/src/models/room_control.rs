@@ -0,0 +1,92 @@
+use serde::*;
+
+use crate::models::group_key::SealedKey;
+use crate::models::member::MemberRole;
+
+/// Messages room members exchange on the room's control topic.
+#[derive(PartialEq, Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum RoomControl {
+    Call(CallControl),
+    Member(MemberControl),
+    Key(GroupKeyControl),
+}
+
+#[derive(PartialEq, Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum CallControl {
+    Invite { call_id: String },
+    Accept { call_id: String },
+    Reject { call_id: String, reason: String },
+    HangUp { call_id: String },
+}
+
+/// A change to the room's member list; members only apply the ones an owner signed.
+#[derive(PartialEq, Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum MemberControl {
+    Added {
+        peer_id: String,
+        role: MemberRole,
+        added_at: i64,
+        /// The protobuf encoding of the member's room public key, when the owner knows it.
+        #[serde(default)]
+        public_key: Option<Vec<u8>>,
+    },
+    Removed { peer_id: String },
+}
+
+/// A new group key epoch, sealed to each member an owner knows the public key of.
+#[derive(PartialEq, Serialize, Deserialize, Debug, Clone)]
+pub struct GroupKeyControl {
+    pub epoch: i64,
+    pub keys: Vec<SealedKey>,
+}
+
+impl CallControl {
+    pub fn call_id(&self) -> &str {
+        match self {
+            CallControl::Invite { call_id }
+            | CallControl::Accept { call_id }
+            | CallControl::Reject { call_id, .. }
+            | CallControl::HangUp { call_id } => call_id,
+        }
+    }
+}
+
+impl RoomControl {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    pub fn from_json(data: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_room_control_wire_format() {
+        let control = RoomControl::Call(CallControl::Reject { call_id: "c".to_string(), reason: "busy".to_string() });
+
+        let value: serde_json::Value = serde_json::from_str(&control.to_json().unwrap()).unwrap();
+
+        assert_eq!(value["kind"], "call");
+        assert_eq!(value["action"], "reject");
+        assert_eq!(RoomControl::from_json(&control.to_json().unwrap()).unwrap(), control);
+    }
+
+    #[test]
+    fn test_member_control_wire_format() {
+        let control = RoomControl::Member(MemberControl::Added { peer_id: "p".to_string(), role: MemberRole::Owner, added_at: 1, public_key: None });
+
+        let value: serde_json::Value = serde_json::from_str(&control.to_json().unwrap()).unwrap();
+
+        assert_eq!(value["kind"], "member");
+        assert_eq!(value["action"], "added");
+        assert_eq!(value["role"], "owner");
+    }
+}
@@ -0,0 +1,58 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use derive_more::From;
+use getset::*;
+use serde::*;
+
+use crate::models::file_transfer::FileOffer;
+
+/// What actually travels over gossipsub for an application message.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct RoomMessage {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub payload: String,
+
+    /// Milliseconds since the unix epoch, as seen by the sender.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub sent_at: i64,
+
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub file: Option<FileOffer>,
+}
+
+impl RoomMessage {
+    pub fn new(payload: String, file: Option<FileOffer>) -> Self {
+        Self::from((payload, now_millis(), file))
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    pub fn from_json(data: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(data)
+    }
+}
+
+pub fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_room_message_round_trip() {
+        let message = RoomMessage::new("hello".to_string(), None);
+
+        let decoded = RoomMessage::from_json(&message.to_json().unwrap()).unwrap();
+
+        assert_eq!(decoded, message);
+        assert!(decoded.sent_at > 0);
+    }
+}
@@ -9,6 +9,14 @@ pub struct RoomOption {
 
     #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
     pub name: String,
+
+    /// Guards the room's swarm with a pre-shared key, so only peers holding it complete a handshake.
+    ///
+    /// Relays and rendezvous servers do not hold the key either, so such a room is only reached
+    /// through the direct addresses its invites carry.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub private: bool,
 }
 
 #[cfg(test)]
@@ -19,7 +27,7 @@ mod tests {
     fn test_room_option_new_with_id() {
         let name = "Test Room";
 
-        let room_option = RoomOption::from((None, name.to_string()));
+        let room_option = RoomOption::from((None, name.to_string(), false));
 
         assert_eq!(room_option.id, None);
         assert_eq!(room_option.name, name.to_string());
@@ -29,9 +37,16 @@ mod tests {
     fn test_room_option_new_without_id() {
         let name = "Test Room";
 
-        let room_option = RoomOption::from((None, name.to_string()));
+        let room_option = RoomOption::from((None, name.to_string(), false));
 
         assert_eq!(room_option.id, None);
         assert_eq!(room_option.name, name.to_string());
     }
+
+    #[test]
+    fn test_room_option_is_public_by_default() {
+        let room_option: RoomOption = serde_json::from_str(r#"{"name":"Test Room"}"#).unwrap();
+
+        assert!(!room_option.private);
+    }
 }
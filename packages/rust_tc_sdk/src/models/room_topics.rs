@@ -0,0 +1,89 @@
+use libp2p::gossipsub::{IdentTopic, TopicHash};
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum RoomTopicKind {
+    Control,
+    Chat,
+    Signaling,
+}
+
+/// The gossipsub topics owned by one room, all derived from its ID.
+#[derive(Debug, Clone)]
+pub struct RoomTopics {
+    control: IdentTopic,
+    chat: IdentTopic,
+    signaling: IdentTopic,
+}
+
+impl RoomTopics {
+    pub fn new(room_id: &str) -> Self {
+        let topic = |kind: &str| IdentTopic::new(format!("/vichiz/room/{room_id}/{kind}"));
+
+        Self {
+            control: topic("control"),
+            chat: topic("chat"),
+            signaling: topic("signaling"),
+        }
+    }
+
+    /// A topic members of `room_id` subscribe to on demand, named by the application.
+    pub fn custom(room_id: &str, name: &str) -> IdentTopic {
+        IdentTopic::new(format!("/vichiz/room/{room_id}/topic/{name}"))
+    }
+
+    pub fn get(&self, kind: RoomTopicKind) -> &IdentTopic {
+        match kind {
+            RoomTopicKind::Control => &self.control,
+            RoomTopicKind::Chat => &self.chat,
+            RoomTopicKind::Signaling => &self.signaling,
+        }
+    }
+
+    pub fn all(&self) -> [&IdentTopic; 3] {
+        [&self.control, &self.chat, &self.signaling]
+    }
+
+    /// Which of this room's topics `hash` is, if any.
+    pub fn kind_of(&self, hash: &TopicHash) -> Option<RoomTopicKind> {
+        [RoomTopicKind::Control, RoomTopicKind::Chat, RoomTopicKind::Signaling]
+            .into_iter()
+            .find(|kind| &self.get(*kind).hash() == hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topics_are_scoped_to_room() {
+        let first = RoomTopics::new("room-a");
+        let second = RoomTopics::new("room-b");
+
+        let chat = first.get(RoomTopicKind::Chat).hash();
+
+        assert_eq!(first.kind_of(&chat), Some(RoomTopicKind::Chat));
+        assert_eq!(second.kind_of(&chat), None);
+    }
+
+    #[test]
+    fn test_custom_topics_are_scoped_to_room() {
+        let topics = RoomTopics::new("room-a");
+
+        let custom = RoomTopics::custom("room-a", "chat");
+
+        assert_ne!(custom.hash(), RoomTopics::custom("room-b", "chat").hash());
+        assert_eq!(topics.kind_of(&custom.hash()), None);
+    }
+
+    #[test]
+    fn test_topic_kinds_are_distinct() {
+        let topics = RoomTopics::new("room-a");
+
+        let control = topics.get(RoomTopicKind::Control).hash();
+        let signaling = topics.get(RoomTopicKind::Signaling).hash();
+
+        assert_ne!(control, signaling);
+        assert_eq!(topics.kind_of(&signaling), Some(RoomTopicKind::Signaling));
+    }
+}
@@ -9,6 +9,28 @@ pub struct RustSDKOptions {
 
     #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
     pub log_level: Option<String>,
+
+    /// Runs every room on one process-wide swarm instead of one swarm per room.
+    /// Private rooms keep a swarm of their own, as their pre-shared key guards the whole swarm.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub")]
+    pub shared_swarm: bool,
+
+    /// Where downloaded files land; the user's download directory when missing.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub download_dir: Option<String>,
+
+    /// The master key the private keys are sealed with, 32 bytes in standard base64, for a host that
+    /// keeps it itself; otherwise `unlockSdk` derives it from a passphrase.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub master_key: Option<String>,
+
+    /// Encrypts the whole database with SQLCipher under this key; a plaintext database is encrypted on start.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub db_key: Option<String>,
 }
 
 
@@ -20,8 +42,15 @@ mod tests {
     fn test_rust_sdk_options_new() {
         let db_url = "sqlite://test.db";
 
-        let options = RustSDKOptions::from((Some(db_url.to_string()), None));
+        let options = RustSDKOptions::from((Some(db_url.to_string()), None, false, None, None, None));
 
         assert_eq!(options.db_url.unwrap(), db_url);
     }
+
+    #[test]
+    fn test_shared_swarm_defaults_to_off() {
+        let options: RustSDKOptions = serde_json::from_str(r#"{"db_url": null, "log_level": null}"#).unwrap();
+
+        assert!(!options.shared_swarm);
+    }
 }
@@ -0,0 +1,17 @@
+use derive_more::From;
+use getset::*;
+use serde::*;
+
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize)]
+pub struct SendMessageData {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub room_id: String,
+
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub payload: String,
+
+    /// One of the room's custom topics; the room's chat when missing.
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub topic: Option<String>,
+}
@@ -0,0 +1,85 @@
+use derive_more::From;
+use getset::*;
+use libp2p::gossipsub::TopicHash;
+use libp2p::{identity, PeerId};
+use serde::*;
+
+use crate::models::error::*;
+
+/// A room payload signed with the room's own identity.
+///
+/// Gossipsub only authenticates the swarm that published a message; when several rooms share
+/// one swarm the envelope is what tells members which room identity authored it.
+#[derive(PartialEq, From, Getters, MutGetters, Setters, Serialize, Debug, Deserialize, Clone)]
+pub struct SignedEnvelope {
+    /// The protobuf encoding of the author's public key.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub author: Vec<u8>,
+
+    /// The serialized `RoomMessage` or `RoomControl`.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub payload: String,
+
+    /// Covers the topic as well, so an envelope cannot be replayed into another room.
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub", get = "pub")]
+    pub signature: Vec<u8>,
+}
+
+impl SignedEnvelope {
+    pub fn seal(keypair: &identity::Keypair, topic: &TopicHash, payload: String) -> Result<Self> {
+        let signature = keypair.sign(&Self::signed_bytes(topic, &payload))
+            .map_err(|e| ErrorKind::InvalidSignature(e.to_string()))?;
+
+        Ok(Self::from((keypair.public().encode_protobuf(), payload, signature)))
+    }
+
+    /// Checks the signature and returns the room identity that authored the payload.
+    pub fn open(&self, topic: &TopicHash) -> Result<PeerId> {
+        let author = identity::PublicKey::try_decode_protobuf(&self.author)?;
+        if !author.verify(&Self::signed_bytes(topic, &self.payload), &self.signature) {
+            return Err(ErrorKind::InvalidSignature("signature does not match the payload".to_string()).into());
+        }
+
+        Ok(author.to_peer_id())
+    }
+
+    pub fn to_bytes(&self) -> serde_json::Result<Vec<u8>> {
+        serde_json::to_vec(self)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> serde_json::Result<Self> {
+        serde_json::from_slice(data)
+    }
+
+    fn signed_bytes(topic: &TopicHash, payload: &str) -> Vec<u8> {
+        [topic.as_str().as_bytes(), &[0], payload.as_bytes()].concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let keypair = identity::Keypair::generate_ecdsa();
+        let topic = TopicHash::from_raw("/vichiz/room/a/chat");
+
+        let envelope = SignedEnvelope::seal(&keypair, &topic, "hello".to_string()).unwrap();
+        let decoded = SignedEnvelope::from_bytes(&envelope.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(decoded.open(&topic).unwrap(), keypair.public().to_peer_id());
+    }
+
+    #[test]
+    fn test_envelope_rejects_tampering() {
+        let keypair = identity::Keypair::generate_ecdsa();
+        let topic = TopicHash::from_raw("/vichiz/room/a/chat");
+        let mut envelope = SignedEnvelope::seal(&keypair, &topic, "hello".to_string()).unwrap();
+
+        assert!(envelope.open(&TopicHash::from_raw("/vichiz/room/b/chat")).is_err());
+
+        envelope.payload = "bye".to_string();
+        assert_eq!(envelope.open(&topic).unwrap_err().code(), "CRYPTO_ERROR");
+    }
+}
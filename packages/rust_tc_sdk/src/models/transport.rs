@@ -0,0 +1,45 @@
+use libp2p::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use serde::*;
+
+/// The transports a room can listen and dial on.
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone, Copy, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Tcp,
+    Quic,
+    Websocket,
+}
+
+impl Transport {
+    pub const ALL: [Transport; 3] = [Transport::Tcp, Transport::Quic, Transport::Websocket];
+
+    /// The transport a connection to `address` runs over; for a relayed address, the one to the relay.
+    pub fn of(address: &Multiaddr) -> Option<Transport> {
+        let mut transport = None;
+        for protocol in address.iter() {
+            match protocol {
+                Protocol::Ws(_) | Protocol::Wss(_) => return Some(Transport::Websocket),
+                Protocol::QuicV1 | Protocol::Quic => transport = Some(Transport::Quic),
+                Protocol::Tcp(_) if transport.is_none() => transport = Some(Transport::Tcp),
+                _ => {}
+            }
+        }
+        transport
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transport_of_address() {
+        let of = |address: &str| Transport::of(&address.parse().unwrap());
+
+        assert_eq!(of("/ip4/1.2.3.4/tcp/4001"), Some(Transport::Tcp));
+        assert_eq!(of("/ip4/1.2.3.4/udp/4001/quic-v1"), Some(Transport::Quic));
+        assert_eq!(of("/dns4/example.com/tcp/443/wss"), Some(Transport::Websocket));
+        assert_eq!(of("/ip4/1.2.3.4"), None);
+    }
+}
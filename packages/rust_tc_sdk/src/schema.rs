@@ -1,10 +1,107 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    blobs (hash) {
+        hash -> Text,
+        ref_count -> BigInt,
+        stored_at -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    calls (id) {
+        id -> Text,
+        room_id -> Text,
+        initiator -> Text,
+        direction -> Text,
+        state -> Text,
+        participants -> Text,
+        started_at -> BigInt,
+        answered_at -> Nullable<BigInt>,
+        ended_at -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    files (room_id, file_id) {
+        room_id -> Text,
+        file_id -> Text,
+        name -> Text,
+        size -> BigInt,
+        chunk_size -> BigInt,
+        chunk_root -> Text,
+        offered_by -> Text,
+        path -> Nullable<Text>,
+        created_at -> BigInt,
+        manifest -> Nullable<Binary>,
+    }
+}
+
+diesel::table! {
+    group_keys (room_id, epoch) {
+        room_id -> Text,
+        epoch -> BigInt,
+        key -> Binary,
+        created_at -> BigInt,
+        encrypted -> Bool,
+    }
+}
+
+diesel::table! {
+    invites (id) {
+        id -> Text,
+        room_id -> Text,
+        expires_at -> Nullable<BigInt>,
+        max_uses -> Nullable<Integer>,
+        uses -> Integer,
+        created_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    key_vault (id) {
+        id -> Integer,
+        kdf -> Text,
+        salt -> Binary,
+        verifier -> Binary,
+        created_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    messages (room_id, id) {
+        id -> Text,
+        room_id -> Text,
+        sender_peer_id -> Text,
+        topic -> Text,
+        payload -> Text,
+        sent_at -> BigInt,
+        received_at -> BigInt,
+        status -> Text,
+        file_id -> Nullable<Text>,
+        envelope -> Nullable<Binary>,
+    }
+}
+
 diesel::table! {
     noise_keys (id) {
         id -> Text,
         private -> Binary,
         public -> Binary,
+        psk -> Nullable<Binary>,
+        encrypted -> Bool,
+        psk_encrypted -> Bool,
+    }
+}
+
+diesel::table! {
+    room_members (room_id, peer_id) {
+        room_id -> Text,
+        peer_id -> Text,
+        role -> Text,
+        added_by -> Text,
+        added_at -> BigInt,
+        public_key -> Nullable<Binary>,
     }
 }
 
@@ -16,6 +113,14 @@ diesel::table! {
 }
 
 diesel::allow_tables_to_appear_in_same_query!(
+    blobs,
+    calls,
+    files,
+    group_keys,
+    invites,
+    key_vault,
+    messages,
     noise_keys,
+    room_members,
     rooms,
 );
@@ -0,0 +1,261 @@
+use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+
+use crate::entities::file::SharedFile;
+use crate::entities::group_key::GroupKeyModel;
+use crate::entities::member::Member;
+use crate::entities::message::Message;
+use crate::entities::noise::NoiseModel;
+use crate::entities::room::Room;
+use crate::models::backup::{Backup, BackupConflict, RestoredBackup, RoomBackup};
+use crate::models::error::*;
+use crate::models::room_message::now_millis;
+use crate::schema::{calls, files, group_keys, invites, messages, noise_keys, room_members, rooms};
+use crate::services::group_key_service::GroupKeyService;
+use crate::services::key_vault::KeyVault;
+use crate::services::message_service::{insert_message, release_room_messages};
+use crate::services::noise_key_service::NoiseKeyService;
+
+/// Takes every room, with its identity, members and history, out of the database and puts it back.
+#[derive(Debug)]
+pub struct BackupService {
+    db_pool: Pool<ConnectionManager<SqliteConnection>>,
+    keys: NoiseKeyService,
+    group_keys: GroupKeyService,
+}
+
+impl BackupService {
+    pub fn new(db_pool: Pool<ConnectionManager<SqliteConnection>>, vault: KeyVault) -> Self {
+        let keys = NoiseKeyService::new(db_pool.clone(), vault.clone());
+        let group_keys = GroupKeyService::new(db_pool.clone(), vault);
+        BackupService { db_pool, keys, group_keys }
+    }
+
+    /// Every room with its keys in the clear, which takes the SDK unlocked.
+    pub fn export(&self) -> Result<Backup> {
+        log::info!("Backing up every room");
+        let all_rooms: Vec<Room> = rooms::table.load(&mut self.db_pool.get()?)?;
+
+        let mut backed_up = Vec::with_capacity(all_rooms.len());
+        for room in all_rooms {
+            let key = self.keys.export_key(&room.id)?;
+            let keys = self.group_keys.export_keys(&room.id)?;
+            let mut conn = self.db_pool.get()?;
+            let members: Vec<Member> = room_members::table
+                .filter(room_members::room_id.eq(&room.id))
+                .load(&mut conn)?;
+            let history: Vec<Message> = messages::table
+                .filter(messages::room_id.eq(&room.id))
+                .order((messages::sent_at.asc(), messages::id.asc()))
+                .load(&mut conn)?;
+            // Manifests are left out, they are worked out again when a member asks.
+            let offers: Vec<SharedFile> = files::table
+                .filter(files::room_id.eq(&room.id))
+                .select(SharedFile::as_select())
+                .load(&mut conn)?;
+
+            backed_up.push(RoomBackup::from((room, key, members.into_iter().map(Into::into).collect(), keys, history, offers)));
+        }
+
+        log::info!("Backed up {} rooms", backed_up.len());
+        Ok(Backup::from((now_millis(), backed_up)))
+    }
+
+    /// Writes the rooms of the backup in one transaction; `conflict` says what happens to those already here.
+    pub fn restore(&self, backup: Backup, conflict: BackupConflict) -> Result<RestoredBackup> {
+        log::info!("Restoring {} rooms", backup.rooms.len());
+        for room in &backup.rooms {
+            check_room(room)?;
+        }
+
+        // Sealing the keys reads the vault, the transaction must not hold a connection meanwhile.
+        let sealed = backup.rooms.into_iter()
+            .map(|mut room| {
+                let key = self.keys.seal(NoiseModel { encrypted: false, psk_encrypted: false, ..room.key.clone() })?;
+                room.group_keys = std::mem::take(&mut room.group_keys).into_iter()
+                    .map(|group_key| self.group_keys.seal(GroupKeyModel { encrypted: false, ..group_key }))
+                    .collect::<Result<_>>()?;
+                Ok((room, key))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut conn = self.db_pool.get()?;
+        let restored = conn.transaction(|conn| {
+            let mut restored = RestoredBackup::default();
+            for (room, key) in sealed {
+                let room_id = room.room.id.clone();
+                let exists = rooms::table.find(&room_id).count().get_result::<i64>(conn)? > 0;
+                match (exists, conflict) {
+                    (true, BackupConflict::Skip) => {
+                        restored.skipped.push(room_id);
+                        continue;
+                    }
+                    (true, BackupConflict::Overwrite) => {
+                        delete_room(conn, &room_id)?;
+                        restored.restored.push(room_id);
+                    }
+                    (true, BackupConflict::Merge) => restored.merged.push(room_id),
+                    (false, _) => restored.restored.push(room_id),
+                }
+                insert_room(conn, room, &key)?;
+            }
+            Ok::<_, Error>(restored)
+        })?;
+
+        log::info!("Restored {} rooms, merged {} and skipped {}", restored.restored.len(), restored.merged.len(), restored.skipped.len());
+        Ok(restored)
+    }
+}
+
+/// Everything in the room's backup must belong to it, an archive cannot write to other rooms.
+fn check_room(room: &RoomBackup) -> Result<()> {
+    let room_id = &room.room.id;
+    let foreign = room.key.id != *room_id
+        || room.members.iter().any(|member| member.member.room_id != *room_id)
+        || room.group_keys.iter().any(|key| key.room_id != *room_id)
+        || room.messages.iter().any(|message| message.room_id != *room_id)
+        || room.files.iter().any(|file| file.room_id != *room_id);
+
+    if foreign {
+        return Err(ErrorKind::InvalidBackup(format!("the backup of room {room_id} holds rows of other rooms")).into());
+    }
+    Ok(())
+}
+
+/// Adds what the room here lacks; whatever it already has stays as it is.
+fn insert_room(conn: &mut SqliteConnection, room: RoomBackup, key: &NoiseModel) -> Result<()> {
+    diesel::insert_or_ignore_into(rooms::table).values(&room.room).execute(conn)?;
+    diesel::insert_or_ignore_into(noise_keys::table).values(key).execute(conn)?;
+
+    for member in room.members {
+        diesel::insert_or_ignore_into(room_members::table).values(Member::from(member)).execute(conn)?;
+    }
+    for group_key in &room.group_keys {
+        diesel::insert_or_ignore_into(group_keys::table).values(group_key).execute(conn)?;
+    }
+    for message in &room.messages {
+        insert_message(conn, message)?;
+    }
+    for file in room.files {
+        // Paths are those of the device the backup was made on.
+        diesel::insert_or_ignore_into(files::table).values(SharedFile { path: None, ..file }).execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Deletes everything kept about the room, as `removeRoom` does.
+fn delete_room(conn: &mut SqliteConnection, room_id: &str) -> Result<()> {
+    diesel::delete(rooms::table.filter(rooms::id.eq(room_id))).execute(conn)?;
+    diesel::delete(noise_keys::table.filter(noise_keys::id.eq(room_id))).execute(conn)?;
+    diesel::delete(calls::table.filter(calls::room_id.eq(room_id))).execute(conn)?;
+    diesel::delete(invites::table.filter(invites::room_id.eq(room_id))).execute(conn)?;
+    diesel::delete(room_members::table.filter(room_members::room_id.eq(room_id))).execute(conn)?;
+    diesel::delete(group_keys::table.filter(group_keys::room_id.eq(room_id))).execute(conn)?;
+    release_room_messages(conn, room_id)?;
+    diesel::delete(files::table.filter(files::room_id.eq(room_id))).execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::r2d2::{ConnectionManager, Pool};
+
+    use crate::models::callback_payload::MessagePayload;
+    use crate::models::master_key::MasterSecret;
+    use crate::models::member::MemberRole;
+    use crate::models::message::MessageStatus;
+    use crate::services::connection::establish_connection;
+    use crate::services::member_service::MemberService;
+    use crate::services::message_service::MessageService;
+    use crate::services::room_service::RoomService;
+
+    use super::*;
+
+    fn setup_database() -> Pool<ConnectionManager<SqliteConnection>> {
+        let database_url = ":memory:"; // SQLite in-memory database
+        establish_connection(Some(database_url.to_string())).unwrap()
+    }
+
+    struct Device {
+        vault: KeyVault,
+        rooms: RoomService,
+        keys: NoiseKeyService,
+        members: MemberService,
+        messages: MessageService,
+        backups: BackupService,
+    }
+
+    fn device() -> Device {
+        let db_pool = setup_database();
+        let vault = KeyVault::new(db_pool.clone());
+        Device {
+            rooms: RoomService::new(db_pool.clone()),
+            keys: NoiseKeyService::new(db_pool.clone(), vault.clone()),
+            members: MemberService::new(db_pool.clone()),
+            messages: MessageService::new(db_pool.clone()),
+            backups: BackupService::new(db_pool, vault.clone()),
+            vault,
+        }
+    }
+
+    fn add_room(device: &Device, room_id: &str, room_name: &str, message_id: &str) {
+        device.rooms.create_room(&Room::from((room_id.to_string(), room_name.to_string()))).unwrap();
+        device.keys.create_key(room_id).unwrap();
+        let owner = device.keys.get_key(room_id).unwrap().public().to_peer_id().to_string();
+        device.members.add_member(&Member::from((room_id.to_string(), owner.clone(), MemberRole::Owner.as_str().to_string(), owner.clone(), 1, None))).unwrap();
+
+        let delivered = MessagePayload::from((room_id.to_string(), owner, message_id.to_string(), "chat".to_string(), "hi".to_string(), 1, false, None));
+        device.messages.store_message(&Message::stored(&delivered, MessageStatus::Sent, 1, None)).unwrap();
+    }
+
+    fn history(device: &Device, room_id: &str) -> Vec<String> {
+        device.messages.get_messages(room_id, None, None).unwrap().into_iter().map(|message| message.id).collect()
+    }
+
+    #[test]
+    fn test_backup_moves_room_identities_to_another_device() {
+        let old = device();
+        old.vault.unlock(MasterSecret::Passphrase("old device".to_string().into())).unwrap();
+        add_room(&old, "room", "Room", "1");
+        let psk = NoiseKeyService::generate_psk();
+        old.keys.set_psk("room", Some(psk)).unwrap();
+        let (_, group_key) = old.backups.group_keys.create_epoch("room").unwrap();
+        let archive = old.backups.export().unwrap().seal("backup").unwrap();
+
+        let new = device();
+        let restored = new.backups.restore(Backup::open(&archive, "backup").unwrap(), BackupConflict::Skip).unwrap();
+
+        assert_eq!(restored.restored, vec!["room".to_string()]);
+        assert_eq!(new.keys.get_key("room").unwrap().public(), old.keys.get_key("room").unwrap().public());
+        assert_eq!(new.members.get_members("room").unwrap(), old.members.get_members("room").unwrap());
+        assert_eq!(history(&new, "room"), vec!["1".to_string()]);
+        assert_eq!(new.keys.get_psk("room").unwrap(), Some(psk));
+        assert_eq!(new.backups.group_keys.get_key("room", 1).unwrap(), Some(group_key));
+    }
+
+    #[test]
+    fn test_conflicting_rooms_are_skipped_overwritten_or_merged() {
+        let old = device();
+        add_room(&old, "room", "Backed up", "1");
+        let backed_up = old.keys.get_key("room").unwrap().public();
+
+        let new = device();
+        add_room(&new, "room", "Local", "2");
+        let local = new.keys.get_key("room").unwrap().public();
+
+        let skipped = new.backups.restore(old.backups.export().unwrap(), BackupConflict::Skip).unwrap();
+        assert_eq!(skipped.skipped, vec!["room".to_string()]);
+        assert_eq!(history(&new, "room"), vec!["2".to_string()]);
+
+        new.backups.restore(old.backups.export().unwrap(), BackupConflict::Merge).unwrap();
+        assert_eq!(new.rooms.get_room("room").unwrap().name, "Local");
+        assert_eq!(new.keys.get_key("room").unwrap().public(), local);
+        assert_eq!(history(&new, "room"), vec!["1".to_string(), "2".to_string()]);
+
+        new.backups.restore(old.backups.export().unwrap(), BackupConflict::Overwrite).unwrap();
+        assert_eq!(new.rooms.get_room("room").unwrap().name, "Backed up");
+        assert_eq!(new.keys.get_key("room").unwrap().public(), backed_up);
+        assert_eq!(history(&new, "room"), vec!["1".to_string()]);
+    }
+}
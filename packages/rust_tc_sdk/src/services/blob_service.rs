@@ -0,0 +1,284 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl};
+use diesel::r2d2::*;
+use diesel::sqlite::SqliteConnection;
+use uuid::Uuid;
+
+use crate::entities::blob::Blob;
+use crate::models::error::*;
+use crate::models::room_message::now_millis;
+use crate::schema::blobs::dsl::*;
+use crate::services::file_transfers::{is_hash, unique_destination};
+
+/// Unfinished downloads and imports, inside the store.
+const PARTIAL_DIR: &str = ".partial";
+
+/// Garbage collection leaves younger blobs alone, their message may not be stored yet.
+const SETTLE_TIME: Duration = Duration::from_secs(60);
+
+/// Files kept once under their SHA-256 next to the database, whichever rooms share them.
+///
+/// Importing, adopting and exporting copy whole files; async callers run them in `spawn_blocking`.
+#[derive(Debug, Clone)]
+pub struct BlobService {
+    db_pool: Pool<ConnectionManager<SqliteConnection>>,
+    dir: PathBuf,
+}
+
+impl BlobService {
+    pub fn new(db_pool: Pool<ConnectionManager<SqliteConnection>>, dir: PathBuf) -> Self {
+        BlobService { db_pool, dir }
+    }
+
+    /// The same store, on another pool of the same database.
+    pub fn with_pool(&self, db_pool: Pool<ConnectionManager<SqliteConnection>>) -> Self {
+        BlobService { db_pool, dir: self.dir.clone() }
+    }
+
+    pub fn partial_dir(&self) -> PathBuf {
+        self.dir.join(PARTIAL_DIR)
+    }
+
+    /// Where the blob goes, whether or not it is there.
+    pub fn path_of(&self, blob: &str) -> Result<PathBuf> {
+        // The hash comes from other peers, it must not lead out of the store.
+        if !is_hash(blob) {
+            return Err(ErrorKind::InvalidArgument(format!("{blob} is not a SHA-256 hash")).into());
+        }
+        Ok(self.dir.join(&blob[..2]).join(blob))
+    }
+
+    /// The blob on this device, if it is there.
+    pub fn held(&self, blob: &str) -> Result<Option<PathBuf>> {
+        let path = self.path_of(blob)?;
+        Ok(path.is_file().then_some(path))
+    }
+
+    /// Copies `source`, whose hash is `blob`, into the store unless the same content is there already.
+    pub fn import(&self, source: &Path, blob: &str) -> Result<PathBuf> {
+        let destination = self.path_of(blob)?;
+        if !destination.is_file() {
+            fs::create_dir_all(self.partial_dir())?;
+            let staged = self.partial_dir().join(format!("{}.import", Uuid::new_v4()));
+            fs::copy(source, &staged)?;
+            self.place(&staged, &destination)?;
+            log::info!("Imported {} as blob {}", source.display(), blob);
+        }
+
+        self.mark_stored(blob)?;
+        Ok(destination)
+    }
+
+    /// Moves a verified download into the store; a copy already there wins.
+    pub fn adopt(&self, file: &Path, blob: &str) -> Result<PathBuf> {
+        let destination = self.path_of(blob)?;
+        if destination.is_file() {
+            fs::remove_file(file)?;
+        } else {
+            self.place(file, &destination)?;
+        }
+
+        self.mark_stored(blob)?;
+        Ok(destination)
+    }
+
+    /// Links the blob into `dir` under `name`, or copies it there when it cannot be linked.
+    pub fn export(&self, blob: &str, dir: &Path, name: &str) -> Result<PathBuf> {
+        let source = self.held(blob)?.ok_or_else(|| ErrorKind::FileNotFound(blob.to_string()))?;
+        fs::create_dir_all(dir)?;
+
+        let destination = unique_destination(dir, name);
+        if fs::hard_link(&source, &destination).is_err() {
+            fs::copy(&source, &destination)?;
+        }
+        Ok(destination)
+    }
+
+    /// Deletes the blobs no message references anymore, with what is left of their downloads, and
+    /// the files of the store the table does not know about. Returns how many files went.
+    pub fn collect_garbage(&self) -> Result<usize> {
+        log::info!("Collecting unreferenced blobs");
+        let mut conn = self.db_pool.get()?;
+        let settled = now_millis() - SETTLE_TIME.as_millis() as i64;
+
+        let unused: Vec<String> = blobs
+            .filter(ref_count.le(0))
+            .filter(stored_at.is_null().or(stored_at.lt(settled)))
+            .select(hash)
+            .load(&mut conn)?;
+        let mut removed = 0;
+        for blob in &unused {
+            if remove_if_present(&self.path_of(blob)?)? {
+                removed += 1;
+            }
+            diesel::delete(blobs.find(blob)).execute(&mut conn)?;
+        }
+
+        let referenced: HashSet<String> = blobs.filter(ref_count.gt(0)).select(hash).load::<String>(&mut conn)?.into_iter().collect();
+        let stored: HashSet<String> = blobs.filter(stored_at.is_not_null()).select(hash).load::<String>(&mut conn)?.into_iter().collect();
+        if !self.dir.is_dir() {
+            return Ok(removed);
+        }
+
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let partial = entry.file_name() == PARTIAL_DIR;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            for file in fs::read_dir(entry.path())? {
+                let file = file?;
+                let name = file.file_name().to_string_lossy().to_string();
+                let keep = if partial {
+                    // A download resumes from its part as long as a message still offers the file.
+                    name.strip_suffix(".part")
+                        .and_then(|stem| stem.split('.').next())
+                        .is_some_and(|blob| referenced.contains(blob))
+                } else {
+                    stored.contains(&name)
+                };
+                if !keep && is_settled(&file.path())? && remove_if_present(&file.path())? {
+                    removed += 1;
+                }
+            }
+        }
+
+        log::info!("Collected {} blob files", removed);
+        Ok(removed)
+    }
+
+    fn place(&self, file: &Path, destination: &Path) -> Result<()> {
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(file, destination)?;
+        Ok(())
+    }
+
+    fn mark_stored(&self, blob: &str) -> Result<()> {
+        let mut conn = self.db_pool.get()?;
+        let now = now_millis();
+        diesel::insert_into(blobs)
+            .values(Blob::from((blob.to_string(), 0, Some(now))))
+            .on_conflict(hash)
+            .do_update()
+            .set(stored_at.eq(now))
+            .execute(&mut conn)?;
+
+        Ok(())
+    }
+}
+
+fn remove_if_present(path: &Path) -> Result<bool> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Whether the file was left alone long enough not to belong to an import still going on.
+fn is_settled(path: &Path) -> Result<bool> {
+    let modified = fs::metadata(path)?.modified()?;
+    Ok(SystemTime::now().duration_since(modified).unwrap_or_default() >= SETTLE_TIME)
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::r2d2::{ConnectionManager, Pool};
+
+    use crate::entities::message::Message;
+    use crate::models::callback_payload::MessagePayload;
+    use crate::models::file_transfer::FileOffer;
+    use crate::models::message::MessageStatus;
+    use crate::services::connection::establish_connection;
+    use crate::services::message_service::MessageService;
+
+    use diesel::OptionalExtension;
+
+    use super::*;
+
+    const BLOB: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    fn setup_database() -> Pool<ConnectionManager<SqliteConnection>> {
+        let database_url = ":memory:"; // SQLite in-memory database
+        establish_connection(Some(database_url.to_string())).unwrap()
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rust-tc-sdk-blobs-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn get_blob(service: &BlobService, blob: &str) -> Option<Blob> {
+        blobs.find(blob).first(&mut service.db_pool.get().unwrap()).optional().unwrap()
+    }
+
+    fn offering(room: &str, message_id: &str) -> Message {
+        let offer = FileOffer::from((BLOB.to_string(), "notes.txt".to_string(), 5, 4, "root".to_string()));
+        let delivered = MessagePayload::from((
+            room.to_string(),
+            "peer".to_string(),
+            message_id.to_string(),
+            "chat".to_string(),
+            "notes.txt".to_string(),
+            1,
+            false,
+            Some(offer),
+        ));
+        Message::stored(&delivered, MessageStatus::Received, 2, None)
+    }
+
+    #[test]
+    fn test_import_keeps_one_copy_and_exports_it() {
+        let dir = scratch_dir("import");
+        let service = BlobService::new(setup_database(), dir.join("blobs"));
+        let source = dir.join("notes.txt");
+        fs::write(&source, b"hello").unwrap();
+
+        let first = service.import(&source, BLOB).unwrap();
+        let second = service.import(&source, BLOB).unwrap();
+        let exported = service.export(BLOB, &dir.join("downloads"), "notes.txt").unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(fs::read(exported).unwrap(), b"hello");
+        assert!(get_blob(&service, BLOB).unwrap().stored_at.is_some());
+        assert_eq!(service.path_of("../../etc/passwd").unwrap_err().code(), "INVALID_ARGUMENT");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_blobs_live_as_long_as_a_room_offers_them() {
+        let dir = scratch_dir("garbage");
+        let db_pool = setup_database();
+        let service = BlobService::new(db_pool.clone(), dir.join("blobs"));
+        let messages = MessageService::new(db_pool);
+        let source = dir.join("notes.txt");
+        fs::write(&source, b"hello").unwrap();
+
+        messages.store_message(&offering("a", "1")).unwrap();
+        messages.store_message(&offering("b", "2")).unwrap();
+        messages.store_message(&offering("b", "2")).unwrap();
+        let blob = service.import(&source, BLOB).unwrap();
+        assert_eq!(get_blob(&service, BLOB).unwrap().ref_count, 2);
+
+        messages.delete_room_messages("a").unwrap();
+        service.collect_garbage().unwrap();
+        assert!(blob.is_file());
+
+        messages.delete_room_messages("b").unwrap();
+        diesel::update(blobs).set(stored_at.eq(0)).execute(&mut service.db_pool.get().unwrap()).unwrap();
+        assert_eq!(service.collect_garbage().unwrap(), 1);
+        assert!(!blob.exists());
+        assert_eq!(get_blob(&service, BLOB), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
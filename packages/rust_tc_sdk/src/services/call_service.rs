@@ -0,0 +1,140 @@
+use diesel::{ExpressionMethods, QueryResult, RunQueryDsl};
+use diesel::prelude::*;
+use diesel::r2d2::*;
+use diesel::sqlite::SqliteConnection;
+
+use crate::entities::call::Call;
+use crate::models::call_state::CallState;
+use crate::models::error::*;
+use crate::models::room_message::now_millis;
+use crate::schema::calls::dsl::*;
+
+#[derive(Debug, Clone)]
+pub struct CallService {
+    db_pool: Pool<ConnectionManager<SqliteConnection>>,
+}
+
+impl CallService {
+    pub fn new(db_pool: Pool<ConnectionManager<SqliteConnection>>) -> Self {
+        CallService { db_pool }
+    }
+
+    pub fn create_call(&self, call: &Call) -> Result<Call> {
+        log::info!("Creating call {}", call.id);
+        let mut conn = self.db_pool.get()?;
+
+        diesel::insert_into(calls)
+            .values(call)
+            .execute(&mut conn)?;
+
+        log::info!("Created call {}", call.id);
+        Ok(call.clone())
+    }
+
+    #[allow(dead_code)]
+    pub fn get_call(&self, call_id: &str) -> Result<Call> {
+        log::info!("Getting call {}", call_id);
+        let conn = &mut self.db_pool.get()?;
+        let result: QueryResult<Call> = calls
+            .filter(id.eq(call_id))
+            .first(conn);
+
+        log::info!("Got call {}", call_id);
+        Ok(result?)
+    }
+
+    pub fn update_call(&self, call: &Call) -> Result<()> {
+        log::info!("Updating call {}", call.id);
+        let mut conn = self.db_pool.get()?;
+        diesel::update(calls.filter(id.eq(&call.id)))
+            .set(call)
+            .execute(&mut conn)?;
+
+        log::info!("Updated call {}", call.id);
+        Ok(())
+    }
+
+    /// Whether any room has a call that is ringing or in progress, other than `call_id`.
+    pub fn has_ongoing_call(&self, call_id: &str) -> Result<bool> {
+        let mut conn = self.db_pool.get()?;
+        let count: i64 = calls
+            .filter(id.ne(call_id))
+            .filter(state.eq_any([CallState::Ringing.as_str(), CallState::Active.as_str()]))
+            .count()
+            .get_result(&mut conn)?;
+
+        Ok(count > 0)
+    }
+
+    /// Ends the calls a previous run left ringing or in progress.
+    pub fn close_stale_calls(&self) -> Result<usize> {
+        log::info!("Closing stale calls");
+        let mut conn = self.db_pool.get()?;
+        let closed = diesel::update(calls.filter(state.eq_any([CallState::Ringing.as_str(), CallState::Active.as_str()])))
+            .set((state.eq(CallState::Ended.as_str()), ended_at.eq(now_millis())))
+            .execute(&mut conn)?;
+
+        log::info!("Closed {} stale call(s)", closed);
+        Ok(closed)
+    }
+
+    pub fn delete_room_calls(&self, room: &str) -> Result<()> {
+        log::info!("Deleting calls of room {}", room);
+        let mut conn = self.db_pool.get()?;
+        diesel::delete(calls.filter(room_id.eq(room))).execute(&mut conn)?;
+
+        log::info!("Deleted calls of room {}", room);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::r2d2::{ConnectionManager, Pool};
+
+    use crate::services::connection::establish_connection;
+
+    use super::*;
+
+    fn setup_database() -> Pool<ConnectionManager<SqliteConnection>> {
+        let database_url = ":memory:"; // SQLite in-memory database
+        establish_connection(Some(database_url.to_string())).unwrap()
+    }
+
+    fn ringing_call(call_id: &str) -> Call {
+        Call::from((
+            call_id.to_string(),
+            "room".to_string(),
+            "peer".to_string(),
+            "outgoing".to_string(),
+            CallState::Ringing.as_str().to_string(),
+            "[]".to_string(),
+            now_millis(),
+            None,
+            None,
+        ))
+    }
+
+    #[test]
+    fn test_create_and_update_call() {
+        let service = CallService::new(setup_database());
+        let mut call = service.create_call(&ringing_call("call-1")).unwrap();
+
+        call.state = CallState::Active.as_str().to_string();
+        service.update_call(&call).unwrap();
+
+        assert_eq!(service.get_call("call-1").unwrap().state, "active");
+    }
+
+    #[test]
+    fn test_ongoing_calls() {
+        let service = CallService::new(setup_database());
+        service.create_call(&ringing_call("call-1")).unwrap();
+
+        assert!(!service.has_ongoing_call("call-1").unwrap());
+        assert!(service.has_ongoing_call("call-2").unwrap());
+
+        assert_eq!(service.close_stale_calls().unwrap(), 1);
+        assert!(!service.has_ongoing_call("call-2").unwrap());
+    }
+}
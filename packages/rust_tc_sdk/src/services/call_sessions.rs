@@ -0,0 +1,356 @@
+use std::collections::{BTreeSet, HashMap};
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::entities::call::Call;
+use crate::models::call_state::CallState;
+use crate::models::callback_payload::CallPayload;
+use crate::models::error::*;
+use crate::models::room_control::CallControl;
+use crate::models::room_message::now_millis;
+use crate::services::call_service::CallService;
+
+/// How long a call may ring before it counts as missed.
+const RING_TIMEOUT: Duration = Duration::from_secs(30);
+
+const OUTGOING: &str = "outgoing";
+const INCOMING: &str = "incoming";
+
+/// The result of a call transition: what to tell JS and what to tell the room.
+#[derive(Debug)]
+pub struct CallUpdate {
+    pub payload: CallPayload,
+    pub control: Option<CallControl>,
+}
+
+struct Session {
+    call: Call,
+    state: CallState,
+    participants: BTreeSet<String>,
+    ring_deadline: Instant,
+    /// Outgoing calls only: how many peers got the invite, and how many declined.
+    invited: usize,
+    declined: usize,
+    busy: usize,
+}
+
+/// The call state machine of one room.
+///
+/// Only calls that are ringing or in progress are kept in memory; every
+/// transition is persisted through the `CallService`.
+pub struct CallSessions {
+    room_id: String,
+    local_peer: String,
+    call_service: CallService,
+    sessions: HashMap<String, Session>,
+}
+
+impl CallSessions {
+    pub fn new(room_id: String, local_peer: String, call_service: CallService) -> Self {
+        Self { room_id, local_peer, call_service, sessions: HashMap::new() }
+    }
+
+    /// Rings every member of the room; `invited` is how many peers will get the invite.
+    pub fn start(&mut self, invited: usize, now: Instant) -> Result<CallUpdate> {
+        if invited == 0 {
+            return Err(ErrorKind::CallFailed("nobody else is in the room".to_string()).into());
+        }
+
+        let call_id = Uuid::new_v4().to_string();
+        if self.call_service.has_ongoing_call(&call_id)? {
+            return Err(ErrorKind::CallFailed("another call is in progress".to_string()).into());
+        }
+
+        log::info!("Starting call {} in room {}", call_id, self.room_id);
+        let mut session = self.new_session(&call_id, &self.local_peer.clone(), OUTGOING, CallState::Ringing, now);
+        session.invited = invited;
+        self.call_service.create_call(&session.call)?;
+        self.sessions.insert(call_id.clone(), session);
+
+        let payload = self.payload(&call_id, "invited", None)?;
+        Ok(CallUpdate { payload, control: Some(CallControl::Invite { call_id }) })
+    }
+
+    pub fn accept(&mut self, call_id: &str) -> Result<CallUpdate> {
+        let local_peer = self.local_peer.clone();
+        let session = self.ringing(call_id, INCOMING)?;
+        session.participants.insert(local_peer);
+        session.call.answered_at = Some(now_millis());
+
+        let payload = self.transition(call_id, CallState::Active, "accepted", None)?;
+        Ok(CallUpdate { payload, control: Some(CallControl::Accept { call_id: call_id.to_string() }) })
+    }
+
+    pub fn reject(&mut self, call_id: &str) -> Result<CallUpdate> {
+        self.ringing(call_id, INCOMING)?;
+
+        let payload = self.transition(call_id, CallState::Rejected, "rejected", None)?;
+        let control = CallControl::Reject { call_id: call_id.to_string(), reason: "declined".to_string() };
+        Ok(CallUpdate { payload, control: Some(control) })
+    }
+
+    pub fn hang_up(&mut self, call_id: &str) -> Result<CallUpdate> {
+        let session = self.session(call_id)?;
+        if session.state == CallState::Ringing && session.call.direction == INCOMING {
+            return self.reject(call_id);
+        }
+
+        let payload = self.transition(call_id, CallState::Ended, "ended", None)?;
+        Ok(CallUpdate { payload, control: Some(CallControl::HangUp { call_id: call_id.to_string() }) })
+    }
+
+    /// Applies a call control message another member published on the room.
+    pub fn on_control(&mut self, from: &str, control: CallControl, now: Instant) -> Result<Option<CallUpdate>> {
+        let call_id = control.call_id().to_string();
+        if from == self.local_peer {
+            return Ok(None);
+        }
+
+        if let CallControl::Invite { .. } = control {
+            return self.on_invite(from, &call_id, now).map(Some);
+        }
+
+        let Some(session) = self.sessions.get_mut(&call_id) else {
+            log::debug!("Ignoring {:?} for unknown call", control);
+            return Ok(None);
+        };
+
+        let direction = session.call.direction.clone();
+        let update = match (control, session.state, direction.as_str()) {
+            (CallControl::Accept { .. }, CallState::Ringing, OUTGOING) => {
+                session.participants.insert(self.local_peer.clone());
+                session.participants.insert(from.to_string());
+                session.call.answered_at = Some(now_millis());
+                Some(self.transition(&call_id, CallState::Active, "accepted", Some(from))?)
+            }
+            (CallControl::Accept { .. }, CallState::Active, _) => {
+                session.participants.insert(from.to_string());
+                Some(self.transition(&call_id, CallState::Active, "joined", Some(from))?)
+            }
+            (CallControl::Accept { .. }, CallState::Ringing, _) => {
+                // Someone else picked up first; they are in the call once we join.
+                session.participants.insert(from.to_string());
+                None
+            }
+            (CallControl::Reject { reason, .. }, CallState::Ringing, OUTGOING) => {
+                let busy = reason == "busy";
+                session.declined += 1;
+                session.busy += busy as usize;
+
+                let event = if busy { "busy" } else { "rejected" };
+                let state = match (session.declined >= session.invited, session.busy == session.declined) {
+                    (false, _) => CallState::Ringing,
+                    (true, true) => CallState::Busy,
+                    (true, false) => CallState::Rejected,
+                };
+                Some(self.transition(&call_id, state, event, Some(from))?)
+            }
+            (CallControl::HangUp { .. }, CallState::Ringing, INCOMING) if session.call.initiator == from => {
+                Some(self.transition(&call_id, CallState::Missed, "missed", Some(from))?)
+            }
+            (CallControl::HangUp { .. }, CallState::Active, _) => {
+                session.participants.remove(from);
+                let alone = session.participants.iter().all(|p| p == &self.local_peer);
+                if alone {
+                    Some(self.transition(&call_id, CallState::Ended, "ended", Some(from))?)
+                } else {
+                    Some(self.transition(&call_id, CallState::Active, "left", Some(from))?)
+                }
+            }
+            (control, state, _) => {
+                log::debug!("Ignoring {:?} for call in state {:?}", control, state);
+                None
+            }
+        };
+
+        Ok(update.map(|payload| CallUpdate { payload, control: None }))
+    }
+
+    /// Turns every call that rang for too long into a missed call.
+    pub fn expire(&mut self, now: Instant) -> Vec<CallUpdate> {
+        let expired: Vec<String> = self.sessions.iter()
+            .filter(|(_, s)| s.state == CallState::Ringing && s.ring_deadline <= now)
+            .map(|(call_id, _)| call_id.clone())
+            .collect();
+
+        let mut updates = Vec::new();
+        for call_id in expired {
+            let outgoing = self.sessions.get(&call_id).is_some_and(|s| s.call.direction == OUTGOING);
+            let control = if outgoing {
+                CallControl::HangUp { call_id: call_id.clone() }
+            } else {
+                CallControl::Reject { call_id: call_id.clone(), reason: "timeout".to_string() }
+            };
+
+            match self.transition(&call_id, CallState::Missed, "missed", None) {
+                Ok(payload) => updates.push(CallUpdate { payload, control: Some(control) }),
+                Err(e) => log::error!("Failed to expire call {}: {:?}", call_id, e),
+            }
+        }
+
+        updates
+    }
+
+    fn on_invite(&mut self, from: &str, call_id: &str, now: Instant) -> Result<CallUpdate> {
+        if self.sessions.contains_key(call_id) {
+            return Err(ErrorKind::CallFailed(format!("call '{}' already exists", call_id)).into());
+        }
+
+        let busy = self.call_service.has_ongoing_call(call_id)?;
+        let state = if busy { CallState::Busy } else { CallState::Ringing };
+        log::info!("Incoming call {} from {} ({:?})", call_id, from, state);
+
+        let session = self.new_session(call_id, from, INCOMING, state, now);
+        self.call_service.create_call(&session.call)?;
+        self.sessions.insert(call_id.to_string(), session);
+
+        if busy {
+            let payload = self.transition(call_id, CallState::Busy, "busy", Some(from))?;
+            let control = CallControl::Reject { call_id: call_id.to_string(), reason: "busy".to_string() };
+            return Ok(CallUpdate { payload, control: Some(control) });
+        }
+
+        let payload = self.payload(call_id, "ringing", Some(from))?;
+        Ok(CallUpdate { payload, control: None })
+    }
+
+    fn new_session(&self, call_id: &str, initiator: &str, direction: &str, state: CallState, now: Instant) -> Session {
+        let participants = BTreeSet::from([initiator.to_string()]);
+        let call = Call::from((
+            call_id.to_string(),
+            self.room_id.clone(),
+            initiator.to_string(),
+            direction.to_string(),
+            state.as_str().to_string(),
+            serde_json::to_string(&participants).unwrap_or_default(),
+            now_millis(),
+            None,
+            None,
+        ));
+
+        Session { call, state, participants, ring_deadline: now + RING_TIMEOUT, invited: 0, declined: 0, busy: 0 }
+    }
+
+    fn session(&mut self, call_id: &str) -> Result<&mut Session> {
+        self.sessions.get_mut(call_id)
+            .ok_or_else(|| ErrorKind::CallNotFound(call_id.to_string()).into())
+    }
+
+    fn ringing(&mut self, call_id: &str, direction: &str) -> Result<&mut Session> {
+        let session = self.session(call_id)?;
+        if session.state != CallState::Ringing || session.call.direction != direction {
+            return Err(ErrorKind::CallFailed(format!("call '{}' is not ringing", call_id)).into());
+        }
+
+        Ok(session)
+    }
+
+    /// Moves a call to `state`, persists it and forgets it once it is over.
+    fn transition(&mut self, call_id: &str, state: CallState, event: &str, peer: Option<&str>) -> Result<CallPayload> {
+        let session = self.sessions.get_mut(call_id)
+            .ok_or_else(|| Error::from(ErrorKind::CallNotFound(call_id.to_string())))?;
+        session.state = state;
+        session.call.state = state.as_str().to_string();
+        session.call.participants = serde_json::to_string(&session.participants)?;
+        if state.is_final() {
+            session.call.ended_at = Some(now_millis());
+        }
+
+        self.call_service.update_call(&session.call)?;
+        let payload = self.payload(call_id, event, peer)?;
+
+        if state.is_final() {
+            self.sessions.remove(call_id);
+        }
+
+        Ok(payload)
+    }
+
+    fn payload(&self, call_id: &str, event: &str, peer: Option<&str>) -> Result<CallPayload> {
+        let session = self.sessions.get(call_id)
+            .ok_or_else(|| Error::from(ErrorKind::CallNotFound(call_id.to_string())))?;
+
+        Ok(CallPayload::from((
+            self.room_id.clone(),
+            call_id.to_string(),
+            event.to_string(),
+            session.state,
+            session.call.direction.clone(),
+            session.call.initiator.clone(),
+            session.participants.iter().cloned().collect(),
+            peer.map(str::to_string),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::connection::establish_connection;
+
+    use super::*;
+
+    fn sessions(local_peer: &str) -> CallSessions {
+        let pool = establish_connection(Some(":memory:".to_string())).unwrap();
+        CallSessions::new("room".to_string(), local_peer.to_string(), CallService::new(pool))
+    }
+
+    #[test]
+    fn test_outgoing_call_accepted_then_hung_up() {
+        let mut calls = sessions("alice");
+        let now = Instant::now();
+
+        let started = calls.start(1, now).unwrap();
+        let call_id = started.payload.call_id.clone();
+        assert_eq!(started.payload.state, CallState::Ringing);
+
+        let accepted = calls.on_control("bob", CallControl::Accept { call_id: call_id.clone() }, now).unwrap().unwrap();
+        assert_eq!(accepted.payload.state, CallState::Active);
+        assert_eq!(accepted.payload.participants, vec!["alice".to_string(), "bob".to_string()]);
+
+        let ended = calls.on_control("bob", CallControl::HangUp { call_id: call_id.clone() }, now).unwrap().unwrap();
+        assert_eq!(ended.payload.event, "ended");
+        assert_eq!(calls.call_service.get_call(&call_id).unwrap().state, "ended");
+    }
+
+    #[test]
+    fn test_incoming_call_is_missed_after_timeout() {
+        let mut calls = sessions("bob");
+        let now = Instant::now();
+
+        let ringing = calls.on_control("alice", CallControl::Invite { call_id: "call-1".to_string() }, now).unwrap().unwrap();
+        assert_eq!(ringing.payload.event, "ringing");
+
+        assert!(calls.expire(now).is_empty());
+        let expired = calls.expire(now + RING_TIMEOUT);
+
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].payload.state, CallState::Missed);
+        assert!(matches!(expired[0].control, Some(CallControl::Reject { .. })));
+    }
+
+    #[test]
+    fn test_second_invite_while_ringing_is_busy() {
+        let mut calls = sessions("bob");
+        let now = Instant::now();
+
+        calls.on_control("alice", CallControl::Invite { call_id: "call-1".to_string() }, now).unwrap();
+        let busy = calls.on_control("carol", CallControl::Invite { call_id: "call-2".to_string() }, now).unwrap().unwrap();
+
+        assert_eq!(busy.payload.state, CallState::Busy);
+        assert!(matches!(busy.control, Some(CallControl::Reject { ref reason, .. }) if reason == "busy"));
+    }
+
+    #[test]
+    fn test_outgoing_call_rejected_by_everyone() {
+        let mut calls = sessions("alice");
+        let now = Instant::now();
+        let call_id = calls.start(2, now).unwrap().payload.call_id;
+
+        let first = calls.on_control("bob", CallControl::Reject { call_id: call_id.clone(), reason: "declined".to_string() }, now).unwrap().unwrap();
+        assert_eq!(first.payload.state, CallState::Ringing);
+
+        let second = calls.on_control("carol", CallControl::Reject { call_id, reason: "busy".to_string() }, now).unwrap().unwrap();
+        assert_eq!(second.payload.state, CallState::Rejected);
+    }
+}
@@ -1,37 +1,157 @@
+use std::fs::{self, File};
+use std::io::Read;
+
+use diesel::connection::SimpleConnection;
 use diesel::r2d2::*;
 use diesel::sqlite::{Sqlite, SqliteConnection};
+use diesel::Connection;
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use zeroize::Zeroizing;
 
-use crate::models::error::Result;
+use crate::models::error::*;
 use crate::services::database_url::get_database_path;
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations");
 
-// This is a type alias for the r2d2 connection pool type we'll be using
-#[inline]
-pub fn establish_connection(user_url: Option<String>) -> Pool<ConnectionManager<SqliteConnection>> {
-    log::info!("Establishing connection");
+/// How every plaintext SQLite file starts; an encrypted one looks random from the first byte.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
 
-    let database_url = match user_url {
-        Some(url) if url.len() > 0 => url,
-        _ => match get_database_path() {
-            Some(path) => path,
-            None => panic!("Failed to get database path")
-        }
-    };
+/// A plaintext database, as the tests use.
+#[cfg(test)]
+pub fn establish_connection(user_url: Option<String>) -> Result<Pool<ConnectionManager<SqliteConnection>>> {
+    open_database(user_url, None)
+}
+
+/// Opens the database, encrypted with `key` under SQLCipher when there is one.
+///
+/// A plaintext database found where an encrypted one is expected is encrypted first, once.
+pub fn open_database(user_url: Option<String>, key: Option<&str>) -> Result<Pool<ConnectionManager<SqliteConnection>>> {
+    log::info!("Establishing connection");
+    let database_url = database_url(user_url)?;
 
     // Use database_url if it is set, otherwise use DATABASE_URL
     log::info!("Using database url: {}", database_url);
-    let manager = ConnectionManager::<SqliteConnection>::new(database_url);
+    let manager = ConnectionManager::<SqliteConnection>::new(database_url.clone());
 
     log::info!("Creating database pool");
-    let pool = Pool::builder().build(manager).expect("Failed to create database pool");
+    let mut builder = Pool::builder();
+    if let Some(key) = key {
+        require_sqlcipher()?;
+        if is_plaintext(&database_url)? {
+            encrypt_plaintext(&database_url, key)?;
+        }
+        builder = builder.connection_customizer(Box::new(DatabaseKey(Zeroizing::new(key.to_string()))));
+    }
+    let pool = builder.build(manager)?;
+
+    // SQLCipher only finds out about a wrong key on the first read.
+    if key.is_some() {
+        pool.get()?.batch_execute("SELECT count(*) FROM sqlite_master;").map_err(|_| ErrorKind::WrongDatabaseKey)?;
+    }
 
     log::info!("Running migrations");
-    run_migrations(&mut *pool.get().expect("Failed to get a connection from the pool")).expect("Failed to run migrations");
+    run_migrations(&mut *pool.get()?)?;
 
     log::info!("Connection established");
-    pool
+    Ok(pool)
+}
+
+/// Re-encrypts an encrypted database with `new_key`, and opens it again with that key.
+///
+/// Nothing may use `db_pool` afterwards: its other connections still hold the old key.
+pub fn rekey_database(db_pool: Pool<ConnectionManager<SqliteConnection>>, user_url: Option<String>, new_key: &str) -> Result<Pool<ConnectionManager<SqliteConnection>>> {
+    require_sqlcipher()?;
+    let database_url = database_url(user_url)?;
+    if database_url == ":memory:" {
+        return Err(ErrorKind::InvalidArgument("an in-memory database cannot be rekeyed".to_string()).into());
+    }
+    if is_plaintext(&database_url)? {
+        return Err(ErrorKind::InvalidArgument("the database is not encrypted, start the SDK with a db_key to encrypt it".to_string()).into());
+    }
+
+    log::info!("Rekeying the database");
+    db_pool.get()?.batch_execute(&format!("PRAGMA rekey = {};", quote(new_key)))?;
+    drop(db_pool);
+
+    open_database(Some(database_url), Some(new_key))
+}
+
+fn database_url(user_url: Option<String>) -> Result<String> {
+    match user_url {
+        Some(url) if !url.is_empty() => Ok(url),
+        _ => get_database_path()
+            .ok_or_else(|| ErrorKind::DatabaseUnavailable("no data directory to store the database in".to_string()).into()),
+    }
+}
+
+fn require_sqlcipher() -> Result<()> {
+    if !cfg!(feature = "sqlcipher") {
+        return Err(ErrorKind::DatabaseUnavailable("a db_key needs the SDK built with its `sqlcipher` cargo feature, as `npm run build-sqlcipher` does".to_string()).into());
+    }
+    Ok(())
+}
+
+/// Whether a database exists at `path` and is not encrypted.
+fn is_plaintext(path: &str) -> Result<bool> {
+    let mut header = [0u8; 16];
+    match File::open(path) {
+        Ok(mut file) => Ok(file.read_exact(&mut header).is_ok() && &header == SQLITE_HEADER),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Copies the plaintext database into an encrypted one, which then takes its place.
+fn encrypt_plaintext(path: &str, key: &str) -> Result<()> {
+    log::info!("Encrypting the plaintext database {}", path);
+    let encrypted = format!("{path}.encrypting");
+    // What an interrupted attempt left behind.
+    remove_if_present(&encrypted)?;
+
+    let mut conn = SqliteConnection::establish(path)
+        .map_err(|e| ErrorKind::DatabaseUnavailable(e.to_string()))?;
+    conn.batch_execute(&format!(
+        "ATTACH DATABASE {} AS encrypted KEY {}; SELECT sqlcipher_export('encrypted'); DETACH DATABASE encrypted;",
+        quote(&encrypted),
+        quote(key),
+    ))?;
+    drop(conn);
+
+    fs::rename(&encrypted, path)?;
+    for leftover in ["-journal", "-wal", "-shm"] {
+        remove_if_present(&format!("{path}{leftover}"))?;
+    }
+
+    log::info!("Encrypted the database {}", path);
+    Ok(())
+}
+
+fn remove_if_present(path: &str) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// A string literal for SQLite.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Hands the key to every connection the pool opens, before anything reads the database.
+struct DatabaseKey(Zeroizing<String>);
+
+impl std::fmt::Debug for DatabaseKey {
+    // Never the key itself.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DatabaseKey")
+    }
+}
+
+impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for DatabaseKey {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> std::result::Result<(), diesel::r2d2::Error> {
+        conn.batch_execute(&format!("PRAGMA key = {};", quote(&self.0))).map_err(diesel::r2d2::Error::QueryError)
+    }
 }
 
 fn run_migrations(connection: &mut impl MigrationHarness<Sqlite>) -> Result<()> {
@@ -43,7 +163,7 @@ fn run_migrations(connection: &mut impl MigrationHarness<Sqlite>) -> Result<()>
     // all available methods.
     connection
         .run_pending_migrations(MIGRATIONS)
-        .unwrap_or_else(|e| panic!("Failed to run migrations: {}", e));
+        .map_err(|e| ErrorKind::DatabaseUnavailable(format!("failed to run migrations: {}", e)))?;
 
     log::info!("Migrations complete");
     Ok(())
@@ -61,7 +181,7 @@ mod tests {
         let db_path = "./test.db";
         let _ = fs::remove_file(db_path); // Clean up any existing test database
 
-        let pool = establish_connection(Some(db_path.to_string()));
+        let pool = establish_connection(Some(db_path.to_string())).expect("Failed to establish connection");
 
         // Assert the pool was created successfully by attempting a connection
         pool.get().expect("Failed to get a connection from the pool");
@@ -69,6 +189,30 @@ mod tests {
         let _ = fs::remove_file(db_path); // Clean up after the test
     }
 
-    // Optionally, you could add more tests for edge cases, invalid URLs, etc.
-    // But handling them might require changing the function to return a Result.
+    #[cfg(not(feature = "sqlcipher"))]
+    #[test]
+    fn test_db_key_needs_sqlcipher() {
+        let error = open_database(Some(":memory:".to_string()), Some("key")).unwrap_err();
+
+        assert_eq!(error.code(), "DB_ERROR");
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn test_plaintext_database_is_encrypted_and_rekeyed() {
+        let db_path = "./test-sqlcipher.db";
+        let _ = fs::remove_file(db_path);
+        establish_connection(Some(db_path.to_string())).unwrap();
+        assert!(is_plaintext(db_path).unwrap());
+
+        let pool = open_database(Some(db_path.to_string()), Some("first")).unwrap();
+        assert!(!is_plaintext(db_path).unwrap());
+        assert_eq!(open_database(Some(db_path.to_string()), Some("second")).unwrap_err().code(), "WRONG_DATABASE_KEY");
+
+        rekey_database(pool, Some(db_path.to_string()), "second").unwrap();
+        assert_eq!(open_database(Some(db_path.to_string()), Some("first")).unwrap_err().code(), "WRONG_DATABASE_KEY");
+        open_database(Some(db_path.to_string()), Some("second")).unwrap();
+
+        let _ = fs::remove_file(db_path);
+    }
 }
@@ -1,4 +1,6 @@
-use dirs::{data_dir};
+use std::path::{Path, PathBuf};
+
+use dirs::{data_dir, download_dir};
 
 pub fn get_database_path() -> Option<String> {
     log::info!("Getting database path");
@@ -8,3 +10,20 @@ pub fn get_database_path() -> Option<String> {
         path.to_str().unwrap().to_string()
     })
 }
+
+/// The user's download directory, or a `downloads` folder in the data directory when there is none.
+pub fn get_download_dir() -> PathBuf {
+    download_dir()
+        .or_else(|| data_dir().map(|path| path.join("downloads")))
+        .unwrap_or_else(|| std::env::temp_dir().join("downloads"))
+}
+
+/// The `blobs` folder next to the database file.
+pub fn get_blob_dir(db_url: Option<&str>) -> PathBuf {
+    db_url
+        .filter(|url| !url.is_empty() && *url != ":memory:")
+        .map(String::from)
+        .or_else(get_database_path)
+        .and_then(|database| Path::new(&database).parent().map(|dir| dir.join("blobs")))
+        .unwrap_or_else(|| std::env::temp_dir().join("blobs"))
+}
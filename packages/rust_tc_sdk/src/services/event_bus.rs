@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use neon::prelude::*;
+use neon_serde3::to_value;
+use uuid::Uuid;
+
+use crate::models::callback_payload::CallbackPayload;
+
+struct Listener {
+    channel: Channel,
+    callback: Arc<Root<JsFunction>>,
+}
+
+/// Fans out swarm events to every JS callback registered through `registerListener`.
+///
+/// Cloning is cheap; every room loop holds its own clone.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    listeners: Arc<Mutex<HashMap<String, Listener>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, channel: Channel, callback: Root<JsFunction>) -> String {
+        let listener_id = Uuid::new_v4().to_string();
+        log::info!("Registering listener {}", listener_id);
+
+        let listener = Listener { channel, callback: Arc::new(callback) };
+        self.listeners.lock().unwrap().insert(listener_id.clone(), listener);
+
+        listener_id
+    }
+
+    pub fn unregister(&self, listener_id: &str) -> bool {
+        log::info!("Unregistering listener {}", listener_id);
+        self.listeners.lock().unwrap().remove(listener_id).is_some()
+    }
+
+    pub fn emit(&self, payload: CallbackPayload) {
+        let listeners = self.listeners.lock().unwrap();
+        log::debug!("Emitting {} to {} listener(s)", payload.event_type(), listeners.len());
+
+        for listener in listeners.values() {
+            let callback = listener.callback.clone();
+            let payload = payload.clone();
+
+            // The send only fails once the JS runtime is shutting down.
+            let _ = listener.channel.try_send(move |mut cx| {
+                let callback = callback.to_inner(&mut cx);
+                let this = cx.undefined();
+                let event_type = cx.string(payload.event_type());
+                let value = to_value(&mut cx, &payload)
+                    .or_else(|e| cx.throw_error(e.to_string()))?
+                    .downcast_or_throw::<JsObject, _>(&mut cx)?;
+                let data: Handle<JsValue> = value.get(&mut cx, "data")?;
+
+                callback.call(&mut cx, this, [event_type.upcast(), data])?;
+                Ok(())
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::models::callback_payload::{ConnectionPayload, HolePunchPayload, MessagePayload, PeerPayload};
+    use crate::models::transport::Transport;
+
+    use super::*;
+
+    /// `emit` hands listeners the `type` and `data` of the serialized payload, as `CallbackPayloads` in `types.d.ts` declares them.
+    #[test]
+    fn test_payloads_serialize_as_type_and_data() {
+        let peer = PeerPayload::from(("room".to_string(), "peer".to_string(), "/ip4/1.2.3.4/tcp/1".to_string()));
+        let connection = ConnectionPayload::from(("room".to_string(), "peer".to_string(), "/ip4/1.2.3.4/tcp/1".to_string(), true, Some(Transport::Tcp), None));
+        let message = MessagePayload::from(("room".to_string(), "peer".to_string(), "id".to_string(), "chat".to_string(), "hi".to_string(), 1, false, None));
+        let hole_punch = HolePunchPayload::from(("room".to_string(), "peer".to_string(), Some("timed out".to_string())));
+
+        let expected = [
+            (CallbackPayload::PeerDiscovered(peer), "peer-discovered", json!({ "room_id": "room", "peer_id": "peer", "address": "/ip4/1.2.3.4/tcp/1" })),
+            (CallbackPayload::ConnectionOpened(connection), "connection-opened", json!({
+                "room_id": "room", "peer_id": "peer", "address": "/ip4/1.2.3.4/tcp/1", "relayed": true, "transport": "tcp", "cause": null,
+            })),
+            (CallbackPayload::Message(message), "message", json!({
+                "room_id": "room", "peer_id": "peer", "message_id": "id", "topic": "chat", "payload": "hi", "timestamp": 1, "synced": false, "file": null,
+            })),
+            (CallbackPayload::HolePunch(hole_punch), "hole-punch", json!({ "room_id": "room", "peer_id": "peer", "error": "timed out" })),
+        ];
+
+        for (payload, event_type, data) in expected {
+            assert_eq!(payload.event_type(), event_type);
+            assert_eq!(serde_json::to_value(&payload).unwrap(), json!({ "type": event_type, "data": data }));
+        }
+    }
+
+    #[test]
+    fn test_unregister_unknown_listener() {
+        let bus = EventBus::new();
+
+        assert!(!bus.unregister("unknown"));
+    }
+}
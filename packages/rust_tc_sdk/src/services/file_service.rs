@@ -0,0 +1,148 @@
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper};
+use diesel::r2d2::*;
+use diesel::sqlite::SqliteConnection;
+
+use crate::entities::file::SharedFile;
+use crate::models::error::*;
+use crate::schema::files::dsl::*;
+
+/// The files shared in each room, and where the ones on this device are.
+#[derive(Debug, Clone)]
+pub struct FileService {
+    db_pool: Pool<ConnectionManager<SqliteConnection>>,
+}
+
+impl FileService {
+    pub fn new(db_pool: Pool<ConnectionManager<SqliteConnection>>) -> Self {
+        FileService { db_pool }
+    }
+
+    /// Remembers an offer; an offer already known keeps its local path.
+    pub fn add_file(&self, file: &SharedFile) -> Result<()> {
+        log::info!("Adding file {} to room {}", file.file_id, file.room_id);
+        let mut conn = self.db_pool.get()?;
+        diesel::insert_or_ignore_into(files)
+            .values(file)
+            .execute(&mut conn)?;
+
+        if file.path.is_some() {
+            self.set_path(&file.room_id, &file.file_id, file.path.as_deref())?;
+        }
+        Ok(())
+    }
+
+    pub fn set_path(&self, room: &str, file: &str, local_path: Option<&str>) -> Result<()> {
+        let mut conn = self.db_pool.get()?;
+        diesel::update(files.filter(room_id.eq(room)).filter(file_id.eq(file)))
+            .set(path.eq(local_path))
+            .execute(&mut conn)?;
+
+        Ok(())
+    }
+
+    pub fn get_file(&self, room: &str, file: &str) -> Result<SharedFile> {
+        let mut conn = self.db_pool.get()?;
+        files
+            .filter(room_id.eq(room))
+            .filter(file_id.eq(file))
+            .select(SharedFile::as_select())
+            .first(&mut conn)
+            .optional()?
+            .ok_or_else(|| ErrorKind::FileNotFound(file.to_string()).into())
+    }
+
+    pub fn get_files(&self, room: &str) -> Result<Vec<SharedFile>> {
+        log::info!("Getting files of room {}", room);
+        let mut conn = self.db_pool.get()?;
+        let shared = files
+            .filter(room_id.eq(room))
+            .order(created_at.asc())
+            .select(SharedFile::as_select())
+            .load(&mut conn)?;
+
+        Ok(shared)
+    }
+
+    /// The concatenated chunk hashes of a file this node serves; `None` until they were worked out.
+    pub fn get_manifest(&self, room: &str, file: &str) -> Result<Option<Vec<u8>>> {
+        let mut conn = self.db_pool.get()?;
+        let hashes: Option<Option<Vec<u8>>> = files
+            .filter(room_id.eq(room))
+            .filter(file_id.eq(file))
+            .select(manifest)
+            .first(&mut conn)
+            .optional()?;
+
+        Ok(hashes.flatten())
+    }
+
+    pub fn set_manifest(&self, room: &str, file: &str, hashes: &[u8]) -> Result<()> {
+        let mut conn = self.db_pool.get()?;
+        diesel::update(files.filter(room_id.eq(room)).filter(file_id.eq(file)))
+            .set(manifest.eq(hashes))
+            .execute(&mut conn)?;
+
+        Ok(())
+    }
+
+    /// Forgets one offer; the copy on disk is left alone.
+    pub fn delete_file(&self, room: &str, file: &str) -> Result<()> {
+        let mut conn = self.db_pool.get()?;
+        diesel::delete(files.filter(room_id.eq(room)).filter(file_id.eq(file))).execute(&mut conn)?;
+
+        Ok(())
+    }
+
+    /// Forgets the room's files; the copies on disk are left alone.
+    pub fn delete_room_files(&self, room: &str) -> Result<()> {
+        log::info!("Deleting the files of room {}", room);
+        let mut conn = self.db_pool.get()?;
+        diesel::delete(files.filter(room_id.eq(room))).execute(&mut conn)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::r2d2::{ConnectionManager, Pool};
+
+    use crate::models::file_transfer::FileOffer;
+    use crate::services::connection::establish_connection;
+
+    use super::*;
+
+    fn setup_database() -> Pool<ConnectionManager<SqliteConnection>> {
+        let database_url = ":memory:"; // SQLite in-memory database
+        establish_connection(Some(database_url.to_string())).unwrap()
+    }
+
+    #[test]
+    fn test_offers_keep_their_local_path() {
+        let service = FileService::new(setup_database());
+        let offer = FileOffer::from(("abc".to_string(), "notes.txt".to_string(), 10, 4, "def".to_string()));
+
+        service.add_file(&SharedFile::offered("room", &offer, "peer", None, 1)).unwrap();
+        service.set_path("room", "abc", Some("/tmp/notes.txt")).unwrap();
+        service.add_file(&SharedFile::offered("room", &offer, "peer", None, 2)).unwrap();
+
+        let file = service.get_file("room", "abc").unwrap();
+        assert_eq!(file.path.as_deref(), Some("/tmp/notes.txt"));
+        assert_eq!(file.offer(), offer);
+        assert_eq!(service.get_file("room", "missing").unwrap_err().code(), "FILE_NOT_FOUND");
+    }
+
+    #[test]
+    fn test_manifests_are_kept_with_the_offer() {
+        let service = FileService::new(setup_database());
+        let offer = FileOffer::from(("abc".to_string(), "notes.txt".to_string(), 10, 4, "def".to_string()));
+        service.add_file(&SharedFile::offered("room", &offer, "peer", None, 1)).unwrap();
+        assert_eq!(service.get_manifest("room", "abc").unwrap(), None);
+
+        service.set_manifest("room", "abc", &[7; 96]).unwrap();
+
+        assert_eq!(service.get_manifest("room", "abc").unwrap(), Some(vec![7; 96]));
+        assert_eq!(service.get_files("room").unwrap().len(), 1);
+        assert_eq!(service.get_manifest("other", "abc").unwrap(), None);
+    }
+}
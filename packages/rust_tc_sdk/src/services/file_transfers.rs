@@ -0,0 +1,585 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use libp2p::request_response::{OutboundRequestId, ResponseChannel};
+use libp2p::{PeerId, Swarm};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::models::behaviour::AppBehaviour;
+use crate::models::callback_payload::FilePayload;
+use crate::models::error::*;
+use crate::models::file_transfer::{ChunkRequest, ChunkResponse, FileOffer};
+use crate::services::blob_service::BlobService;
+
+pub const FILE_TRANSFER_PROTOCOL: &str = "/vichiz/file-transfer/2";
+
+pub const CHUNK_SIZE: i64 = 256 * 1024;
+
+/// The largest chunk an offer may ask for, each one is read into memory whole.
+pub const MAX_CHUNK_SIZE: i64 = 4 * 1024 * 1024;
+
+/// How many chunks a download asks for at once.
+const MAX_IN_FLIGHT: usize = 4;
+
+/// How many chunk hashes a manifest response holds; the codec reads at most 10 MiB of a response.
+pub const MANIFEST_PAGE_LEN: u64 = 64 * 1024;
+
+pub type ChunkHash = [u8; 32];
+
+/// Work done off the swarm loop, for the loop to pick up.
+pub enum TransferDone {
+    /// The answer to a request of `peer`, read from disk.
+    Served { peer: PeerId, channel: ResponseChannel<ChunkResponse>, response: ChunkResponse },
+    /// The chunks still missing from a download, and how many bytes an earlier attempt left on disk.
+    Checked { key: DownloadKey, checked: Result<(VecDeque<u64>, i64)> },
+    /// Where a download landed once it was moved to the blob store.
+    Finished { key: DownloadKey, finished: Result<PathBuf> },
+}
+
+/// Hashes the file chunk by chunk: the offer for it and the hash of every chunk.
+pub fn hash_file(path: &Path, chunk_size: i64) -> Result<(FileOffer, Vec<ChunkHash>)> {
+    check_chunk_size(chunk_size)?;
+    let name = path.file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| ErrorKind::InvalidArgument(format!("{} is not a file", path.display())))?;
+
+    let mut file = File::open(path)?;
+    let mut whole = Sha256::new();
+    let mut manifest = Vec::new();
+    let mut size = 0;
+    let mut buffer = vec![0u8; chunk_size as usize];
+    loop {
+        let read = read_full(&mut file, &mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        whole.update(&buffer[..read]);
+        manifest.push(Sha256::digest(&buffer[..read]).into());
+        size += read as i64;
+    }
+
+    let offer = FileOffer::from((to_hex(&whole.finalize()), name.to_string(), size, chunk_size, chunk_root(&manifest)));
+    Ok((offer, manifest))
+}
+
+pub fn chunk_root(manifest: &[ChunkHash]) -> String {
+    to_hex(&Sha256::digest(manifest.concat()))
+}
+
+/// The hashes of `manifest` from chunk `from` on, a page at most.
+pub fn manifest_page(manifest: &[u8], from: u64) -> Result<Vec<u8>> {
+    let start = from.checked_mul(32)
+        .filter(|start| *start <= manifest.len() as u64)
+        .ok_or_else(|| ErrorKind::InvalidArgument(format!("the manifest has no chunk {from}")))? as usize;
+    let end = manifest.len().min(start + MANIFEST_PAGE_LEN as usize * 32);
+    Ok(manifest[start..end].to_vec())
+}
+
+pub fn read_chunk(path: &Path, chunk_size: i64, index: u64) -> Result<Vec<u8>> {
+    check_chunk_size(chunk_size)?;
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(index * chunk_size as u64))?;
+
+    let mut chunk = Vec::new();
+    file.take(chunk_size as u64).read_to_end(&mut chunk)?;
+    Ok(chunk)
+}
+
+/// Offers come from other members; one that could not be downloaded is refused before anything keeps it.
+pub fn check_offer(offer: &FileOffer) -> Result<()> {
+    check_chunk_size(offer.chunk_size)?;
+    if offer.size < 0 {
+        return Err(ErrorKind::InvalidArgument(format!("file {} has a negative size", offer.file_id)).into());
+    }
+    if !is_hash(&offer.file_id) || !is_hash(&offer.chunk_root) {
+        return Err(ErrorKind::InvalidArgument(format!("file {} is not named by its SHA-256", offer.file_id)).into());
+    }
+    Ok(())
+}
+
+fn check_chunk_size(chunk_size: i64) -> Result<()> {
+    if !(1..=MAX_CHUNK_SIZE).contains(&chunk_size) {
+        return Err(ErrorKind::InvalidArgument(format!("chunks must be between 1 and {MAX_CHUNK_SIZE} bytes, not {chunk_size}")).into());
+    }
+    Ok(())
+}
+
+/// Whether `value` is a hex SHA-256.
+pub fn is_hash(value: &str) -> bool {
+    value.len() == 64 && value.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// The partial file of a download; rooms downloading the same file each write their own.
+fn partial_name(room_id: &str, file_id: &str) -> String {
+    format!("{file_id}.{}.part", &to_hex(&Sha256::digest(room_id))[..16])
+}
+
+/// The manifest of `offer`, as long as it matches the chunk root the offer vouches for.
+fn parse_manifest(bytes: &[u8], offer: &FileOffer) -> Result<Vec<ChunkHash>> {
+    let manifest: Vec<ChunkHash> = bytes.chunks(32)
+        .map(|hash| hash.try_into())
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|_| ErrorKind::TransferFailed("malformed manifest".to_string()))?;
+
+    if manifest.len() as u64 != chunk_count(offer) || chunk_root(&manifest) != offer.chunk_root {
+        return Err(ErrorKind::TransferFailed("the manifest does not match the offer".to_string()).into());
+    }
+    Ok(manifest)
+}
+
+fn chunk_count(offer: &FileOffer) -> u64 {
+    (offer.size as u64).div_ceil(offer.chunk_size as u64)
+}
+
+/// Reads what a previous attempt wrote to `part`: the chunks that do not match the manifest, and how many bytes do.
+fn check_partial(part: &Path, chunk_size: i64, manifest: &[ChunkHash]) -> Result<(VecDeque<u64>, i64)> {
+    let mut file = part.exists().then(|| File::open(part)).transpose()?;
+    let mut buffer = vec![0u8; chunk_size as usize];
+    let mut pending = VecDeque::new();
+    let mut received = 0;
+    for (index, hash) in manifest.iter().enumerate() {
+        let read = match file.as_mut() {
+            Some(file) => read_full(file, &mut buffer)?,
+            None => 0,
+        };
+        if read > 0 && Sha256::digest(&buffer[..read])[..] == hash[..] {
+            received += read as i64;
+        } else {
+            pending.push_back(index as u64);
+        }
+    }
+    Ok((pending, received))
+}
+
+/// Moves a download to the blob store and links it into `dir`.
+///
+/// Every chunk was checked against the manifest the offer vouches for, the whole file needs no second look.
+fn store_download(blobs: &BlobService, dir: &Path, part: &Path, offer: &FileOffer) -> Result<PathBuf> {
+    if offer.size == 0 {
+        File::create(part)?;
+    }
+    blobs.adopt(part, &offer.file_id)?;
+    blobs.export(&offer.file_id, dir, &offer.name)
+}
+
+fn read_full(file: &mut File, buffer: &mut [u8]) -> Result<usize> {
+    let mut read = 0;
+    while read < buffer.len() {
+        match file.read(&mut buffer[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub type DownloadKey = (String, String);
+
+struct Download {
+    room_id: String,
+    offer: FileOffer,
+    /// The swarm peer of the member serving the file.
+    peer: PeerId,
+    part: PathBuf,
+    /// The pages of the manifest received so far.
+    manifest_pages: Vec<u8>,
+    /// Empty until the serving peer sent all of it.
+    manifest: Vec<ChunkHash>,
+    pending: VecDeque<u64>,
+    in_flight: usize,
+    received: i64,
+    paused: bool,
+}
+
+impl Download {
+    fn payload(&self, event: &str) -> FilePayload {
+        FilePayload::from((
+            self.room_id.clone(),
+            self.offer.file_id.clone(),
+            self.offer.name.clone(),
+            event.to_string(),
+            self.received,
+            self.offer.size,
+            None,
+            None,
+        ))
+    }
+
+    fn key(&self) -> DownloadKey {
+        (self.room_id.clone(), self.offer.file_id.clone())
+    }
+
+    /// Takes in a page of the manifest; `true` once all of it is in and matches the offer.
+    fn add_manifest_page(&mut self, page: &[u8]) -> Result<bool> {
+        let expected = chunk_count(&self.offer) * 32;
+        if !page.len().is_multiple_of(32) || (page.is_empty() && (self.manifest_pages.len() as u64) < expected) {
+            return Err(ErrorKind::TransferFailed("malformed manifest".to_string()).into());
+        }
+
+        self.manifest_pages.extend_from_slice(page);
+        if (self.manifest_pages.len() as u64) < expected {
+            return Ok(false);
+        }
+        self.manifest = parse_manifest(&std::mem::take(&mut self.manifest_pages), &self.offer)?;
+        Ok(true)
+    }
+
+    fn write_chunk(&mut self, index: u64, chunk: &[u8]) -> Result<()> {
+        if Sha256::digest(chunk)[..] != self.manifest[index as usize] {
+            return Err(ErrorKind::TransferFailed(format!("chunk {index} does not match its hash")).into());
+        }
+
+        let mut part = OpenOptions::new().create(true).truncate(false).write(true).open(&self.part)?;
+        part.seek(SeekFrom::Start(index * self.offer.chunk_size as u64))?;
+        part.write_all(chunk)?;
+        self.received += chunk.len() as i64;
+        Ok(())
+    }
+}
+
+/// The files this node is pulling, chunk by chunk, from the members serving them.
+pub struct Downloads {
+    /// Where finished downloads are linked to, out of the blob store.
+    dir: PathBuf,
+    blobs: BlobService,
+    active: HashMap<DownloadKey, Download>,
+    /// The download each request is for, and the chunk unless it asked for the manifest.
+    requests: HashMap<OutboundRequestId, (DownloadKey, Option<u64>)>,
+    /// Where checking and storing downloads off the swarm loop report back.
+    done: UnboundedSender<TransferDone>,
+}
+
+impl Downloads {
+    pub fn new(dir: PathBuf, blobs: BlobService, done: UnboundedSender<TransferDone>) -> Self {
+        Downloads { dir, blobs, active: HashMap::new(), requests: HashMap::new(), done }
+    }
+
+    /// Starts pulling the file from `peer`, picking up what an earlier attempt left on disk.
+    pub fn start(&mut self, swarm: &mut Swarm<AppBehaviour>, room_id: &str, offer: FileOffer, peer: PeerId) -> Result<FilePayload> {
+        check_offer(&offer)?;
+        let key = (room_id.to_string(), offer.file_id.clone());
+        if self.active.get(&key).is_some_and(|download| !download.paused) {
+            return Err(ErrorKind::InvalidArgument(format!("file {} is already downloading", offer.file_id)).into());
+        }
+
+        let partial_dir = self.blobs.partial_dir();
+        fs::create_dir_all(&partial_dir)?;
+        let download = Download {
+            room_id: room_id.to_string(),
+            part: partial_dir.join(partial_name(room_id, &offer.file_id)),
+            offer,
+            peer,
+            manifest_pages: Vec::new(),
+            manifest: Vec::new(),
+            pending: VecDeque::new(),
+            in_flight: 0,
+            received: 0,
+            paused: false,
+        };
+        let payload = download.payload("started");
+        self.active.insert(key.clone(), download);
+        self.request_manifest(swarm, key);
+
+        Ok(payload)
+    }
+
+    /// Resumes the paused downloads `peer` was serving.
+    pub fn resume(&mut self, swarm: &mut Swarm<AppBehaviour>, peer: PeerId) -> Vec<FilePayload> {
+        let keys: Vec<DownloadKey> = self.active.values()
+            .filter(|download| download.paused && download.peer == peer)
+            .map(Download::key)
+            .collect();
+
+        keys.into_iter().filter_map(|key| {
+            let download = self.active.get_mut(&key)?;
+            download.paused = false;
+            download.manifest_pages.clear();
+            download.manifest.clear();
+            let payload = download.payload("started");
+            self.request_manifest(swarm, key);
+            Some(payload)
+        }).collect()
+    }
+
+    /// The room and file a request is about.
+    pub fn request_of(&self, request_id: &OutboundRequestId) -> Option<(String, String, Option<u64>)> {
+        self.requests.get(request_id).map(|((room_id, file_id), index)| (room_id.clone(), file_id.clone(), *index))
+    }
+
+    /// Asks for the next page of the manifest, or checks what is on disk already once all of it is in.
+    pub fn on_manifest(&mut self, swarm: &mut Swarm<AppBehaviour>, request_id: OutboundRequestId, page: &[u8]) -> Option<FilePayload> {
+        let (key, _) = self.requests.remove(&request_id)?;
+        let download = self.active.get_mut(&key)?;
+        download.in_flight = download.in_flight.saturating_sub(1);
+
+        match download.add_manifest_page(page) {
+            Ok(false) => self.request_manifest(swarm, key),
+            Ok(true) => self.check_partial(key),
+            Err(e) => return self.fail(&key, e),
+        }
+        None
+    }
+
+    /// Asks for the chunks the disk is missing, once they were counted.
+    pub fn on_checked(&mut self, swarm: &mut Swarm<AppBehaviour>, key: DownloadKey, checked: Result<(VecDeque<u64>, i64)>) -> Option<FilePayload> {
+        let download = self.active.get_mut(&key)?;
+        if download.paused {
+            return None;
+        }
+
+        match checked {
+            Ok((pending, received)) => {
+                download.pending = pending;
+                download.received = received;
+                Some(self.advance(swarm, key))
+            }
+            Err(e) => self.fail(&key, e),
+        }
+    }
+
+    pub fn on_finished(&mut self, key: DownloadKey, finished: Result<PathBuf>) -> Option<FilePayload> {
+        let destination = match finished {
+            Ok(destination) => destination,
+            Err(e) => return self.fail(&key, e),
+        };
+        let download = self.active.remove(&key)?;
+        log::info!("Downloaded file {} to {}", download.offer.file_id, destination.display());
+
+        let mut payload = download.payload("completed");
+        payload.path = Some(destination.to_string_lossy().to_string());
+        Some(payload)
+    }
+
+    /// Verifies and writes the chunk the request asked for, once the network opened it.
+    pub fn on_chunk(&mut self, swarm: &mut Swarm<AppBehaviour>, request_id: OutboundRequestId, chunk: Result<Vec<u8>>) -> Option<FilePayload> {
+        let (key, index) = self.requests.remove(&request_id)?;
+        let download = self.active.get_mut(&key)?;
+        download.in_flight = download.in_flight.saturating_sub(1);
+        if download.paused {
+            return None;
+        }
+
+        let written = chunk.and_then(|chunk| download.write_chunk(index.unwrap_or_default(), &chunk));
+        if let Err(e) = written {
+            return self.fail(&key, e);
+        }
+        Some(self.advance(swarm, key))
+    }
+
+    /// The serving peer went away or refused; a reconnection or another `start` resumes the download.
+    pub fn on_failure(&mut self, request_id: OutboundRequestId, reason: String) -> Option<FilePayload> {
+        let (key, _) = self.requests.remove(&request_id)?;
+        let download = self.active.get_mut(&key)?;
+        download.in_flight = download.in_flight.saturating_sub(1);
+        if download.paused {
+            return None;
+        }
+
+        log::info!("Pausing the download of {}: {}", download.offer.file_id, reason);
+        download.paused = true;
+        download.in_flight = 0;
+        let mut payload = download.payload("paused");
+        payload.error = Some(reason);
+
+        // Answers still on their way belong to the attempt that failed; resuming checks the disk again.
+        self.requests.retain(|_, (request_key, _)| *request_key != key);
+        Some(payload)
+    }
+
+    fn request_manifest(&mut self, swarm: &mut Swarm<AppBehaviour>, key: DownloadKey) {
+        let Some(download) = self.active.get_mut(&key) else { return };
+        let from = download.manifest_pages.len() as u64 / 32;
+        let request = ChunkRequest::Manifest { room_id: key.0.clone(), file_id: key.1.clone(), from };
+        let request_id = swarm.behaviour_mut().file_transfer_mut().send_request(&download.peer, request);
+        download.in_flight += 1;
+        self.requests.insert(request_id, (key, None));
+    }
+
+    /// Asks for more chunks, or finishes the download once every chunk is in.
+    fn advance(&mut self, swarm: &mut Swarm<AppBehaviour>, key: DownloadKey) -> FilePayload {
+        let Some(download) = self.active.get_mut(&key) else { unreachable!("advancing a download that is not active") };
+        while download.in_flight < MAX_IN_FLIGHT {
+            let Some(index) = download.pending.pop_front() else { break };
+            let request = ChunkRequest::Chunk { room_id: key.0.clone(), file_id: key.1.clone(), index };
+            let request_id = swarm.behaviour_mut().file_transfer_mut().send_request(&download.peer, request);
+            download.in_flight += 1;
+            self.requests.insert(request_id, (key.clone(), Some(index)));
+        }
+
+        let payload = download.payload("progress");
+        if download.pending.is_empty() && download.in_flight == 0 {
+            self.finish(key);
+        }
+        payload
+    }
+
+    /// Counts the chunks an earlier attempt left on disk, off the swarm loop.
+    fn check_partial(&self, key: DownloadKey) {
+        let Some(download) = self.active.get(&key) else { return };
+        let (part, chunk_size, manifest, done) = (download.part.clone(), download.offer.chunk_size, download.manifest.clone(), self.done.clone());
+        tokio::task::spawn_blocking(move || {
+            let checked = check_partial(&part, chunk_size, &manifest);
+            let _ = done.send(TransferDone::Checked { key, checked });
+        });
+    }
+
+    /// Moves the file to the blob store and links it into the download directory, off the swarm loop.
+    fn finish(&self, key: DownloadKey) {
+        let Some(download) = self.active.get(&key) else { return };
+        let (blobs, dir, part, offer, done) = (self.blobs.clone(), self.dir.clone(), download.part.clone(), download.offer.clone(), self.done.clone());
+        tokio::task::spawn_blocking(move || {
+            let finished = store_download(&blobs, &dir, &part, &offer);
+            let _ = done.send(TransferDone::Finished { key, finished });
+        });
+    }
+
+    fn fail(&mut self, key: &DownloadKey, error: Error) -> Option<FilePayload> {
+        let download = self.active.remove(key)?;
+        log::warn!("Download of {} failed: {}", download.offer.file_id, error.chain_message());
+        self.requests.retain(|_, (request_key, _)| request_key != key);
+
+        let mut payload = download.payload("failed");
+        payload.error = Some(error.chain_message());
+        Some(payload)
+    }
+}
+
+/// `name` inside `dir`, numbered when a file of that name is already there.
+pub fn unique_destination(dir: &Path, name: &str) -> PathBuf {
+    // Only the last component, the name comes from another peer.
+    let name = Path::new(name).file_name().map(PathBuf::from).unwrap_or_else(|| PathBuf::from("download"));
+    let stem = name.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = name.extension().map(|extension| format!(".{}", extension.to_string_lossy())).unwrap_or_default();
+
+    let mut destination = dir.join(&name);
+    let mut copy = 1;
+    while destination.exists() {
+        destination = dir.join(format!("{stem} ({copy}){extension}"));
+        copy += 1;
+    }
+    destination
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rust-tc-sdk-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_hash_file_chunks_and_manifest() {
+        let dir = scratch_dir("hash");
+        let path = dir.join("data.bin");
+        fs::write(&path, b"0123456789").unwrap();
+
+        let (offer, manifest) = hash_file(&path, 4).unwrap();
+
+        assert_eq!(offer.name, "data.bin");
+        assert_eq!(offer.size, 10);
+        assert_eq!(manifest.len(), 3);
+        assert_eq!(read_chunk(&path, 4, 2).unwrap(), b"89");
+        assert_eq!(parse_manifest(&manifest.concat(), &offer).unwrap(), manifest);
+        assert_eq!(parse_manifest(&manifest[..2].concat(), &offer).unwrap_err().code(), "TRANSFER_FAILED");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_manifest_larger_than_one_response_comes_in_pages() {
+        // What request_response's CBOR codec reads of one response.
+        const RESPONSE_LIMIT: usize = 10 * 1024 * 1024;
+        let manifest: Vec<ChunkHash> = (0..(RESPONSE_LIMIT / 32 + 1) as u64).map(|index| Sha256::digest(index.to_be_bytes()).into()).collect();
+        let bytes = manifest.concat();
+        assert!(bytes.len() > RESPONSE_LIMIT);
+
+        let offer = FileOffer::from(("a".repeat(64), "big.bin".to_string(), manifest.len() as i64, 1, chunk_root(&manifest)));
+        let mut download = Download {
+            room_id: "room".to_string(),
+            offer,
+            peer: PeerId::random(),
+            part: PathBuf::from("unused.part"),
+            manifest_pages: Vec::new(),
+            manifest: Vec::new(),
+            pending: VecDeque::new(),
+            in_flight: 0,
+            received: 0,
+            paused: false,
+        };
+
+        let mut pages = 0;
+        loop {
+            let page = manifest_page(&bytes, download.manifest_pages.len() as u64 / 32).unwrap();
+            // CBOR spends at most two bytes on each byte of the page.
+            assert!(page.len() * 2 < RESPONSE_LIMIT);
+            pages += 1;
+            if download.add_manifest_page(&page).unwrap() {
+                break;
+            }
+        }
+
+        assert_eq!(pages, manifest.len().div_ceil(MANIFEST_PAGE_LEN as usize));
+        assert_eq!(download.manifest, manifest);
+        assert!(manifest_page(&bytes, manifest.len() as u64).unwrap().is_empty());
+        assert_eq!(manifest_page(&bytes, manifest.len() as u64 + 1).unwrap_err().code(), "INVALID_ARGUMENT");
+    }
+
+    #[test]
+    fn test_check_partial_keeps_the_chunks_that_match() {
+        let dir = scratch_dir("partial");
+        let path = dir.join("data.bin");
+        fs::write(&path, b"0123456789").unwrap();
+        let (_, manifest) = hash_file(&path, 4).unwrap();
+
+        let part = dir.join("data.part");
+        fs::write(&part, b"0123xxxx").unwrap();
+
+        assert_eq!(check_partial(&part, 4, &manifest).unwrap(), (VecDeque::from([1, 2]), 4));
+        assert_eq!(check_partial(&dir.join("missing.part"), 4, &manifest).unwrap(), (VecDeque::from([0, 1, 2]), 0));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_offers_with_unusable_chunks_are_refused() {
+        let offer = |size: i64, chunk_size: i64| FileOffer::from(("a".repeat(64), "notes.txt".to_string(), size, chunk_size, "b".repeat(64)));
+
+        assert!(check_offer(&offer(10, 4)).is_ok());
+        assert_eq!(check_offer(&offer(10, 0)).unwrap_err().code(), "INVALID_ARGUMENT");
+        assert_eq!(check_offer(&offer(10, -4)).unwrap_err().code(), "INVALID_ARGUMENT");
+        assert_eq!(check_offer(&offer(10, MAX_CHUNK_SIZE + 1)).unwrap_err().code(), "INVALID_ARGUMENT");
+        assert_eq!(check_offer(&offer(-1, 4)).unwrap_err().code(), "INVALID_ARGUMENT");
+        assert_eq!(check_offer(&FileOffer { file_id: "../x".to_string(), ..offer(10, 4) }).unwrap_err().code(), "INVALID_ARGUMENT");
+        assert!(hash_file(Path::new("unused"), 0).is_err());
+        assert!(read_chunk(Path::new("unused"), -4, 0).is_err());
+    }
+
+    #[test]
+    fn test_rooms_download_the_same_file_apart() {
+        let file_id = "a".repeat(64);
+
+        assert_ne!(partial_name("one", &file_id), partial_name("other", &file_id));
+        assert!(partial_name("one", &file_id).starts_with(&file_id));
+    }
+
+    #[test]
+    fn test_unique_destination_keeps_names_apart() {
+        let dir = scratch_dir("destination");
+        fs::write(dir.join("notes.txt"), b"").unwrap();
+
+        assert_eq!(unique_destination(&dir, "../notes.txt"), dir.join("notes (1).txt"));
+        assert_eq!(unique_destination(&dir, "other.txt"), dir.join("other.txt"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
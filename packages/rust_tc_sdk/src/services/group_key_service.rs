@@ -0,0 +1,236 @@
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use diesel::r2d2::*;
+use diesel::sqlite::SqliteConnection;
+use rand::RngCore;
+
+use crate::entities::group_key::GroupKeyModel;
+use crate::models::error::*;
+use crate::models::group_key::{GroupKey, GROUP_KEY_SIZE};
+use crate::models::room_message::now_millis;
+use crate::schema::group_keys::dsl::*;
+use crate::services::key_vault::KeyVault;
+
+/// The group keys of each room, one per epoch; older epochs are kept to read older messages.
+///
+/// Keys are sealed with the master key once the vault is set up.
+#[derive(Debug, Clone)]
+pub struct GroupKeyService {
+    db_pool: Pool<ConnectionManager<SqliteConnection>>,
+    vault: KeyVault,
+}
+
+impl GroupKeyService {
+    pub fn new(db_pool: Pool<ConnectionManager<SqliteConnection>>, vault: KeyVault) -> Self {
+        GroupKeyService { db_pool, vault }
+    }
+
+    /// Starts a new epoch with a fresh random key.
+    pub fn create_epoch(&self, room: &str) -> Result<(i64, GroupKey)> {
+        log::info!("Starting a new group key epoch in room {}", room);
+        // Sealing itself only needs the master key, the transaction may hold the connection meanwhile.
+        let sealing = self.vault.is_set_up()?;
+        let mut conn = self.db_pool.get()?;
+
+        let mut new_key = [0u8; GROUP_KEY_SIZE];
+        rand::thread_rng().fill_bytes(&mut new_key);
+
+        conn.transaction(|conn| {
+            let latest: Option<i64> = group_keys
+                .filter(room_id.eq(room))
+                .select(diesel::dsl::max(epoch))
+                .first(conn)?;
+            let next = latest.unwrap_or(0) + 1;
+
+            diesel::insert_into(group_keys)
+                .values(self.to_model(room, next, &new_key, sealing)?)
+                .execute(conn)?;
+
+            log::info!("Started group key epoch {} in room {}", next, room);
+            Ok((next, new_key))
+        })
+    }
+
+    /// Stores a key handed out by another member; `false` when the epoch was already known.
+    pub fn add_key(&self, room: &str, key_epoch: i64, group_key: &GroupKey) -> Result<bool> {
+        let model = self.to_model(room, key_epoch, group_key, self.vault.is_set_up()?)?;
+        let mut conn = self.db_pool.get()?;
+        let added = diesel::insert_or_ignore_into(group_keys)
+            .values(model)
+            .execute(&mut conn)?;
+
+        if added > 0 {
+            log::info!("Got the group key of epoch {} in room {}", key_epoch, room);
+        }
+        Ok(added > 0)
+    }
+
+    /// The key of the latest epoch, which new messages are sealed with.
+    pub fn current_key(&self, room: &str) -> Result<Option<(i64, GroupKey)>> {
+        let mut conn = self.db_pool.get()?;
+        let model: Option<GroupKeyModel> = group_keys
+            .filter(room_id.eq(room))
+            .order(epoch.desc())
+            .first(&mut conn)
+            .optional()?;
+
+        model.map(|model| Ok((model.epoch, self.to_key(model)?))).transpose()
+    }
+
+    pub fn get_key(&self, room: &str, key_epoch: i64) -> Result<Option<GroupKey>> {
+        let mut conn = self.db_pool.get()?;
+        let model: Option<GroupKeyModel> = group_keys
+            .filter(room_id.eq(room))
+            .filter(epoch.eq(key_epoch))
+            .first(&mut conn)
+            .optional()?;
+
+        model.map(|model| self.to_key(model)).transpose()
+    }
+
+    /// Every key of the room in the clear, oldest first, as a backup holds them.
+    pub fn export_keys(&self, room: &str) -> Result<Vec<GroupKeyModel>> {
+        let models: Vec<GroupKeyModel> = group_keys
+            .filter(room_id.eq(room))
+            .order(epoch.asc())
+            .load(&mut self.db_pool.get()?)?;
+
+        models.into_iter()
+            .map(|model| {
+                let plain = self.to_key(model.clone())?;
+                Ok(GroupKeyModel { key: plain.to_vec(), encrypted: false, ..model })
+            })
+            .collect()
+    }
+
+    /// The key as it is stored, sealed unless the vault was never set up.
+    pub fn seal(&self, model: GroupKeyModel) -> Result<GroupKeyModel> {
+        if model.encrypted || !self.vault.is_set_up()? {
+            return Ok(model);
+        }
+        let sealed = self.vault.seal(&Self::sealed_for(&model.room_id, model.epoch), &model.key)?;
+        Ok(GroupKeyModel { key: sealed, encrypted: true, ..model })
+    }
+
+    /// Seals the keys written before the vault was set up; returns how many there were.
+    pub fn seal_plain_keys(&self) -> Result<usize> {
+        let plain: Vec<GroupKeyModel> = group_keys.filter(encrypted.eq(false)).load(&mut self.db_pool.get()?)?;
+        if plain.is_empty() {
+            return Ok(0);
+        }
+
+        log::info!("Sealing {} group keys", plain.len());
+        let sealed = plain.into_iter().map(|model| self.seal(model)).collect::<Result<Vec<_>>>()?;
+        let mut conn = self.db_pool.get()?;
+        conn.transaction(|conn| {
+            for model in &sealed {
+                diesel::update(group_keys.filter(room_id.eq(&model.room_id)).filter(epoch.eq(model.epoch)))
+                    .set((key.eq(&model.key), encrypted.eq(model.encrypted)))
+                    .execute(conn)?;
+            }
+            Ok(sealed.len())
+        })
+    }
+
+    pub fn delete_room_keys(&self, room: &str) -> Result<()> {
+        log::info!("Deleting the group keys of room {}", room);
+        let mut conn = self.db_pool.get()?;
+        diesel::delete(group_keys.filter(room_id.eq(room))).execute(&mut conn)?;
+
+        Ok(())
+    }
+
+    /// What a sealed key is bound to, so it cannot pass for the key of another room or epoch.
+    fn sealed_for(room: &str, key_epoch: i64) -> Vec<u8> {
+        format!("group_keys/{room}/{key_epoch}").into_bytes()
+    }
+
+    fn to_model(&self, room: &str, key_epoch: i64, group_key: &GroupKey, sealing: bool) -> Result<GroupKeyModel> {
+        let stored = match sealing {
+            true => self.vault.seal(&Self::sealed_for(room, key_epoch), group_key)?,
+            false => group_key.to_vec(),
+        };
+        Ok(GroupKeyModel::from((room.to_string(), key_epoch, stored, now_millis(), sealing)))
+    }
+
+    fn to_key(&self, model: GroupKeyModel) -> Result<GroupKey> {
+        let plain = match model.encrypted {
+            true => self.vault.open(&Self::sealed_for(&model.room_id, model.epoch), &model.key)?.to_vec(),
+            false => model.key,
+        };
+        plain.try_into().map_err(|_| ErrorKind::InvalidKey("malformed group key".to_string()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::r2d2::{ConnectionManager, Pool};
+
+    use crate::models::master_key::MasterSecret;
+    use crate::services::connection::establish_connection;
+
+    use super::*;
+
+    fn setup_database() -> Pool<ConnectionManager<SqliteConnection>> {
+        let database_url = ":memory:"; // SQLite in-memory database
+        establish_connection(Some(database_url.to_string())).unwrap()
+    }
+
+    fn service(db_pool: Pool<ConnectionManager<SqliteConnection>>) -> GroupKeyService {
+        GroupKeyService::new(db_pool.clone(), KeyVault::new(db_pool))
+    }
+
+    #[test]
+    fn test_epochs_follow_each_other() {
+        let service = service(setup_database());
+        assert!(service.current_key("room").unwrap().is_none());
+
+        let (first, first_key) = service.create_epoch("room").unwrap();
+        let (second, second_key) = service.create_epoch("room").unwrap();
+
+        assert_eq!((first, second), (1, 2));
+        assert_ne!(first_key, second_key);
+        assert_eq!(service.current_key("room").unwrap(), Some((2, second_key)));
+        assert_eq!(service.get_key("room", 1).unwrap(), Some(first_key));
+    }
+
+    #[test]
+    fn test_add_key_keeps_the_first_copy() {
+        let service = service(setup_database());
+
+        assert!(service.add_key("room", 3, &[1u8; GROUP_KEY_SIZE]).unwrap());
+        assert!(!service.add_key("room", 3, &[2u8; GROUP_KEY_SIZE]).unwrap());
+
+        assert_eq!(service.get_key("room", 3).unwrap(), Some([1u8; GROUP_KEY_SIZE]));
+        assert_eq!(service.get_key("room", 4).unwrap(), None);
+    }
+
+    #[test]
+    fn test_keys_are_sealed_at_rest() {
+        let db_pool = setup_database();
+        let vault = KeyVault::new(db_pool.clone());
+        let service = GroupKeyService::new(db_pool.clone(), vault.clone());
+        let stored = |key_epoch: i64| -> GroupKeyModel {
+            group_keys.filter(room_id.eq("room")).filter(epoch.eq(key_epoch)).first(&mut db_pool.get().unwrap()).unwrap()
+        };
+
+        // Written in the clear before the vault, then sealed on unlock.
+        let (_, plain) = service.create_epoch("room").unwrap();
+        assert_eq!(stored(1).key, plain.to_vec());
+
+        vault.unlock(MasterSecret::Passphrase("correct horse".to_string().into())).unwrap();
+        assert_eq!(service.seal_plain_keys().unwrap(), 1);
+        let (_, sealed) = service.create_epoch("room").unwrap();
+        assert_eq!(service.seal_plain_keys().unwrap(), 0);
+
+        for (key_epoch, group_key) in [(1, plain), (2, sealed)] {
+            let stored = stored(key_epoch);
+            assert!(stored.encrypted);
+            assert_ne!(stored.key, group_key.to_vec());
+            assert_eq!(service.get_key("room", key_epoch).unwrap(), Some(group_key));
+        }
+        assert_eq!(service.export_keys("room").unwrap()[0].key, plain.to_vec());
+
+        vault.lock();
+        assert_eq!(service.get_key("room", 1).unwrap_err().code(), "SDK_LOCKED");
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use libp2p::request_response::OutboundRequestId;
+use libp2p::{PeerId, Swarm};
+
+use crate::models::behaviour::AppBehaviour;
+use crate::models::group_key::GroupKeyRequest;
+
+pub const GROUP_KEY_PROTOCOL: &str = "/vichiz/group-key/1";
+
+/// How long before the same peer is asked for the same key again.
+const RETRY_AFTER: Duration = Duration::from_secs(30);
+
+type Asked = (String, Option<i64>, PeerId);
+
+/// Group keys asked to other members, for the epochs this node could not read yet.
+#[derive(Default)]
+pub struct KeyRequests {
+    /// The room each request is about.
+    in_flight: HashMap<OutboundRequestId, String>,
+    asked: HashMap<Asked, Instant>,
+}
+
+impl KeyRequests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks `peer` for the key of `epoch` (the current one when `None`), unless it was asked recently.
+    pub fn request(&mut self, swarm: &mut Swarm<AppBehaviour>, peer: PeerId, request: GroupKeyRequest, now: Instant) {
+        if !self.should_ask((request.room_id.clone(), request.epoch, peer), now) {
+            return;
+        }
+
+        log::info!("Asking {} for the group key of room {} ({:?})", peer, request.room_id, request.epoch);
+        let room_id = request.room_id.clone();
+        let request_id = swarm.behaviour_mut().group_keys_mut().send_request(&peer, request);
+        self.in_flight.insert(request_id, room_id);
+    }
+
+    /// The room an answered or failed request was about.
+    pub fn on_answer(&mut self, request_id: OutboundRequestId) -> Option<String> {
+        self.in_flight.remove(&request_id)
+    }
+
+    pub fn expire(&mut self, now: Instant) {
+        self.asked.retain(|_, asked_at| now.duration_since(*asked_at) < RETRY_AFTER);
+    }
+
+    fn should_ask(&mut self, asked: Asked, now: Instant) -> bool {
+        match self.asked.get(&asked) {
+            Some(asked_at) if now.duration_since(*asked_at) < RETRY_AFTER => false,
+            _ => {
+                self.asked.insert(asked, now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_key_is_not_asked_twice_in_a_row() {
+        let mut requests = KeyRequests::new();
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        assert!(requests.should_ask(("room".to_string(), Some(1), peer), now));
+        assert!(!requests.should_ask(("room".to_string(), Some(1), peer), now));
+        assert!(requests.should_ask(("room".to_string(), Some(2), peer), now));
+
+        requests.expire(now + RETRY_AFTER);
+        assert!(requests.should_ask(("room".to_string(), Some(1), peer), now + RETRY_AFTER));
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use libp2p::request_response::{OutboundFailure, OutboundRequestId};
+use libp2p::{PeerId, Swarm};
+use tokio::sync::oneshot;
+
+use crate::models::behaviour::AppBehaviour;
+use crate::models::error::*;
+use crate::models::invitation::{InviteRequest, InviteResponse};
+
+pub const INVITE_PROTOCOL: &str = "/vichiz/invite/1";
+
+/// How long a joiner waits to reach the inviter before giving up on the invite.
+const REDEEM_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Resolves with what the inviter handed out once it accepted the invite.
+pub type RedeemReply = oneshot::Sender<Result<InviteResponse>>;
+
+struct PendingRedemption {
+    request: InviteRequest,
+    queued_at: Instant,
+    reply: RedeemReply,
+}
+
+/// Invites being redeemed with the swarms that issued them.
+///
+/// A redemption waits here until the inviter is connected, which joining the
+/// room takes care of by dialing the addresses in the invite.
+#[derive(Default)]
+pub struct Redemptions {
+    waiting: HashMap<PeerId, Vec<PendingRedemption>>,
+    in_flight: HashMap<OutboundRequestId, RedeemReply>,
+}
+
+impl Redemptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn redeem(&mut self, swarm: &mut Swarm<AppBehaviour>, inviter: PeerId, request: InviteRequest, reply: RedeemReply) {
+        let pending = PendingRedemption { request, queued_at: Instant::now(), reply };
+        if swarm.is_connected(&inviter) {
+            self.send(swarm, inviter, pending);
+        } else {
+            log::info!("Redeeming the invite once {} connects", inviter);
+            self.waiting.entry(inviter).or_default().push(pending);
+        }
+    }
+
+    pub fn on_connected(&mut self, swarm: &mut Swarm<AppBehaviour>, peer: PeerId) {
+        for pending in self.waiting.remove(&peer).unwrap_or_default() {
+            self.send(swarm, peer, pending);
+        }
+    }
+
+    pub fn on_response(&mut self, request_id: OutboundRequestId, response: InviteResponse) {
+        if let Some(reply) = self.in_flight.remove(&request_id) {
+            let result = match response.rejection.clone() {
+                None => Ok(response),
+                Some(reason) => Err(ErrorKind::InviteRejected(reason).into()),
+            };
+            let _ = reply.send(result);
+        }
+    }
+
+    pub fn on_failure(&mut self, request_id: OutboundRequestId, error: OutboundFailure) {
+        if let Some(reply) = self.in_flight.remove(&request_id) {
+            let _ = reply.send(Err(ErrorKind::InviteRejected(format!("the inviter did not answer: {error}")).into()));
+        }
+    }
+
+    /// Fails the redemptions whose inviter never connected.
+    pub fn expire(&mut self, now: Instant) {
+        for (peer, queue) in self.waiting.iter_mut() {
+            let (expired, kept) = std::mem::take(queue).into_iter()
+                .partition(|pending| now.duration_since(pending.queued_at) >= REDEEM_TIMEOUT);
+            *queue = kept;
+
+            for pending in expired {
+                log::warn!("Could not reach inviter {} to redeem invite {}", peer, pending.request.invite_id);
+                let _ = pending.reply.send(Err(ErrorKind::InviteRejected("the inviter could not be reached".to_string()).into()));
+            }
+        }
+        self.waiting.retain(|_, queue| !queue.is_empty());
+    }
+
+    fn send(&mut self, swarm: &mut Swarm<AppBehaviour>, peer: PeerId, pending: PendingRedemption) {
+        let request_id = swarm.behaviour_mut().invites_mut().send_request(&peer, pending.request);
+        self.in_flight.insert(request_id, pending.reply);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expire_fails_unreachable_inviters() {
+        let mut redemptions = Redemptions::new();
+        let peer = PeerId::random();
+        let (reply, mut response) = oneshot::channel();
+
+        let request = InviteRequest::from(("room".to_string(), "invite".to_string(), "peer".to_string(), vec![]));
+        redemptions.waiting.entry(peer).or_default().push(PendingRedemption { request, queued_at: Instant::now(), reply });
+
+        redemptions.expire(Instant::now());
+        assert!(response.try_recv().is_err());
+
+        redemptions.expire(Instant::now() + REDEEM_TIMEOUT);
+        assert_eq!(response.try_recv().unwrap().unwrap_err().code(), "INVITE_REJECTED");
+        assert!(redemptions.waiting.is_empty());
+    }
+}
@@ -0,0 +1,111 @@
+use diesel::{ExpressionMethods, QueryResult, RunQueryDsl};
+use diesel::prelude::*;
+use diesel::r2d2::*;
+use diesel::sqlite::SqliteConnection;
+use uuid::Uuid;
+
+use crate::entities::invite::Invite;
+use crate::models::error::*;
+use crate::models::room_message::now_millis;
+use crate::schema::invites::dsl::*;
+
+#[derive(Debug, Clone)]
+pub struct InviteService {
+    db_pool: Pool<ConnectionManager<SqliteConnection>>,
+}
+
+impl InviteService {
+    pub fn new(db_pool: Pool<ConnectionManager<SqliteConnection>>) -> Self {
+        InviteService { db_pool }
+    }
+
+    pub fn create_invite(&self, room: &str, expires: Option<i64>, max: Option<i32>) -> Result<Invite> {
+        log::info!("Creating invite to room {}", room);
+        let invite = Invite::from((Uuid::new_v4().to_string(), room.to_string(), expires, max, 0, now_millis()));
+        let mut conn = self.db_pool.get()?;
+
+        diesel::insert_into(invites)
+            .values(&invite)
+            .execute(&mut conn)?;
+
+        log::info!("Created invite {} to room {}", invite.id, room);
+        Ok(invite)
+    }
+
+    /// Counts one use of the invite, unless it is unknown, expired or used up.
+    pub fn redeem(&self, invite_id: &str, room: &str, now: i64) -> Result<()> {
+        log::info!("Redeeming invite {} to room {}", invite_id, room);
+        let mut conn = self.db_pool.get()?;
+
+        conn.transaction(|conn| {
+            let result: QueryResult<Invite> = invites
+                .filter(id.eq(invite_id))
+                .filter(room_id.eq(room))
+                .first(conn);
+
+            let invite = match result {
+                Ok(invite) => invite,
+                Err(diesel::result::Error::NotFound) => return Err(ErrorKind::InviteRejected("unknown invite".to_string()).into()),
+                Err(e) => return Err(e.into()),
+            };
+
+            if invite.expires_at.is_some_and(|expires| expires <= now) {
+                return Err(ErrorKind::InviteRejected("the invite expired".to_string()).into());
+            }
+            if invite.max_uses.is_some_and(|max| invite.uses >= max) {
+                return Err(ErrorKind::InviteRejected("the invite was used up".to_string()).into());
+            }
+
+            diesel::update(invites.filter(id.eq(invite_id)))
+                .set(uses.eq(uses + 1))
+                .execute(conn)?;
+
+            log::info!("Redeemed invite {} to room {}", invite_id, room);
+            Ok(())
+        })
+    }
+
+    pub fn delete_room_invites(&self, room: &str) -> Result<()> {
+        log::info!("Deleting invites to room {}", room);
+        let mut conn = self.db_pool.get()?;
+        diesel::delete(invites.filter(room_id.eq(room))).execute(&mut conn)?;
+
+        log::info!("Deleted invites to room {}", room);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::r2d2::{ConnectionManager, Pool};
+
+    use crate::services::connection::establish_connection;
+
+    use super::*;
+
+    fn setup_database() -> Pool<ConnectionManager<SqliteConnection>> {
+        let database_url = ":memory:"; // SQLite in-memory database
+        establish_connection(Some(database_url.to_string())).unwrap()
+    }
+
+    #[test]
+    fn test_redeem_counts_uses() {
+        let service = InviteService::new(setup_database());
+        let invite = service.create_invite("room", None, Some(1)).unwrap();
+
+        assert!(service.redeem(&invite.id, "other-room", now_millis()).is_err());
+        service.redeem(&invite.id, "room", now_millis()).unwrap();
+
+        let error = service.redeem(&invite.id, "room", now_millis()).unwrap_err();
+        assert_eq!(error.code(), "INVITE_REJECTED");
+    }
+
+    #[test]
+    fn test_redeem_expired_invite() {
+        let service = InviteService::new(setup_database());
+        let invite = service.create_invite("room", Some(1_000), None).unwrap();
+
+        assert!(service.redeem(&invite.id, "room", 999).is_ok());
+        assert!(service.redeem(&invite.id, "room", 1_000).is_err());
+    }
+}
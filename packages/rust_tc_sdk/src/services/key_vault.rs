@@ -0,0 +1,156 @@
+use std::sync::{Arc, RwLock};
+
+use diesel::{OptionalExtension, QueryDsl, RunQueryDsl};
+use diesel::r2d2::*;
+use diesel::sqlite::SqliteConnection;
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use crate::entities::key_vault::VaultModel;
+use crate::models::error::*;
+use crate::models::master_key::{open_at_rest, seal_at_rest, MasterKey, MasterSecret};
+use crate::models::room_message::now_millis;
+use crate::schema::key_vault::dsl::*;
+
+const VAULT_ID: i32 = 1;
+
+const SALT_SIZE: usize = 16;
+
+/// What the verifier seals.
+const VERIFIER_AAD: &[u8] = b"vichiz/key-vault/1";
+
+/// Holds the master key while the SDK is unlocked; shared by everything that seals keys at rest.
+///
+/// Until the first unlock there is no vault, and keys are kept as older versions wrote them.
+#[derive(Clone)]
+pub struct KeyVault {
+    db_pool: Pool<ConnectionManager<SqliteConnection>>,
+    master: Arc<RwLock<Option<MasterKey>>>,
+}
+
+impl KeyVault {
+    pub fn new(db_pool: Pool<ConnectionManager<SqliteConnection>>) -> Self {
+        KeyVault { db_pool, master: Arc::new(RwLock::new(None)) }
+    }
+
+    /// The same vault, still unlocked if it was, on another pool of the same database.
+    pub fn with_pool(&self, db_pool: Pool<ConnectionManager<SqliteConnection>>) -> Self {
+        KeyVault { db_pool, master: self.master.clone() }
+    }
+
+    /// Whether keys are sealed at rest, which they are from the first unlock on.
+    pub fn is_set_up(&self) -> Result<bool> {
+        Ok(self.get_vault()?.is_some())
+    }
+
+    pub fn is_locked(&self) -> Result<bool> {
+        Ok(self.master.read().unwrap_or_else(|e| e.into_inner()).is_none() && self.is_set_up()?)
+    }
+
+    /// Derives the master key and keeps it in memory; the first unlock sets the vault up with it.
+    ///
+    /// Slow for a passphrase, on purpose.
+    pub fn unlock(&self, secret: MasterSecret) -> Result<()> {
+        let master_key = match self.get_vault()? {
+            Some(vault) => {
+                if vault.kdf != secret.kdf() {
+                    return Err(ErrorKind::WrongMasterKey.into());
+                }
+                let master_key = secret.derive(&vault.salt)?;
+                open_at_rest(&master_key, VERIFIER_AAD, &vault.verifier).map_err(|_| ErrorKind::WrongMasterKey)?;
+                master_key
+            }
+            None => self.set_up(&secret)?,
+        };
+
+        *self.master.write().unwrap_or_else(|e| e.into_inner()) = Some(master_key);
+        log::info!("Unlocked the key vault");
+        Ok(())
+    }
+
+    /// Forgets the master key.
+    pub fn lock(&self) {
+        *self.master.write().unwrap_or_else(|e| e.into_inner()) = None;
+        log::info!("Locked the key vault");
+    }
+
+    pub fn seal(&self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+        seal_at_rest(&self.master_key()?, aad, plaintext)
+    }
+
+    pub fn open(&self, aad: &[u8], sealed: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+        open_at_rest(&self.master_key()?, aad, sealed)
+    }
+
+    fn master_key(&self) -> Result<MasterKey> {
+        self.master.read().unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .ok_or_else(|| ErrorKind::SdkLocked.into())
+    }
+
+    fn set_up(&self, secret: &MasterSecret) -> Result<MasterKey> {
+        log::info!("Setting the key vault up");
+        let mut new_salt = vec![0u8; SALT_SIZE];
+        rand::thread_rng().fill_bytes(&mut new_salt);
+        let master_key = secret.derive(&new_salt)?;
+        let sealed = seal_at_rest(&master_key, VERIFIER_AAD, VERIFIER_AAD)?;
+
+        let mut conn = self.db_pool.get()?;
+        diesel::insert_into(key_vault)
+            .values(VaultModel::from((VAULT_ID, secret.kdf().to_string(), new_salt, sealed, now_millis())))
+            .execute(&mut conn)?;
+        Ok(master_key)
+    }
+
+    fn get_vault(&self) -> Result<Option<VaultModel>> {
+        let mut conn = self.db_pool.get()?;
+        let vault = key_vault.find(VAULT_ID).first(&mut conn).optional()?;
+        Ok(vault)
+    }
+}
+
+impl std::fmt::Debug for KeyVault {
+    // Never the master key itself.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyVault").field("unlocked", &self.master.read().map(|key| key.is_some()).unwrap_or_default()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use diesel::r2d2::{ConnectionManager, Pool};
+
+    use crate::services::connection::establish_connection;
+
+    use super::*;
+
+    fn setup_database() -> Pool<ConnectionManager<SqliteConnection>> {
+        let database_url = ":memory:"; // SQLite in-memory database
+        establish_connection(Some(database_url.to_string())).unwrap()
+    }
+
+    fn passphrase(words: &str) -> MasterSecret {
+        MasterSecret::Passphrase(Zeroizing::new(words.to_string()))
+    }
+
+    #[test]
+    fn test_only_the_first_passphrase_unlocks() {
+        let vault = KeyVault::new(setup_database());
+        assert!(!vault.is_locked().unwrap());
+
+        vault.unlock(passphrase("correct horse")).unwrap();
+        let sealed = vault.seal(b"room", b"secret").unwrap();
+        vault.lock();
+        assert!(vault.is_locked().unwrap());
+        assert_eq!(vault.open(b"room", &sealed).unwrap_err().code(), "SDK_LOCKED");
+
+        assert_eq!(vault.unlock(passphrase("battery staple")).unwrap_err().code(), "WRONG_MASTER_KEY");
+        let host = MasterSecret::host(&STANDARD.encode([7u8; 32])).unwrap();
+        assert_eq!(vault.unlock(host).unwrap_err().code(), "WRONG_MASTER_KEY");
+
+        vault.unlock(passphrase("correct horse")).unwrap();
+        assert_eq!(vault.open(b"room", &sealed).unwrap().as_slice(), b"secret");
+    }
+}
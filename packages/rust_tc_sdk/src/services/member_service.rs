@@ -0,0 +1,185 @@
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use diesel::dsl::exists;
+use diesel::r2d2::*;
+use diesel::sqlite::SqliteConnection;
+use libp2p::identity;
+
+use crate::entities::member::Member;
+use crate::models::error::*;
+use crate::models::member::MemberRole;
+use crate::schema::room_members::dsl::*;
+
+/// The allow-list of each room: only what its members publish is accepted.
+#[derive(Debug, Clone)]
+pub struct MemberService {
+    db_pool: Pool<ConnectionManager<SqliteConnection>>,
+}
+
+impl MemberService {
+    pub fn new(db_pool: Pool<ConnectionManager<SqliteConnection>>) -> Self {
+        MemberService { db_pool }
+    }
+
+    /// Adds the member, or updates its role when it already is one.
+    pub fn add_member(&self, member: &Member) -> Result<()> {
+        log::info!("Adding {} to room {} as {}", member.peer_id, member.room_id, member.role);
+        let mut conn = self.db_pool.get()?;
+
+        conn.transaction(|conn| {
+            // A change that does not carry the member's public key keeps the one already known.
+            let mut member = member.clone();
+            if member.public_key.is_none() {
+                member.public_key = room_members
+                    .filter(room_id.eq(&member.room_id))
+                    .filter(peer_id.eq(&member.peer_id))
+                    .select(public_key)
+                    .first(conn)
+                    .optional()?
+                    .flatten();
+            }
+
+            diesel::replace_into(room_members)
+                .values(&member)
+                .execute(conn)?;
+            Ok(())
+        })
+    }
+
+    /// Remembers the public key of a member, to seal group keys to it.
+    pub fn set_public_key(&self, room: &str, key: &identity::PublicKey) -> Result<()> {
+        let mut conn = self.db_pool.get()?;
+        diesel::update(room_members.filter(room_id.eq(room)).filter(peer_id.eq(key.to_peer_id().to_string())))
+            .set(public_key.eq(key.encode_protobuf()))
+            .execute(&mut conn)?;
+
+        Ok(())
+    }
+
+    /// Makes `owner` the owner of a room nobody is a member of yet; `false` when it already had members.
+    pub fn claim_room(&self, room: &str, owner: &identity::PublicKey, now: i64) -> Result<bool> {
+        let mut conn = self.db_pool.get()?;
+        let has_members: bool = diesel::select(exists(room_members.filter(room_id.eq(room))))
+            .get_result(&mut conn)?;
+        if has_members {
+            return Ok(false);
+        }
+
+        let peer = owner.to_peer_id().to_string();
+        log::info!("Claiming room {} for {}", room, peer);
+        let owner = Member::from((room.to_string(), peer.clone(), MemberRole::Owner.as_str().to_string(), peer, now, Some(owner.encode_protobuf())));
+        diesel::insert_into(room_members)
+            .values(&owner)
+            .execute(&mut conn)?;
+
+        Ok(true)
+    }
+
+    /// `false` when `peer` was not a member.
+    pub fn remove_member(&self, room: &str, peer: &str) -> Result<bool> {
+        log::info!("Removing {} from room {}", peer, room);
+        let mut conn = self.db_pool.get()?;
+        let removed = diesel::delete(room_members.filter(room_id.eq(room)).filter(peer_id.eq(peer)))
+            .execute(&mut conn)?;
+
+        Ok(removed > 0)
+    }
+
+    pub fn get_members(&self, room: &str) -> Result<Vec<Member>> {
+        let mut conn = self.db_pool.get()?;
+        let members = room_members
+            .filter(room_id.eq(room))
+            .order(added_at.asc())
+            .load(&mut conn)?;
+
+        Ok(members)
+    }
+
+    pub fn is_member(&self, room: &str, peer: &str) -> Result<bool> {
+        let mut conn = self.db_pool.get()?;
+        let found = diesel::select(exists(room_members.filter(room_id.eq(room)).filter(peer_id.eq(peer))))
+            .get_result(&mut conn)?;
+
+        Ok(found)
+    }
+
+    pub fn is_owner(&self, room: &str, peer: &str) -> Result<bool> {
+        let mut conn = self.db_pool.get()?;
+        let found = diesel::select(exists(room_members
+            .filter(room_id.eq(room))
+            .filter(peer_id.eq(peer))
+            .filter(role.eq(MemberRole::Owner.as_str()))))
+            .get_result(&mut conn)?;
+
+        Ok(found)
+    }
+
+    pub fn delete_room_members(&self, room: &str) -> Result<()> {
+        log::info!("Deleting the members of room {}", room);
+        let mut conn = self.db_pool.get()?;
+        diesel::delete(room_members.filter(room_id.eq(room))).execute(&mut conn)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::r2d2::{ConnectionManager, Pool};
+
+    use crate::services::connection::establish_connection;
+
+    use super::*;
+
+    fn setup_database() -> Pool<ConnectionManager<SqliteConnection>> {
+        let database_url = ":memory:"; // SQLite in-memory database
+        establish_connection(Some(database_url.to_string())).unwrap()
+    }
+
+    fn member(peer: &str, member_role: MemberRole) -> Member {
+        Member::from(("room".to_string(), peer.to_string(), member_role.as_str().to_string(), "owner".to_string(), 1, None))
+    }
+
+    #[test]
+    fn test_claim_room_only_once() {
+        let service = MemberService::new(setup_database());
+        let owner = identity::Keypair::generate_ecdsa().public();
+        let intruder = identity::Keypair::generate_ecdsa().public();
+
+        assert!(service.claim_room("room", &owner, 1).unwrap());
+        assert!(!service.claim_room("room", &intruder, 2).unwrap());
+
+        assert!(service.is_owner("room", &owner.to_peer_id().to_string()).unwrap());
+        assert!(!service.is_member("room", &intruder.to_peer_id().to_string()).unwrap());
+    }
+
+    #[test]
+    fn test_changes_keep_the_known_public_key() {
+        let service = MemberService::new(setup_database());
+        let key = identity::Keypair::generate_ecdsa().public();
+        let peer = key.to_peer_id().to_string();
+
+        service.add_member(&member(&peer, MemberRole::Member)).unwrap();
+        service.set_public_key("room", &key).unwrap();
+        service.add_member(&member(&peer, MemberRole::Owner)).unwrap();
+
+        let members = service.get_members("room").unwrap();
+        assert_eq!(members[0].public_key, Some(key.encode_protobuf()));
+        assert_eq!(members[0].role, "owner");
+    }
+
+    #[test]
+    fn test_add_and_remove_members() {
+        let service = MemberService::new(setup_database());
+        service.add_member(&member("peer", MemberRole::Member)).unwrap();
+        assert!(service.is_member("room", "peer").unwrap());
+        assert!(!service.is_owner("room", "peer").unwrap());
+
+        service.add_member(&member("peer", MemberRole::Owner)).unwrap();
+        assert!(service.is_owner("room", "peer").unwrap());
+        assert_eq!(service.get_members("room").unwrap().len(), 1);
+
+        assert!(service.remove_member("room", "peer").unwrap());
+        assert!(!service.remove_member("room", "peer").unwrap());
+        assert!(service.get_members("room").unwrap().is_empty());
+    }
+}
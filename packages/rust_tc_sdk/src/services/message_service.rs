@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+
+use diesel::{BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use diesel::r2d2::*;
+use diesel::sqlite::SqliteConnection;
+
+use crate::entities::message::Message;
+use crate::models::error::*;
+use crate::models::message_sync::SyncMark;
+use crate::schema::blobs;
+use crate::schema::messages::dsl::*;
+
+/// How many messages a page holds when the caller does not say.
+const DEFAULT_PAGE_SIZE: i64 = 50;
+
+/// The largest page a caller can ask for.
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// The history of every room, sent and received messages alike.
+#[derive(Debug, Clone)]
+pub struct MessageService {
+    db_pool: Pool<ConnectionManager<SqliteConnection>>,
+}
+
+impl MessageService {
+    pub fn new(db_pool: Pool<ConnectionManager<SqliteConnection>>) -> Self {
+        MessageService { db_pool }
+    }
+
+    /// `false` when the message was already stored, gossip can deliver it more than once.
+    ///
+    /// A message offering a file holds a reference to its blob.
+    pub fn store_message(&self, message: &Message) -> Result<bool> {
+        log::info!("Storing message {} of room {}", message.id, message.room_id);
+        let mut conn = self.db_pool.get()?;
+        conn.transaction(|conn| insert_message(conn, message))
+    }
+
+    /// The latest `limit` messages sent before `before`, oldest first.
+    pub fn get_messages(&self, room: &str, before: Option<i64>, limit: Option<i64>) -> Result<Vec<Message>> {
+        log::info!("Getting messages of room {}", room);
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(ErrorKind::InvalidArgument(format!("limit must be between 1 and {MAX_PAGE_SIZE}")).into());
+        }
+
+        let mut conn = self.db_pool.get()?;
+        let mut query = messages
+            .filter(room_id.eq(room))
+            .order((sent_at.desc(), id.desc()))
+            .limit(limit)
+            .into_boxed();
+        if let Some(before) = before {
+            query = query.filter(sent_at.lt(before));
+        }
+
+        let mut page: Vec<Message> = query.load(&mut conn)?;
+        page.reverse();
+        Ok(page)
+    }
+
+    /// The latest message held from each sender of the room.
+    pub fn high_water_marks(&self, room: &str) -> Result<HashMap<String, SyncMark>> {
+        let mut conn = self.db_pool.get()?;
+        let senders: Vec<String> = messages
+            .filter(room_id.eq(room))
+            .select(sender_peer_id)
+            .distinct()
+            .load(&mut conn)?;
+
+        let mut marks = HashMap::new();
+        for sender in senders {
+            let (latest_sent_at, latest_id): (i64, String) = messages
+                .filter(room_id.eq(room))
+                .filter(sender_peer_id.eq(&sender))
+                .order((sent_at.desc(), id.desc()))
+                .select((sent_at, id))
+                .first(&mut conn)?;
+            marks.insert(sender, SyncMark::from((latest_sent_at, latest_id)));
+        }
+        Ok(marks)
+    }
+
+    /// Up to `limit` messages that can be synced, sent after the marks and after `after`, oldest first,
+    /// with where the next page starts when more are left.
+    pub fn messages_after(&self, room: &str, marks: &HashMap<String, SyncMark>, after: Option<&SyncMark>, limit: usize) -> Result<(Vec<Message>, Option<SyncMark>)> {
+        let mut conn = self.db_pool.get()?;
+        let senders: Vec<String> = messages
+            .filter(room_id.eq(room))
+            .select(sender_peer_id)
+            .distinct()
+            .load(&mut conn)?;
+
+        // Whatever comes after the first `limit` missing messages of every sender is not needed yet.
+        let mut missing = Vec::new();
+        for sender in senders {
+            let mut query = messages
+                .filter(room_id.eq(room))
+                .filter(sender_peer_id.eq(&sender))
+                // Messages kept before their envelopes were have nothing to prove their author with.
+                .filter(envelope.is_not_null())
+                .order((sent_at.asc(), id.asc()))
+                .limit(limit as i64 + 1)
+                .into_boxed();
+            for mark in [marks.get(&sender), after].into_iter().flatten() {
+                query = query.filter(sent_at.gt(mark.sent_at).or(sent_at.eq(mark.sent_at).and(id.gt(mark.id.clone()))));
+            }
+            missing.extend(query.load::<Message>(&mut conn)?);
+        }
+
+        missing.sort_by(|a, b| (a.sent_at, &a.id).cmp(&(b.sent_at, &b.id)));
+        let more = missing.len() > limit;
+        missing.truncate(limit);
+        let next = missing.last()
+            .filter(|_| more)
+            .map(|last| SyncMark::from((last.sent_at, last.id.clone())));
+        Ok((missing, next))
+    }
+
+    /// Whether the message is stored already.
+    pub fn has_message(&self, room: &str, message_id: &str) -> Result<bool> {
+        let mut conn = self.db_pool.get()?;
+        let count: i64 = messages
+            .filter(room_id.eq(room))
+            .filter(id.eq(message_id))
+            .count()
+            .get_result(&mut conn)?;
+        Ok(count > 0)
+    }
+
+    /// Deletes one message and releases the blob it held.
+    pub fn remove_message(&self, room: &str, message_id: &str) -> Result<()> {
+        log::info!("Removing message {} of room {}", message_id, room);
+        let mut conn = self.db_pool.get()?;
+        conn.transaction(|conn| {
+            let held: Option<Option<String>> = messages
+                .filter(room_id.eq(room))
+                .filter(id.eq(message_id))
+                .select(file_id)
+                .first(conn)
+                .optional()?;
+            if let Some(file) = held.flatten() {
+                diesel::update(blobs::table.filter(blobs::hash.eq(file)))
+                    .set(blobs::ref_count.eq(blobs::ref_count - 1))
+                    .execute(conn)?;
+            }
+
+            diesel::delete(messages.filter(room_id.eq(room)).filter(id.eq(message_id))).execute(conn)?;
+            Ok(())
+        })
+    }
+
+    /// Deletes the room's history and releases the blobs its messages held.
+    pub fn delete_room_messages(&self, room: &str) -> Result<()> {
+        log::info!("Deleting the messages of room {}", room);
+        let mut conn = self.db_pool.get()?;
+        conn.transaction(|conn| release_room_messages(conn, room))
+    }
+}
+
+/// Stores the message unless it is there already, taking a reference to the blob it offers.
+pub(crate) fn insert_message(conn: &mut SqliteConnection, message: &Message) -> Result<bool> {
+    let stored = diesel::insert_or_ignore_into(messages)
+        .values(message)
+        .execute(conn)? > 0;
+
+    if let Some(file) = message.file_id.as_ref().filter(|_| stored) {
+        diesel::insert_into(blobs::table)
+            .values((blobs::hash.eq(file), blobs::ref_count.eq(1)))
+            .on_conflict(blobs::hash)
+            .do_update()
+            .set(blobs::ref_count.eq(blobs::ref_count + 1))
+            .execute(conn)?;
+    }
+    Ok(stored)
+}
+
+/// Deletes the messages of the room, releasing the blobs they held.
+pub(crate) fn release_room_messages(conn: &mut SqliteConnection, room: &str) -> Result<()> {
+    let held: Vec<(Option<String>, i64)> = messages
+        .filter(room_id.eq(room))
+        .filter(file_id.is_not_null())
+        .group_by(file_id)
+        .select((file_id, diesel::dsl::count_star()))
+        .load(conn)?;
+    for (file, count) in held.into_iter().filter_map(|(file, count)| Some((file?, count))) {
+        diesel::update(blobs::table.filter(blobs::hash.eq(file)))
+            .set(blobs::ref_count.eq(blobs::ref_count - count))
+            .execute(conn)?;
+    }
+
+    diesel::delete(messages.filter(room_id.eq(room))).execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::r2d2::{ConnectionManager, Pool};
+
+    use crate::models::callback_payload::MessagePayload;
+    use crate::models::file_transfer::FileOffer;
+    use crate::models::message::MessageStatus;
+    use crate::services::connection::establish_connection;
+
+    use super::*;
+
+    fn setup_database() -> Pool<ConnectionManager<SqliteConnection>> {
+        let database_url = ":memory:"; // SQLite in-memory database
+        establish_connection(Some(database_url.to_string())).unwrap()
+    }
+
+    fn message(message_id: &str, timestamp: i64) -> Message {
+        let delivered = MessagePayload::from((
+            "room".to_string(),
+            "peer".to_string(),
+            message_id.to_string(),
+            "chat".to_string(),
+            format!("message {message_id}"),
+            timestamp,
+            false,
+            None,
+        ));
+        Message::stored(&delivered, MessageStatus::Received, timestamp + 1, Some(vec![1]))
+    }
+
+    #[test]
+    fn test_store_message_once() {
+        let service = MessageService::new(setup_database());
+
+        assert!(service.store_message(&message("a", 1)).unwrap());
+        assert!(!service.store_message(&message("a", 1)).unwrap());
+        assert_eq!(service.get_messages("room", None, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_pages_go_back_in_time() {
+        let service = MessageService::new(setup_database());
+        for (message_id, timestamp) in [("a", 1), ("b", 2), ("c", 3), ("d", 4)] {
+            service.store_message(&message(message_id, timestamp)).unwrap();
+        }
+
+        let ids = |page: Vec<Message>| page.into_iter().map(|message| message.id).collect::<Vec<_>>();
+        assert_eq!(ids(service.get_messages("room", None, Some(2)).unwrap()), ["c", "d"]);
+        assert_eq!(ids(service.get_messages("room", Some(3), Some(2)).unwrap()), ["a", "b"]);
+        assert!(service.get_messages("other", None, None).unwrap().is_empty());
+        assert_eq!(service.get_messages("room", None, Some(0)).unwrap_err().code(), "INVALID_ARGUMENT");
+    }
+
+    #[test]
+    fn test_remove_message_releases_its_blob() {
+        let db_pool = setup_database();
+        let service = MessageService::new(db_pool.clone());
+        let offer = FileOffer::from(("f".repeat(64), "notes.txt".to_string(), 1, 1, "c".repeat(64)));
+        let offering = MessagePayload::from(("room".to_string(), "peer".to_string(), "a".to_string(), "chat".to_string(), "notes.txt".to_string(), 1, false, Some(offer)));
+        service.store_message(&Message::stored(&offering, MessageStatus::Sent, 1, None)).unwrap();
+        let refs = || blobs::table.find("f".repeat(64)).select(blobs::ref_count).first::<i64>(&mut db_pool.get().unwrap()).unwrap();
+        assert_eq!(refs(), 1);
+
+        service.remove_message("room", "a").unwrap();
+
+        assert_eq!(refs(), 0);
+        assert!(service.get_messages("room", None, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_messages_after_the_marks() {
+        let service = MessageService::new(setup_database());
+        for (message_id, timestamp) in [("a", 1), ("b", 1), ("c", 2), ("d", 3)] {
+            service.store_message(&message(message_id, timestamp)).unwrap();
+        }
+        let mark = |at: i64, message_id: &str| SyncMark::from((at, message_id.to_string()));
+        assert_eq!(service.high_water_marks("room").unwrap(), HashMap::from([("peer".to_string(), mark(3, "d"))]));
+
+        let ids = |(page, next): (Vec<Message>, Option<SyncMark>)| (page.into_iter().map(|message| message.id).collect::<Vec<_>>(), next);
+        // "b" went out in the same millisecond as "a".
+        let marks = HashMap::from([("peer".to_string(), mark(1, "a"))]);
+        assert_eq!(ids(service.messages_after("room", &marks, None, 10).unwrap()), (vec!["b".to_string(), "c".to_string(), "d".to_string()], None));
+
+        let (first, next) = ids(service.messages_after("room", &HashMap::new(), None, 2).unwrap());
+        assert_eq!((first, next.clone()), (vec!["a".to_string(), "b".to_string()], Some(mark(1, "b"))));
+        assert_eq!(ids(service.messages_after("room", &HashMap::new(), next.as_ref(), 2).unwrap()), (vec!["c".to_string(), "d".to_string()], None));
+    }
+
+    #[test]
+    fn test_messages_without_envelopes_are_not_synced() {
+        let service = MessageService::new(setup_database());
+        let mut kept = message("a", 1);
+        kept.envelope = None;
+        service.store_message(&kept).unwrap();
+        service.store_message(&message("b", 2)).unwrap();
+
+        let (page, next) = service.messages_after("room", &HashMap::new(), None, 1).unwrap();
+        assert_eq!(page.into_iter().map(|message| message.id).collect::<Vec<_>>(), ["b"]);
+        assert_eq!(next, None);
+        assert!(service.has_message("room", "a").unwrap());
+        assert!(!service.has_message("room", "c").unwrap());
+    }
+}
@@ -0,0 +1,39 @@
+use std::collections::{HashMap, HashSet};
+
+use libp2p::request_response::OutboundRequestId;
+use libp2p::{PeerId, Swarm};
+
+use crate::models::behaviour::AppBehaviour;
+use crate::models::message_sync::SyncRequest;
+
+pub const MESSAGE_SYNC_PROTOCOL: &str = "/vichiz/message-sync/1";
+
+/// Catch-up requests sent to other members, at most one per room and peer at a time.
+#[derive(Default)]
+pub struct SyncRequests {
+    in_flight: HashMap<OutboundRequestId, (SyncRequest, PeerId)>,
+    syncing: HashSet<(String, PeerId)>,
+}
+
+impl SyncRequests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request(&mut self, swarm: &mut Swarm<AppBehaviour>, peer: PeerId, request: SyncRequest) {
+        if !self.syncing.insert((request.room_id.clone(), peer)) {
+            return;
+        }
+
+        log::info!("Syncing room {} with {}", request.room_id, peer);
+        let request_id = swarm.behaviour_mut().message_sync_mut().send_request(&peer, request.clone());
+        self.in_flight.insert(request_id, (request, peer));
+    }
+
+    /// The request an answer or failure was about, and the peer it went to; the peer can be asked again.
+    pub fn on_answer(&mut self, request_id: OutboundRequestId) -> Option<(SyncRequest, PeerId)> {
+        let (request, peer) = self.in_flight.remove(&request_id)?;
+        self.syncing.remove(&(request.room_id.clone(), peer));
+        Some((request, peer))
+    }
+}
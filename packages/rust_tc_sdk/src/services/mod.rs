@@ -6,4 +6,22 @@ pub(crate) mod network;
 pub(crate) mod setup;
 pub(crate) mod database_url;
 pub(crate) mod swarm_controller;
+pub(crate) mod event_bus;
+pub(crate) mod signaling;
+pub(crate) mod call_service;
+pub(crate) mod call_sessions;
+pub(crate) mod rendezvous;
+pub(crate) mod relay;
+pub(crate) mod invite_service;
+pub(crate) mod invitations;
+pub(crate) mod member_service;
+pub(crate) mod group_key_service;
+pub(crate) mod group_keys;
+pub(crate) mod message_service;
+pub(crate) mod message_sync;
+pub(crate) mod file_service;
+pub(crate) mod file_transfers;
+pub(crate) mod blob_service;
+pub(crate) mod key_vault;
+pub(crate) mod backup_service;
 mod state;
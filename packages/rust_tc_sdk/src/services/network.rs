@@ -1,54 +1,110 @@
+use std::collections::{HashMap, HashSet};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::net::Ipv4Addr;
-use libp2p::multiaddr::Protocol::{P2p, QuicV1, Tcp, Udp};
-use std::sync::Arc;
-use std::time::Duration;
+use std::path::PathBuf;
+use libp2p::multiaddr::Protocol::{QuicV1, Tcp, Udp, Ws};
+use std::time::{Duration, Instant};
 
 use libp2p::{
-    gossipsub,
+    autonat, dcutr, dns, gossipsub,
     identify, identity,
     mdns, Multiaddr,
-    noise, ping,
+    PeerId,
+    noise, ping, quic,
     relay,
-    rendezvous, Swarm, tcp,
-    yamux,
+    rendezvous, request_response, StreamProtocol, Swarm, tcp,
+    websocket, yamux,
 };
-use libp2p::futures::StreamExt;
-use libp2p::swarm::SwarmEvent;
-use tokio::sync::mpsc::Receiver;
-use tokio::sync::Mutex;
+use libp2p::core::muxing::StreamMuxerBox;
+use libp2p::core::transport::{Boxed, dummy::DummyTransport, ListenerId};
+use libp2p::core::{upgrade, Transport as _};
+use libp2p::swarm::derive_prelude::Either;
+use libp2p::futures::{AsyncRead, AsyncWrite, StreamExt};
+use libp2p::pnet::{PnetConfig, PreSharedKey};
+use libp2p::rendezvous::Namespace;
+use libp2p::request_response::ProtocolSupport;
+use libp2p::gossipsub::{IdentTopic, TopicHash};
+use libp2p::swarm::dial_opts::DialOpts;
+use libp2p::swarm::{ConnectionId, SwarmEvent};
+use tokio::sync::mpsc::{self, Receiver, UnboundedSender};
+use tokio::sync::oneshot;
 
-use crate::entities::room::Room;
 use crate::models::behaviour::*;
+use crate::models::callback_payload::*;
 use crate::models::connection_data::ConnectionData;
+use crate::models::network_status::{NetworkStatus, Reachability};
+use crate::models::peer_data::PeerInfo;
+use crate::entities::member::Member;
+use crate::entities::file::SharedFile;
+use crate::entities::message::Message;
+use crate::models::file_transfer::{chunk_scope, ChunkRequest, ChunkResponse};
+use crate::models::group_key::{GroupKeyRequest, GroupKeyResponse, SealedKey, SealedPayload};
+use crate::models::member::MemberRole;
+use crate::models::message::MessageStatus;
+use crate::models::message_sync::{sync_scope, SyncRequest, SyncResponse, SyncedMessage};
+use crate::models::room_control::{GroupKeyControl, MemberControl, RoomControl};
+use crate::models::invitation::{InviteRequest, InviteResponse};
+use crate::models::room_message::{now_millis, RoomMessage};
+use crate::models::room_topics::{RoomTopicKind, RoomTopics};
+use crate::models::signed_envelope::SignedEnvelope;
+use crate::models::signal::{SignalRequest, SignalResponse};
+use crate::models::transport::Transport;
 use crate::models::error::*;
-use crate::services::swarm_controller::ControlMessage;
+use crate::services::call_service::CallService;
+use crate::services::call_sessions::{CallSessions, CallUpdate};
+use crate::services::event_bus::EventBus;
+use crate::services::group_key_service::GroupKeyService;
+use crate::services::group_keys::{KeyRequests, GROUP_KEY_PROTOCOL};
+use crate::services::blob_service::BlobService;
+use crate::services::file_service::FileService;
+use crate::services::file_transfers::{check_offer, hash_file, manifest_page, read_chunk, Downloads, TransferDone, FILE_TRANSFER_PROTOCOL};
+use crate::services::message_service::MessageService;
+use crate::services::message_sync::{SyncRequests, MESSAGE_SYNC_PROTOCOL};
+use crate::services::invitations::{Redemptions, INVITE_PROTOCOL};
+use crate::services::invite_service::InviteService;
+use crate::services::member_service::MemberService;
+use crate::services::relay::{is_relayed, RelayReservations};
+use crate::services::rendezvous::RendezvousClient;
+use crate::services::signaling::{SignalQueue, SIGNAL_PROTOCOL};
+use crate::services::swarm_controller::{CallCommand, ControlMessage};
 
-pub async fn create_private_network(_: Room, config: &ConnectionData, keypair: identity::Keypair) -> Result<Swarm<AppBehaviour>> {
+/// How often a swarm loop runs its housekeeping (timeouts, retries).
+const TICK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long launching a room waits for its listeners to report their addresses.
+const LISTEN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many messages a room keeps while waiting for the group key to open them.
+const MAX_HELD_MESSAGES: usize = 256;
+
+/// How many missed messages one sync response carries.
+const SYNC_BATCH_SIZE: usize = 100;
+
+/// Builds a swarm that speaks `transports`; rooms join it afterwards through `ControlMessage::JoinRoom`.
+///
+/// A swarm given a pre-shared key only ever connects to peers holding the same key.
+pub fn create_private_network(keypair: identity::Keypair, transports: &[Transport], psk: Option<PreSharedKey>) -> Result<Swarm<AppBehaviour>> {
     log::info!("Creating private network");
-    let mut swarm = libp2p::SwarmBuilder::with_existing_identity(keypair.clone())
+    if let Some(psk) = &psk {
+        log::info!("Guarding the network with pre-shared key {}", psk.fingerprint());
+    }
+    let transport = create_transport(&keypair, transports, psk)?;
+
+    let swarm = libp2p::SwarmBuilder::with_existing_identity(keypair.clone())
         .with_tokio()
-        .with_tcp(
-            tcp::Config::default(),
-            noise::Config::new,
-            yamux::Config::default,
-        )?
+        .with_other_transport(|_| transport)?
         .with_dns()?
         .with_relay_client(noise::Config::new, yamux::Config::default)?
-        .with_behaviour(|key, _| {
+        .with_behaviour(|key, relay_client| {
             // To content-address message, we can take the hash of message and use it as an ID.
-            let message_id_fn = |message: &gossipsub::Message| {
-                let mut s = DefaultHasher::new();
-                message.data.hash(&mut s);
-                // TODO check this
-                gossipsub::MessageId::from(Vec::from(s.finish().to_be_bytes()))
-            };
+            let message_id_fn = |message: &gossipsub::Message| gossip_message_id(&message.topic, &message.data);
 
             // Set a custom gossipsub configuration
             let gossipsub_config = gossipsub::ConfigBuilder::default()
                 .heartbeat_interval(Duration::from_secs(10)) // This is set to aid debugging by not cluttering the log space
                 .validation_mode(gossipsub::ValidationMode::Strict) // This sets the kind of message validation. The default is Strict (enforce message signing)
+                .validate_messages() // Only forward messages once their author is known to be a member of the room.
                 .message_id_fn(message_id_fn) // content-address messages. No two messages of the same content will be propagated.
                 .build()?; // Temporary hack because `build` does not return a proper `std::error::Error`.
 
@@ -69,9 +125,38 @@ pub async fn create_private_network(_: Room, config: &ConnectionData, keypair: i
 
             let relay = relay::Behaviour::new(key.public().to_peer_id(), relay::Config::default());
 
+            let dcutr = dcutr::Behaviour::new(key.public().to_peer_id());
+
+            let autonat = autonat::Behaviour::new(key.public().to_peer_id(), autonat::Config::default());
+
             let rendezvous = rendezvous::client::Behaviour::new(key.clone());
 
-            Ok(AppBehaviour::from((gossip_sub, mdns, ping, identify, relay, rendezvous)))
+            let signaling = request_response::json::Behaviour::new(
+                [(StreamProtocol::new(SIGNAL_PROTOCOL), ProtocolSupport::Full)],
+                request_response::Config::default(),
+            );
+
+            let invites = request_response::json::Behaviour::new(
+                [(StreamProtocol::new(INVITE_PROTOCOL), ProtocolSupport::Full)],
+                request_response::Config::default(),
+            );
+
+            let group_keys = request_response::json::Behaviour::new(
+                [(StreamProtocol::new(GROUP_KEY_PROTOCOL), ProtocolSupport::Full)],
+                request_response::Config::default(),
+            );
+
+            let message_sync = request_response::json::Behaviour::new(
+                [(StreamProtocol::new(MESSAGE_SYNC_PROTOCOL), ProtocolSupport::Full)],
+                request_response::Config::default(),
+            );
+
+            let file_transfer = request_response::cbor::Behaviour::new(
+                [(StreamProtocol::new(FILE_TRANSFER_PROTOCOL), ProtocolSupport::Full)],
+                request_response::Config::default(),
+            );
+
+            Ok(AppBehaviour::from((gossip_sub, mdns, ping, identify, relay, relay_client, dcutr, autonat, rendezvous, signaling, invites, group_keys, message_sync, file_transfer)))
         })
         .unwrap_or_else(|err| panic!("Failed to build behaviour: {:?}", err))
         .with_swarm_config(|cfg| {
@@ -81,31 +166,51 @@ pub async fn create_private_network(_: Room, config: &ConnectionData, keypair: i
         })
         .build();
 
-    log::info!("Starting private network");
+    Ok(swarm)
+}
+
+/// Listens on an OS-assigned port of every transport in `transports`.
+pub fn listen_on_defaults(swarm: &mut Swarm<AppBehaviour>, transports: &[Transport]) -> Result<Vec<ListenerId>> {
+    let mut listeners = Vec::new();
+
+    // Port 0 lets the OS pick a free port, so several rooms and apps can run side by side.
+    if transports.contains(&Transport::Tcp) {
+        let address_to_listen = Multiaddr::from(Ipv4Addr::UNSPECIFIED).with(Tcp(0));
+        log::info!("Will listen on: {:?}", address_to_listen);
+        listeners.push(swarm.listen_on(address_to_listen)?);
+    }
+
+    if transports.contains(&Transport::Quic) {
+        let address_to_listen = Multiaddr::from(Ipv4Addr::UNSPECIFIED).with(Udp(0)).with(QuicV1);
+        log::info!("Will listen on: {:?}", address_to_listen);
+        listeners.push(swarm.listen_on(address_to_listen)?);
+    }
+
+    if transports.contains(&Transport::Websocket) {
+        let address_to_listen = Multiaddr::from(Ipv4Addr::UNSPECIFIED).with(Tcp(0)).with(Ws("/".into()));
+        log::info!("Will listen on: {:?}", address_to_listen);
+        listeners.push(swarm.listen_on(address_to_listen)?);
+    }
+
+    Ok(listeners)
+}
 
-    // Create a Gossipsub topic
-    let topic = gossipsub::IdentTopic::new("test-net");
+/// Subscribes the swarm to the room's topics, listens on its extra addresses and dials its members.
+fn join_room(swarm: &mut Swarm<AppBehaviour>, room_id: &str, config: &ConnectionData) -> Result<Vec<ListenerId>> {
+    log::info!("Joining room {}", room_id);
 
-    log::info!("Subscribing to topic: {}", topic);
-    // subscribes to our topic
-    swarm.behaviour_mut().gossip_sub_mut().subscribe(&topic)?;
+    // Every room only ever talks on the topics derived from its own ID.
+    for topic in RoomTopics::new(room_id).all() {
+        log::info!("Subscribing to topic: {}", topic);
+        swarm.behaviour_mut().gossip_sub_mut().subscribe(topic)?;
+    }
 
-    if config.room_listen_on.len() > 0 {
+    let mut listeners = Vec::new();
+    if !config.room_listen_on.is_empty() {
         log::info!("Listening on: {:?}", config.room_listen_on);
-        // Tell the swarm to listen on all interfaces and a random, OS-assigned
-        // port.
         for url in &config.room_listen_on {
-            swarm.listen_on(url.parse()?)?;
+            listeners.push(swarm.listen_on(url.parse()?)?);
         }
-    } else {
-        // let address_to_listen = Multiaddr::from(Ipv4Addr::UNSPECIFIED);
-        let address_to_listen = Multiaddr::from(Ipv4Addr::UNSPECIFIED).with(Tcp(4001));
-        log::info!("Will listen on: {:?}", address_to_listen);
-        swarm.listen_on(address_to_listen)?;
-
-        let address_to_listen = Multiaddr::from(Ipv4Addr::UNSPECIFIED).with(Tcp(2001)).with(P2p(keypair.public().to_peer_id()));
-        log::info!("Will listen on: {:?}", address_to_listen);
-        swarm.listen_on(address_to_listen)?;
     }
 
     if !config.room_multi_address.is_empty() {
@@ -117,82 +222,1404 @@ pub async fn create_private_network(_: Room, config: &ConnectionData, keypair: i
         }
     }
 
-    Ok(swarm)
+    Ok(listeners)
+}
+
+/// Builds the transports the room enabled; addresses of the other ones cannot be dialed or listened on.
+///
+/// With a pre-shared key every stream first goes through the private network handshake, which
+/// peers without the key cannot complete. QUIC has no stream to wrap, so such swarms leave it out.
+fn create_transport(keypair: &identity::Keypair, transports: &[Transport], psk: Option<PreSharedKey>) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+    let mut transport = DummyTransport::new().boxed();
+
+    for enabled in transports {
+        let next = match enabled {
+            Transport::Tcp => authenticate(tcp::tokio::Transport::new(tcp::Config::default()), keypair, psk)?,
+            Transport::Quic if psk.is_some() => {
+                log::warn!("QUIC cannot carry a private network, leaving it out");
+                continue;
+            }
+            Transport::Quic => quic::tokio::Transport::new(quic::Config::new(keypair))
+                .map(|(peer_id, connection), _| (peer_id, StreamMuxerBox::new(connection)))
+                .boxed(),
+            Transport::Websocket => {
+                let tcp = dns::tokio::Transport::system(tcp::tokio::Transport::new(tcp::Config::default()))?;
+                authenticate(websocket::WsConfig::new(tcp), keypair, psk)?
+            }
+        };
+
+        transport = transport
+            .or_transport(next)
+            .map(|either, _| either.into_inner())
+            .boxed();
+    }
+
+    Ok(transport)
+}
+
+/// Secures and multiplexes the streams of `base`, inside the private network of `psk` if there is one.
+fn authenticate<T>(base: T, keypair: &identity::Keypair, psk: Option<PreSharedKey>) -> Result<Boxed<(PeerId, StreamMuxerBox)>>
+where
+    T: libp2p::Transport + Send + Unpin + 'static,
+    T::Output: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T::Error: Send + Sync + 'static,
+    T::Dial: Send + 'static,
+    T::ListenerUpgrade: Send + 'static,
+{
+    let base = match psk {
+        Some(psk) => Either::Left(base.and_then(move |socket, _| PnetConfig::new(psk).handshake(socket))),
+        None => Either::Right(base),
+    };
+
+    Ok(base
+        .upgrade(upgrade::Version::V1Lazy)
+        .authenticate(noise::Config::new(keypair)?)
+        .multiplex(yamux::Config::default())
+        .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+        .boxed())
+}
+
+/// Everything needed to run a room on a swarm.
+pub struct RoomLaunch {
+    pub room_id: String,
+    /// Signs what the room publishes, whichever swarm carries it.
+    pub keypair: identity::Keypair,
+    pub config: ConnectionData,
+    pub rendezvous: RendezvousClient,
+}
+
+/// Replies to room launches once every listener they wait on reported the address it actually bound.
+struct ListenReady {
+    listeners: HashSet<ListenerId>,
+    deadline: Instant,
+    replies: Vec<oneshot::Sender<Result<Vec<String>>>>,
+}
+
+impl ListenReady {
+    fn new(listeners: Vec<ListenerId>) -> Self {
+        Self {
+            listeners: listeners.into_iter().collect(),
+            deadline: Instant::now() + LISTEN_TIMEOUT,
+            replies: Vec::new(),
+        }
+    }
+
+    fn expect(&mut self, listeners: &[ListenerId]) {
+        if !listeners.is_empty() {
+            self.listeners.extend(listeners);
+            self.deadline = Instant::now() + LISTEN_TIMEOUT;
+        }
+    }
+
+    fn wait(&mut self, swarm: &Swarm<AppBehaviour>, reply: oneshot::Sender<Result<Vec<String>>>) {
+        self.replies.push(reply);
+        if self.listeners.is_empty() {
+            self.resolve(swarm);
+        }
+    }
+
+    /// Notes that `listener_id` is up (or gone), and resolves the launches when none is left.
+    fn on_listener(&mut self, swarm: &Swarm<AppBehaviour>, listener_id: ListenerId) {
+        if self.listeners.remove(&listener_id) && self.listeners.is_empty() {
+            self.resolve(swarm);
+        }
+    }
+
+    fn tick(&mut self, swarm: &Swarm<AppBehaviour>, now: Instant) {
+        if !self.listeners.is_empty() && self.deadline <= now {
+            log::warn!("Not every listener came up in time");
+            self.listeners.clear();
+            self.resolve(swarm);
+        }
+    }
+
+    fn resolve(&mut self, swarm: &Swarm<AppBehaviour>) {
+        for reply in self.replies.drain(..) {
+            let _ = reply.send(Ok(listen_addresses(swarm)));
+        }
+    }
+}
+
+/// The storage a swarm loop works with; cheap to clone.
+#[derive(Clone)]
+pub struct NodeServices {
+    pub call_service: CallService,
+    pub invite_service: InviteService,
+    pub member_service: MemberService,
+    pub group_key_service: GroupKeyService,
+    pub message_service: MessageService,
+    pub file_service: FileService,
+    pub blob_service: BlobService,
+    /// Where downloaded files land.
+    pub download_dir: PathBuf,
+}
+
+/// How to reach a room's swarm, for invitations.
+pub struct RoomAddresses {
+    pub peer_id: PeerId,
+    /// Each ends with `/p2p/<peer id>`.
+    pub addresses: Vec<String>,
+    pub rendezvous_nodes: Vec<String>,
+}
+
+/// Everything one room keeps on the swarm it runs on.
+struct RoomContext {
+    room_id: String,
+    topics: RoomTopics,
+    keypair: identity::Keypair,
+    calls: CallSessions,
+    rendezvous: RendezvousClient,
+    /// The swarm each room identity was last seen publishing from, to route signals.
+    swarm_peers: HashMap<PeerId, PeerId>,
+    /// Addresses this room asked for on top of the swarm's own.
+    listeners: Vec<ListenerId>,
+    /// Topics the application subscribed to on top of the room's own; their messages are chat.
+    custom_topics: HashSet<TopicHash>,
+    /// Messages sealed with a group key this node has not received yet.
+    held: Vec<HeldMessage>,
+    /// Members whose public key was stored this session.
+    known_keys: HashSet<PeerId>,
+}
+
+/// A chat message waiting for the group key of its epoch.
+struct HeldMessage {
+    author: PeerId,
+    id: gossipsub::MessageId,
+    topic: TopicHash,
+    sealed: SealedPayload,
+    /// The signed envelope it came in, kept with the message.
+    envelope: Vec<u8>,
+    /// `Received` over gossip, or `Synced` from another member.
+    status: MessageStatus,
+}
+
+impl RoomContext {
+    fn owns(&self, topic: &TopicHash) -> bool {
+        self.topics.kind_of(topic).is_some() || self.custom_topics.contains(topic)
+    }
+
+    fn local_peer(&self) -> PeerId {
+        self.keypair.public().to_peer_id()
+    }
+
+    /// The swarm to reach the room identity `peer` on; the same peer when they are one.
+    fn swarm_peer(&self, peer: PeerId) -> PeerId {
+        self.swarm_peers.get(&peer).copied().unwrap_or(peer)
+    }
+
+    /// Asks for the group key of `epoch` (the current one when `None`), sealed to this room's identity.
+    fn key_request(&self, epoch: Option<i64>) -> GroupKeyRequest {
+        GroupKeyRequest::from((self.room_id.clone(), epoch, self.keypair.public().encode_protobuf()))
+    }
+
+    fn hold(&mut self, message: HeldMessage) {
+        if self.held.len() >= MAX_HELD_MESSAGES {
+            let dropped = self.held.remove(0);
+            log::warn!("Dropping message {} of room {}, its group key never came", dropped.id, self.room_id);
+        }
+        self.held.push(message);
+    }
+
+    /// The room identity speaking through the swarm `peer`, if it published anything yet.
+    fn room_peer(&self, peer: PeerId) -> PeerId {
+        self.swarm_peers.iter()
+            .find(|(_, swarm_peer)| **swarm_peer == peer)
+            .map(|(room_peer, _)| *room_peer)
+            .unwrap_or(peer)
+    }
 }
 
-pub async fn run_swarm(swarm: Arc<Mutex<Swarm<AppBehaviour>>>, mut receiver: Receiver<ControlMessage>) -> Result<()> {
+/// Everything a swarm loop keeps next to its swarm, shared by the rooms running on it.
+struct NodeContext {
+    event_bus: EventBus,
+    services: NodeServices,
+    rooms: HashMap<String, RoomContext>,
+    signals: SignalQueue,
+    redemptions: Redemptions,
+    key_requests: KeyRequests,
+    syncs: SyncRequests,
+    downloads: Downloads,
+    /// Where file reads done off the loop report back.
+    transfers: UnboundedSender<TransferDone>,
+    relays: RelayReservations,
+    ready: ListenReady,
+    /// Dials started through `ControlMessage::Dial`, waiting for their connection.
+    dials: HashMap<ConnectionId, oneshot::Sender<Result<PeerId>>>,
+}
+
+/// Drives a swarm and every room joined on it until `ControlMessage::Stop`.
+///
+/// The loop owns the swarm; everything else reaches it through the `SwarmController`.
+/// `listeners` are the swarm's own listeners; launches wait for them to come up.
+pub async fn run_swarm(mut swarm: Swarm<AppBehaviour>, mut receiver: Receiver<ControlMessage>, event_bus: EventBus, services: NodeServices, listeners: Vec<ListenerId>) {
     log::info!("Running swarm...");
-    loop {
-        if ControlMessage::Stop == receiver.try_recv()? {
-            log::info!("Actually stopping the swarm...");
-            break;
+    let (transfers, mut transfers_done) = mpsc::unbounded_channel();
+    let downloads = Downloads::new(services.download_dir.clone(), services.blob_service.clone(), transfers.clone());
+    let mut ctx = NodeContext {
+        event_bus,
+        services,
+        rooms: HashMap::new(),
+        signals: SignalQueue::new(),
+        redemptions: Redemptions::new(),
+        key_requests: KeyRequests::new(),
+        syncs: SyncRequests::new(),
+        downloads,
+        transfers,
+        relays: RelayReservations::new(),
+        ready: ListenReady::new(listeners),
+        dials: HashMap::new(),
+    };
+    let mut ticker = tokio::time::interval(TICK_INTERVAL);
+
+    let stopped = loop {
+        tokio::select! {
+            command = receiver.recv() => match command {
+                Some(ControlMessage::Stop { reply }) => break Some(reply),
+                None => break None,
+                Some(command) => handle_control_message(&mut ctx, &mut swarm, command),
+            },
+            event = swarm.select_next_some() => {
+                handle_swarm_event(&mut ctx, &mut swarm, event);
+            }
+            Some(done) = transfers_done.recv() => handle_transfer_done(&mut ctx, &mut swarm, done),
+            _ = ticker.tick() => {
+                let now = Instant::now();
+                ctx.signals.expire(now);
+                ctx.redemptions.expire(now);
+                ctx.key_requests.expire(now);
+                ctx.relays.tick(&mut swarm);
+                ctx.ready.tick(&swarm, now);
+                for room in ctx.rooms.values_mut() {
+                    room.rendezvous.tick(&mut swarm, now);
+                    for update in room.calls.expire(now) {
+                        apply_call_update(&ctx.event_bus, room, &mut swarm, update);
+                    }
+                }
+            }
+        }
+    };
+
+    log::info!("Actually stopping the swarm...");
+    // Listeners and connections close with the swarm, before the caller hears back.
+    drop(swarm);
+    if let Some(reply) = stopped {
+        let _ = reply.send(());
+    }
+}
+
+fn handle_control_message(ctx: &mut NodeContext, swarm: &mut Swarm<AppBehaviour>, command: ControlMessage) {
+    match command {
+        ControlMessage::JoinRoom { room, reply } => match add_room(ctx, swarm, room) {
+            Ok(()) => ctx.ready.wait(swarm, reply),
+            Err(e) => {
+                let _ = reply.send(Err(e));
+            }
+        },
+        ControlMessage::LeaveRoom { room_id, reply } => {
+            remove_room(ctx, swarm, &room_id);
+            let _ = reply.send(());
+        }
+        ControlMessage::Publish { room_id, topic, payload, file, reply } => {
+            let result = room(ctx, &room_id)
+                .and_then(|room| publish_message(room, swarm, &ctx.services, topic, RoomMessage::new(payload, file)));
+            if let Err(Error(ErrorKind::GroupKeyMissing(_), _)) = &result {
+                request_current_key(ctx, swarm, &room_id);
+            }
+            let _ = reply.send(result);
+        }
+        ControlMessage::Download { room_id, offer, holder, reply } => {
+            let result = room(ctx, &room_id)
+                .map(|room| room.swarm_peer(holder))
+                .and_then(|peer| ctx.downloads.start(swarm, &room_id, offer, peer));
+            if let Ok(payload) = &result {
+                ctx.event_bus.emit(CallbackPayload::File(payload.clone()));
+            }
+            let _ = reply.send(result);
+        }
+        ControlMessage::SendSignal { room_id, to_peer, signal, reply } => match ctx.rooms.get(&room_id) {
+            Some(room) => {
+                let request = SignalRequest::from((room_id, signal));
+                ctx.signals.send(swarm, room.swarm_peer(to_peer), request, reply);
+            }
+            None => {
+                let _ = reply.send(Err(ErrorKind::RoomNotRunning(room_id).into()));
+            }
+        },
+        ControlMessage::Call { room_id, command, reply } => {
+            let result = match ctx.rooms.get_mut(&room_id) {
+                Some(room) => handle_call_command(&ctx.event_bus, room, swarm, command),
+                None => Err(ErrorKind::RoomNotRunning(room_id).into()),
+            };
+            let _ = reply.send(result);
+        }
+        ControlMessage::ListListenAddrs { reply } => {
+            let _ = reply.send(listen_addresses(swarm));
+        }
+        ControlMessage::NetworkStatus { room_id, reply } => {
+            let _ = reply.send(network_status(&room_id, swarm));
+        }
+        ControlMessage::Dial { address, reply } => {
+            let opts = DialOpts::from(address);
+            let connection_id = opts.connection_id();
+            match swarm.dial(opts) {
+                Ok(()) => {
+                    ctx.dials.insert(connection_id, reply);
+                }
+                Err(e) => {
+                    let _ = reply.send(Err(e.into()));
+                }
+            }
+        }
+        ControlMessage::Disconnect { room_id, peer_id, reply } => {
+            let result = room(ctx, &room_id).and_then(|room| {
+                swarm.disconnect_peer_id(room.swarm_peer(peer_id))
+                    .map_err(|_| ErrorKind::PeerNotConnected(peer_id.to_string()).into())
+            });
+            let _ = reply.send(result);
+        }
+        ControlMessage::ListPeers { room_id, reply } => {
+            let _ = reply.send(room(ctx, &room_id).and_then(|room| list_peers(room, swarm, &ctx.services.member_service)));
+        }
+        ControlMessage::Subscribe { room_id, topic, reply } => {
+            let result = match ctx.rooms.get_mut(&room_id) {
+                Some(room) => {
+                    let topic = RoomTopics::custom(&room_id, &topic);
+                    room.custom_topics.insert(topic.hash());
+                    swarm.behaviour_mut().gossip_sub_mut().subscribe(&topic).map(|_| ()).map_err(Error::from)
+                }
+                None => Err(ErrorKind::RoomNotRunning(room_id).into()),
+            };
+            let _ = reply.send(result);
+        }
+        ControlMessage::Unsubscribe { room_id, topic, reply } => {
+            let result = match ctx.rooms.get_mut(&room_id) {
+                Some(room) => {
+                    let topic = RoomTopics::custom(&room_id, &topic);
+                    room.custom_topics.remove(&topic.hash());
+                    swarm.behaviour_mut().gossip_sub_mut().unsubscribe(&topic).map(|_| ()).map_err(Error::from)
+                }
+                None => Err(ErrorKind::RoomNotRunning(room_id).into()),
+            };
+            let _ = reply.send(result);
+        }
+        ControlMessage::AddExternalAddress { address, reply } => {
+            log::info!("Adding external address {address}");
+            swarm.add_external_address(address);
+            for room in ctx.rooms.values_mut() {
+                room.rendezvous.on_external_address(swarm, Instant::now());
+            }
+            let _ = reply.send(());
+        }
+        ControlMessage::ShareRoom { room_id, reply } => {
+            let _ = reply.send(room(ctx, &room_id).map(|room| room_addresses(room, swarm)));
+        }
+        ControlMessage::RedeemInvite { inviter, request, reply } => {
+            ctx.redemptions.redeem(swarm, inviter, request, reply);
+        }
+        ControlMessage::Announce { room_id, control, reply } => {
+            let _ = reply.send(room(ctx, &room_id).and_then(|room| publish_control(room, swarm, control)));
+        }
+        ControlMessage::Stop { .. } => {}
+    }
+}
+
+fn room_addresses(room: &RoomContext, swarm: &Swarm<AppBehaviour>) -> RoomAddresses {
+    let peer_id = *swarm.local_peer_id();
+    let mut addresses: Vec<String> = Vec::new();
+    for address in swarm.external_addresses().chain(swarm.listeners()) {
+        let address = address.clone().with_p2p(peer_id).unwrap_or_else(|address| address).to_string();
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+
+    RoomAddresses {
+        peer_id,
+        addresses,
+        rendezvous_nodes: room.rendezvous.addresses().iter().map(|address| address.to_string()).collect(),
+    }
+}
+
+/// Every connected peer, telling the room's members from the rest.
+fn list_peers(room: &RoomContext, swarm: &Swarm<AppBehaviour>, member_service: &MemberService) -> Result<Vec<PeerInfo>> {
+    swarm.connected_peers()
+        .map(|peer| {
+            let member = member_service.is_member(&room.room_id, &room.room_peer(*peer).to_string())?;
+            Ok(PeerInfo::from((peer.to_string(), member)))
+        })
+        .collect()
+}
+
+fn room<'a>(ctx: &'a NodeContext, room_id: &str) -> Result<&'a RoomContext> {
+    ctx.rooms.get(room_id).ok_or_else(|| ErrorKind::RoomNotRunning(room_id.to_string()).into())
+}
+
+fn add_room(ctx: &mut NodeContext, swarm: &mut Swarm<AppBehaviour>, launch: Box<RoomLaunch>) -> Result<()> {
+    let RoomLaunch { room_id, keypair, config, rendezvous } = *launch;
+    if ctx.rooms.contains_key(&room_id) {
+        return Ok(());
+    }
+
+    let listeners = join_room(swarm, &room_id, &config)?;
+    ctx.ready.expect(&listeners);
+
+    let local_peer = keypair.public().to_peer_id().to_string();
+    let mut room = RoomContext {
+        topics: RoomTopics::new(&room_id),
+        calls: CallSessions::new(room_id.clone(), local_peer, ctx.services.call_service.clone()),
+        room_id: room_id.clone(),
+        keypair,
+        rendezvous,
+        swarm_peers: HashMap::new(),
+        listeners,
+        custom_topics: HashSet::new(),
+        held: Vec::new(),
+        known_keys: HashSet::new(),
+    };
+
+    // The swarm may already be connected to the room's rendezvous nodes through another room.
+    let now = Instant::now();
+    for peer in swarm.connected_peers().copied().collect::<Vec<_>>() {
+        room.rendezvous.on_connected(peer, now);
+    }
+    if swarm.external_addresses().next().is_some() {
+        room.rendezvous.on_external_address(swarm, now);
+    }
+
+    ctx.rooms.insert(room_id, room);
+    update_relays(ctx, swarm);
+    Ok(())
+}
+
+/// Reserves circuits on the rendezvous nodes of the rooms still on the swarm, and on those only.
+fn update_relays(ctx: &mut NodeContext, swarm: &mut Swarm<AppBehaviour>) {
+    let allowed = ctx.rooms.values().flat_map(|room| room.rendezvous.peers()).collect();
+    ctx.relays.set_allowed(swarm, allowed);
+}
+
+fn remove_room(ctx: &mut NodeContext, swarm: &mut Swarm<AppBehaviour>, room_id: &str) {
+    let Some(room) = ctx.rooms.remove(room_id) else { return };
+    log::info!("Leaving room {}", room_id);
+
+    for topic in room.topics.all() {
+        if let Err(e) = swarm.behaviour_mut().gossip_sub_mut().unsubscribe(topic) {
+            log::warn!("Could not unsubscribe from {}: {:?}", topic, e);
+        }
+    }
+    for topic in room.custom_topics {
+        // An identity topic hashes to its own name, so the hash is enough to rebuild it.
+        if let Err(e) = swarm.behaviour_mut().gossip_sub_mut().unsubscribe(&IdentTopic::new(topic.as_str())) {
+            log::warn!("Could not unsubscribe from {}: {:?}", topic, e);
         }
+    }
+    for listener_id in room.listeners {
+        swarm.remove_listener(listener_id);
+    }
+    update_relays(ctx, swarm);
+}
 
-        if let Err(e) = process_swarm_events(swarm.clone()).await {
-            log::error!("Error while processing swarm events: {:?}", e);
+fn handle_call_command(event_bus: &EventBus, room: &mut RoomContext, swarm: &mut Swarm<AppBehaviour>, command: CallCommand) -> Result<CallPayload> {
+    let update = match command {
+        CallCommand::Start => {
+            let control = room.topics.get(RoomTopicKind::Control).hash();
+            let invited = swarm.behaviour().gossip_sub().all_peers()
+                .filter(|(_, topics)| topics.contains(&&control))
+                .count();
+            room.calls.start(invited, Instant::now())?
         }
+        CallCommand::Accept(call_id) => room.calls.accept(&call_id)?,
+        CallCommand::Reject(call_id) => room.calls.reject(&call_id)?,
+        CallCommand::HangUp(call_id) => room.calls.hang_up(&call_id)?,
     };
 
+    Ok(apply_call_update(event_bus, room, swarm, update))
+}
+
+/// Tells the room and the JS listeners about a call transition.
+fn apply_call_update(event_bus: &EventBus, room: &RoomContext, swarm: &mut Swarm<AppBehaviour>, update: CallUpdate) -> CallPayload {
+    if let Some(control) = update.control {
+        if let Err(e) = publish_control(room, swarm, RoomControl::Call(control)) {
+            log::warn!("Could not publish call control in room {}: {:?}", room.room_id, e);
+        }
+    }
+
+    event_bus.emit(CallbackPayload::Call(update.payload.clone()));
+    update.payload
+}
+
+fn listen_addresses(swarm: &Swarm<AppBehaviour>) -> Vec<String> {
+    swarm.listeners().map(|address| address.to_string()).collect()
+}
+
+fn network_status(room_id: &str, swarm: &Swarm<AppBehaviour>) -> NetworkStatus {
+    let autonat = swarm.behaviour().autonat();
+    NetworkStatus::from((
+        room_id.to_string(),
+        Reachability::from(&autonat.nat_status()),
+        autonat.public_address().map(|address| address.to_string()),
+        swarm.external_addresses().map(|address| address.to_string()).collect(),
+    ))
+}
+
+/// Content-addresses gossip messages, no two messages of the same content are propagated.
+fn gossip_message_id(topic: &TopicHash, data: &[u8]) -> gossipsub::MessageId {
+    let mut s = DefaultHasher::new();
+    topic.hash(&mut s);
+    data.hash(&mut s);
+    gossipsub::MessageId::from(Vec::from(s.finish().to_be_bytes()))
+}
+
+/// Publishes `payload` on one of the room's topics, signed with the room's identity.
+fn publish_signed(room: &RoomContext, swarm: &mut Swarm<AppBehaviour>, topic: &IdentTopic, payload: String) -> Result<gossipsub::MessageId> {
+    let envelope = SignedEnvelope::seal(&room.keypair, &topic.hash(), payload)?;
+    Ok(swarm.behaviour_mut().gossip_sub_mut().publish(topic.clone(), envelope.to_bytes()?)?)
+}
+
+fn publish_control(room: &RoomContext, swarm: &mut Swarm<AppBehaviour>, control: RoomControl) -> Result<()> {
+    publish_signed(room, swarm, room.topics.get(RoomTopicKind::Control), control.to_json()?)?;
     Ok(())
 }
 
-async fn process_swarm_events(swarm: Arc<Mutex<Swarm<AppBehaviour>>>) -> Result<()> {
-    log::info!("Processing swarm events...");
+/// Seals the message with the room's current group key, so relaying peers only see ciphertext.
+///
+/// The message is stored before it goes out: with no other member online it waits there for them to sync it.
+fn publish_message(room: &RoomContext, swarm: &mut Swarm<AppBehaviour>, services: &NodeServices, topic: Option<String>, message: RoomMessage) -> Result<MessagePayload> {
+    log::info!("Publishing message in room {}", room.room_id);
+    let topic = match topic {
+        Some(name) => RoomTopics::custom(&room.room_id, &name),
+        None => room.topics.get(RoomTopicKind::Chat).clone(),
+    };
+    let (epoch, key) = services.group_key_service.current_key(&room.room_id)?
+        .ok_or_else(|| Error::from(ErrorKind::GroupKeyMissing(room.room_id.clone())))?;
+
+    let sealed = SealedPayload::seal(&key, epoch, &topic.hash(), &message.to_json()?)?;
+    let envelope = SignedEnvelope::seal(&room.keypair, &topic.hash(), sealed.to_json()?)?.to_bytes()?;
+    // The id gossipsub gives the message, it only depends on the content.
+    let message_id = gossip_message_id(&topic.hash(), &envelope);
 
-    let mut locked_swarm = swarm.lock().await;
-    log::info!("Swarm locked");
+    let sent = MessagePayload::from((
+        room.room_id.clone(),
+        room.local_peer().to_string(),
+        message_id.to_string(),
+        topic.to_string(),
+        message.payload,
+        message.sent_at,
+        false,
+        message.file,
+    ));
+    services.message_service.store_message(&Message::stored(&sent, MessageStatus::Sent, sent.timestamp, Some(envelope.clone())))?;
 
-    match locked_swarm.select_next_some().await {
+    match swarm.behaviour_mut().gossip_sub_mut().publish(topic, envelope) {
+        Ok(_) => {}
+        Err(gossipsub::PublishError::InsufficientPeers) => {
+            log::info!("No member of room {} is online, message {} is queued for sync", room.room_id, sent.message_id);
+        }
+        Err(e) => {
+            if let Err(e) = services.message_service.remove_message(&room.room_id, &sent.message_id) {
+                log::error!("Could not forget unsent message {}: {}", sent.message_id, e.chain_message());
+            }
+            return Err(e.into());
+        }
+    }
+    Ok(sent)
+}
+
+/// Emits one event per room running on the swarm, for events that concern the whole swarm.
+fn emit_to_rooms(ctx: &NodeContext, payload: impl Fn(String) -> CallbackPayload) {
+    for room_id in ctx.rooms.keys() {
+        ctx.event_bus.emit(payload(room_id.clone()));
+    }
+}
+
+fn handle_swarm_event(ctx: &mut NodeContext, swarm: &mut Swarm<AppBehaviour>, event: SwarmEvent<AppBehaviourEvent>) {
+    match event {
         SwarmEvent::Behaviour(AppBehaviourEvent::Mdns(mdns::Event::Discovered(list))) => {
-            for (peer_id, _multiaddr) in list {
+            for (peer_id, multiaddr) in list {
                 log::info!("mDNS discovered a new peer: {peer_id}");
-                locked_swarm.behaviour_mut().gossip_sub_mut().add_explicit_peer(&peer_id);
+                swarm.behaviour_mut().gossip_sub_mut().add_explicit_peer(&peer_id);
+                emit_to_rooms(ctx, |room_id| CallbackPayload::PeerDiscovered(PeerPayload::from((
+                    room_id, peer_id.to_string(), multiaddr.to_string(),
+                ))));
             }
         }
         SwarmEvent::Behaviour(AppBehaviourEvent::Mdns(mdns::Event::Expired(list))) => {
-            for (peer_id, _multiaddr) in list {
+            for (peer_id, multiaddr) in list {
                 log::info!("mDNS discover peer has expired: {peer_id}");
-                locked_swarm.behaviour_mut().gossip_sub_mut().remove_explicit_peer(&peer_id);
+                swarm.behaviour_mut().gossip_sub_mut().remove_explicit_peer(&peer_id);
+                emit_to_rooms(ctx, |room_id| CallbackPayload::PeerExpired(PeerPayload::from((
+                    room_id, peer_id.to_string(), multiaddr.to_string(),
+                ))));
             }
         }
         SwarmEvent::Behaviour(AppBehaviourEvent::Ping(ping::Event { peer, connection: _, result: Ok(res) })) => {
             log::info!("Ping event from: {:?} in {:?}", peer, res.as_millis());
+            emit_to_rooms(ctx, |room_id| CallbackPayload::Ping(PingPayload::from((
+                room_id, peer.to_string(), Some(res.as_millis() as u64), None,
+            ))));
         }
         SwarmEvent::Behaviour(AppBehaviourEvent::Ping(ping::Event { peer, connection: _, result: Err(err) })) => {
             log::info!("Ping failed event from {:?}: {:?}", peer, err);
+            emit_to_rooms(ctx, |room_id| CallbackPayload::Ping(PingPayload::from((
+                room_id, peer.to_string(), None, Some(err.to_string()),
+            ))));
         }
         SwarmEvent::Behaviour(AppBehaviourEvent::GossipSub(gossipsub::Event::Message { propagation_source: peer_id, message_id: id, message, })) => {
-            log::info!("Got message: '{}' with id: {id} from peer: {peer_id}", String::from_utf8_lossy(&message.data));
+            log::info!("Got message with id: {id} from peer: {peer_id}");
+            let acceptance = handle_gossip_message(ctx, swarm, peer_id, &id, message);
+            if let Err(e) = swarm.behaviour_mut().gossip_sub_mut().report_message_validation_result(&id, &peer_id, acceptance) {
+                log::warn!("Could not report the validation of message {id}: {:?}", e);
+            }
+        }
+        SwarmEvent::Behaviour(AppBehaviourEvent::Signaling(request_response::Event::Message { peer, message })) => match message {
+            request_response::Message::Request { request, channel, .. } => {
+                let accepted = match ctx.rooms.get(&request.room_id) {
+                    Some(room) => {
+                        let sender = room.room_peer(peer).to_string();
+                        match ctx.services.member_service.is_member(&room.room_id, &sender) {
+                            Ok(true) => {
+                                log::info!("Got signal from: {peer}");
+                                ctx.event_bus.emit(CallbackPayload::Signal(SignalPayload::from((request.room_id, sender, request.signal))));
+                                true
+                            }
+                            Ok(false) => {
+                                log::warn!("Rejecting signal from {sender}, who is not a member of room {}", request.room_id);
+                                false
+                            }
+                            Err(e) => {
+                                log::error!("Could not check the members of room {}: {}", request.room_id, e.chain_message());
+                                false
+                            }
+                        }
+                    }
+                    None => {
+                        log::warn!("Rejecting signal from {peer} for room {}", request.room_id);
+                        false
+                    }
+                };
+
+                if swarm.behaviour_mut().signaling_mut().send_response(channel, SignalResponse::from(accepted)).is_err() {
+                    log::warn!("Could not acknowledge signal from {peer}");
+                }
+            }
+            request_response::Message::Response { request_id, response } => {
+                ctx.signals.on_response(request_id, response);
+            }
+        },
+        SwarmEvent::Behaviour(AppBehaviourEvent::Signaling(request_response::Event::OutboundFailure { peer, request_id, error })) => {
+            log::info!("Signal to {peer} failed: {error}");
+            ctx.signals.on_failure(swarm, request_id, error);
+        }
+        SwarmEvent::Behaviour(AppBehaviourEvent::Invites(request_response::Event::Message { peer, message })) => match message {
+            request_response::Message::Request { request, channel, .. } => {
+                log::info!("{peer} redeems invite {} to room {}", request.invite_id, request.room_id);
+                let response = redeem_invite(ctx, swarm, request)
+                    .unwrap_or_else(|e| InviteResponse::from((Some(e.chain_message()), Vec::new(), None)));
+
+                if swarm.behaviour_mut().invites_mut().send_response(channel, response).is_err() {
+                    log::warn!("Could not answer the invite redemption of {peer}");
+                }
+            }
+            request_response::Message::Response { request_id, response } => {
+                ctx.redemptions.on_response(request_id, response);
+            }
+        },
+        SwarmEvent::Behaviour(AppBehaviourEvent::Invites(request_response::Event::OutboundFailure { peer, request_id, error })) => {
+            log::info!("Redeeming an invite with {peer} failed: {error}");
+            ctx.redemptions.on_failure(request_id, error);
+        }
+        SwarmEvent::Behaviour(AppBehaviourEvent::GroupKeys(request_response::Event::Message { peer, message })) => match message {
+            request_response::Message::Request { request, channel, .. } => {
+                let response = match hand_out_key(ctx, &request) {
+                    Ok(key) => GroupKeyResponse::from((Some(key), None)),
+                    Err(e) => {
+                        log::info!("Not handing the group key of room {} to {peer}: {}", request.room_id, e.chain_message());
+                        GroupKeyResponse::from((None, Some(e.chain_message())))
+                    }
+                };
+
+                if swarm.behaviour_mut().group_keys_mut().send_response(channel, response).is_err() {
+                    log::warn!("Could not answer the group key request of {peer}");
+                }
+            }
+            request_response::Message::Response { request_id, response } => {
+                if let Some(room_id) = ctx.key_requests.on_answer(request_id) {
+                    receive_key(ctx, &room_id, response);
+                }
+            }
+        },
+        SwarmEvent::Behaviour(AppBehaviourEvent::GroupKeys(request_response::Event::OutboundFailure { peer, request_id, error })) => {
+            log::info!("Asking {peer} for a group key failed: {error}");
+            ctx.key_requests.on_answer(request_id);
+        }
+        SwarmEvent::Behaviour(AppBehaviourEvent::MessageSync(request_response::Event::Message { peer, message })) => match message {
+            request_response::Message::Request { request, channel, .. } => {
+                let response = serve_sync(ctx, &request).unwrap_or_else(|e| {
+                    log::info!("Not syncing room {} with {peer}: {}", request.room_id, e.chain_message());
+                    SyncResponse::from((None, None, Some(e.chain_message())))
+                });
+
+                if swarm.behaviour_mut().message_sync_mut().send_response(channel, response).is_err() {
+                    log::warn!("Could not answer the sync request of {peer}");
+                }
+            }
+            request_response::Message::Response { request_id, response } => {
+                if let Some((request, peer)) = ctx.syncs.on_answer(request_id) {
+                    receive_sync(ctx, swarm, request, peer, response);
+                }
+            }
+        },
+        SwarmEvent::Behaviour(AppBehaviourEvent::MessageSync(request_response::Event::OutboundFailure { peer, request_id, error })) => {
+            log::info!("Syncing with {peer} failed: {error}");
+            ctx.syncs.on_answer(request_id);
+        }
+        SwarmEvent::Behaviour(AppBehaviourEvent::FileTransfer(request_response::Event::Message { peer, message })) => match message {
+            request_response::Message::Request { request, channel, .. } => match serve_file(ctx, request) {
+                Ok(read) => {
+                    let done = ctx.transfers.clone();
+                    tokio::task::spawn_blocking(move || {
+                        let response = read().unwrap_or_else(|e| {
+                            log::info!("Not serving a file to {peer}: {}", e.chain_message());
+                            ChunkResponse::Rejected(e.chain_message())
+                        });
+                        let _ = done.send(TransferDone::Served { peer, channel, response });
+                    });
+                }
+                Err(e) => {
+                    log::info!("Not serving a file to {peer}: {}", e.chain_message());
+                    answer_file_request(swarm, peer, channel, ChunkResponse::Rejected(e.chain_message()));
+                }
+            },
+            request_response::Message::Response { request_id, response } => {
+                let progress = match response {
+                    ChunkResponse::Manifest(manifest) => ctx.downloads.on_manifest(swarm, request_id, &manifest),
+                    ChunkResponse::Chunk(sealed) => {
+                        let chunk = open_chunk(ctx, &request_id, &sealed);
+                        ctx.downloads.on_chunk(swarm, request_id, chunk)
+                    }
+                    ChunkResponse::Rejected(reason) => ctx.downloads.on_failure(request_id, reason),
+                };
+                if let Some(progress) = progress {
+                    report_download(ctx, progress);
+                }
+            }
+        },
+        SwarmEvent::Behaviour(AppBehaviourEvent::FileTransfer(request_response::Event::OutboundFailure { request_id, error, .. })) => {
+            if let Some(progress) = ctx.downloads.on_failure(request_id, error.to_string()) {
+                report_download(ctx, progress);
+            }
         }
-        SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+        SwarmEvent::ConnectionEstablished { peer_id, connection_id, endpoint, num_established, .. } => {
             log::info!("Connection established with: {peer_id}");
+            if let Some(reply) = ctx.dials.remove(&connection_id) {
+                let _ = reply.send(Ok(peer_id));
+            }
+            let address = endpoint.get_remote_address();
+            emit_to_rooms(ctx, |room_id| CallbackPayload::ConnectionOpened(ConnectionPayload::from((
+                room_id, peer_id.to_string(), address.to_string(), is_relayed(address), Transport::of(address), None,
+            ))));
+            ctx.relays.on_connected(peer_id, &endpoint);
+            ctx.signals.on_connected(swarm, peer_id);
+            ctx.redemptions.on_connected(swarm, peer_id);
+            for room in ctx.rooms.values_mut() {
+                room.rendezvous.on_connected(peer_id, Instant::now());
+                // A room that never got its group key asks every peer it meets.
+                if matches!(ctx.services.group_key_service.current_key(&room.room_id), Ok(None)) {
+                    ctx.key_requests.request(swarm, peer_id, room.key_request(None), Instant::now());
+                }
+            }
+            for progress in ctx.downloads.resume(swarm, peer_id) {
+                report_download(ctx, progress);
+            }
+            // Catch up on what was said while either side was away, once per peer.
+            if num_established.get() == 1 {
+                let room_ids: Vec<String> = ctx.rooms.keys().cloned().collect();
+                for room_id in room_ids {
+                    request_sync(ctx, swarm, &room_id, peer_id);
+                }
+            }
+        }
+        SwarmEvent::Behaviour(AppBehaviourEvent::Identify(identify::Event::Received { peer_id, info })) => {
+            log::info!("Identified {peer_id}, observed us at {}", info.observed_addr);
+            ctx.relays.on_identified(swarm, peer_id, &info.protocols);
+        }
+        SwarmEvent::Behaviour(AppBehaviourEvent::Autonat(autonat::Event::StatusChanged { old, new })) => {
+            log::info!("NAT status changed from {:?} to {:?}", old, new);
+            emit_to_rooms(ctx, |room_id| CallbackPayload::Reachability(network_status(&room_id, swarm)));
+        }
+        SwarmEvent::ExternalAddrConfirmed { address } => {
+            log::info!("External address confirmed: {address}");
+            for room in ctx.rooms.values_mut() {
+                room.rendezvous.on_external_address(swarm, Instant::now());
+            }
         }
-        SwarmEvent::Behaviour(AppBehaviourEvent::Rendezvous(rendezvous::client::Event::Discovered { rendezvous_node, .. })) => {
-            log::info!("RDV discovered with: {rendezvous_node}");
+        SwarmEvent::Behaviour(AppBehaviourEvent::RelayClient(relay::client::Event::ReservationReqAccepted { relay_peer_id, renewal, .. })) => {
+            ctx.relays.on_reservation_accepted(relay_peer_id, renewal);
         }
-        SwarmEvent::Behaviour(AppBehaviourEvent::Rendezvous(rendezvous::client::Event::DiscoverFailed { rendezvous_node, .. })) => {
+        SwarmEvent::Behaviour(AppBehaviourEvent::Dcutr(dcutr::Event { remote_peer_id, result })) => {
+            let error = match result {
+                Ok(_) => {
+                    log::info!("Upgraded the relayed connection with {remote_peer_id} to a direct one");
+                    None
+                }
+                Err(e) => {
+                    log::info!("Hole punching {remote_peer_id} failed: {e}");
+                    Some(e.to_string())
+                }
+            };
+            emit_to_rooms(ctx, |room_id| CallbackPayload::HolePunch(HolePunchPayload::from((room_id, remote_peer_id.to_string(), error.clone()))));
+        }
+        SwarmEvent::ListenerClosed { listener_id, addresses, reason } => {
+            log::info!("Stopped listening on {:?}: {:?}", addresses, reason);
+            ctx.relays.on_listener_closed(listener_id);
+            ctx.ready.on_listener(swarm, listener_id);
+        }
+        SwarmEvent::Behaviour(AppBehaviourEvent::Rendezvous(rendezvous::client::Event::Discovered { rendezvous_node, registrations, cookie })) => {
+            log::info!("RDV discovered {} member(s) with: {rendezvous_node}", registrations.len());
+            let Some(room) = rendezvous_room(&mut ctx.rooms, cookie.namespace()) else { return };
+            emit_rendezvous(&ctx.event_bus, &room.room_id, "discovered", rendezvous_node);
+            room.rendezvous.on_discovered(swarm, rendezvous_node, registrations, cookie);
+        }
+        SwarmEvent::Behaviour(AppBehaviourEvent::Rendezvous(rendezvous::client::Event::DiscoverFailed { rendezvous_node, namespace, .. })) => {
             log::info!("RDV discover failed with: {rendezvous_node}");
+            let Some(room) = rendezvous_room(&mut ctx.rooms, namespace.as_ref()) else { return };
+            emit_rendezvous(&ctx.event_bus, &room.room_id, "discover-failed", rendezvous_node);
         }
-        SwarmEvent::Behaviour(AppBehaviourEvent::Rendezvous(rendezvous::client::Event::Registered { rendezvous_node, .. })) => {
-            log::info!("RDV registered with: {rendezvous_node}");
+        SwarmEvent::Behaviour(AppBehaviourEvent::Rendezvous(rendezvous::client::Event::Registered { rendezvous_node, ttl, namespace })) => {
+            log::info!("RDV registered with: {rendezvous_node} for {ttl}s");
+            let Some(room) = rendezvous_room(&mut ctx.rooms, Some(&namespace)) else { return };
+            emit_rendezvous(&ctx.event_bus, &room.room_id, "registered", rendezvous_node);
+            room.rendezvous.on_registered(rendezvous_node, ttl, Instant::now());
         }
-        SwarmEvent::Behaviour(AppBehaviourEvent::Rendezvous(rendezvous::client::Event::RegisterFailed { rendezvous_node, .. })) => {
-            log::info!("RDV registration failed with: {rendezvous_node}");
+        SwarmEvent::Behaviour(AppBehaviourEvent::Rendezvous(rendezvous::client::Event::RegisterFailed { rendezvous_node, namespace, error })) => {
+            log::info!("RDV registration failed with: {rendezvous_node}: {:?}", error);
+            let Some(room) = rendezvous_room(&mut ctx.rooms, Some(&namespace)) else { return };
+            emit_rendezvous(&ctx.event_bus, &room.room_id, "register-failed", rendezvous_node);
+            room.rendezvous.on_register_failed(rendezvous_node, Instant::now());
         }
         SwarmEvent::Behaviour(AppBehaviourEvent::Rendezvous(rendezvous::client::Event::Expired { peer })) => {
             log::info!("RDV expired: {peer}");
+            for room_id in ctx.rooms.keys() {
+                emit_rendezvous(&ctx.event_bus, room_id, "expired", peer);
+            }
         }
-        SwarmEvent::ConnectionClosed { peer_id, .. } => {
-            log::info!("Connection established with: {peer_id}");
+        SwarmEvent::ConnectionClosed { peer_id, endpoint, cause, num_established, .. } => {
+            log::info!("Connection closed with: {peer_id}");
+            let address = endpoint.get_remote_address();
+            let cause = cause.map(|c| c.to_string());
+            emit_to_rooms(ctx, |room_id| CallbackPayload::ConnectionClosed(ConnectionPayload::from((
+                room_id,
+                peer_id.to_string(),
+                address.to_string(),
+                is_relayed(address),
+                Transport::of(address),
+                cause.clone(),
+            ))));
+            if num_established == 0 {
+                ctx.relays.on_disconnected(peer_id);
+                for room in ctx.rooms.values_mut() {
+                    room.rendezvous.on_disconnected(peer_id, Instant::now());
+                }
+            }
+        }
+        SwarmEvent::OutgoingConnectionError { connection_id, peer_id, error } => {
+            log::info!("Could not connect to {:?}: {error}", peer_id);
+            if let Some(reply) = ctx.dials.remove(&connection_id) {
+                let _ = reply.send(Err(error.into()));
+            }
         }
-        SwarmEvent::NewListenAddr { address, .. } => {
+        SwarmEvent::NewListenAddr { listener_id, address } => {
             log::info!("Local node is listening on {address}");
+            ctx.ready.on_listener(swarm, listener_id);
         }
         event => {
             log::info!("Some other event: {:?}", event);
         }
     };
+}
+
+/// Hands a gossip message to the room it belongs to, once its author proved to be one of the room's members.
+fn handle_gossip_message(ctx: &mut NodeContext, swarm: &mut Swarm<AppBehaviour>, peer_id: PeerId, id: &gossipsub::MessageId, message: gossipsub::Message) -> gossipsub::MessageAcceptance {
+    let Some(room) = ctx.rooms.values_mut().find(|room| room.owns(&message.topic)) else {
+        log::warn!("Dropping message {id} on foreign topic {}", message.topic);
+        return gossipsub::MessageAcceptance::Ignore;
+    };
+
+    let opened = SignedEnvelope::from_bytes(&message.data)
+        .map_err(Error::from)
+        .and_then(|envelope| Ok((envelope.open(&message.topic)?, envelope.author, envelope.payload)));
+    let (author, author_key, payload) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            log::warn!("Rejecting unsigned message {id} from {peer_id}: {}", e.chain_message());
+            return gossipsub::MessageAcceptance::Reject;
+        }
+    };
+
+    match ctx.services.member_service.is_member(&room.room_id, &author.to_string()) {
+        Ok(true) => {}
+        Ok(false) => {
+            log::warn!("Rejecting message {id} from {author}, who is not a member of room {}", room.room_id);
+            return gossipsub::MessageAcceptance::Reject;
+        }
+        Err(e) => {
+            log::error!("Could not check the members of room {}: {}", room.room_id, e.chain_message());
+            return gossipsub::MessageAcceptance::Ignore;
+        }
+    }
+    let author_swarm = message.source.unwrap_or(peer_id);
+    room.swarm_peers.insert(author, author_swarm);
+
+    // Group keys are sealed to the public keys members sign with.
+    if room.known_keys.insert(author) {
+        let stored = identity::PublicKey::try_decode_protobuf(&author_key)
+            .map_err(Error::from)
+            .and_then(|key| ctx.services.member_service.set_public_key(&room.room_id, &key));
+        if let Err(e) = stored {
+            log::warn!("Could not store the public key of {author}: {}", e.chain_message());
+        }
+    }
+
+    // Custom topics carry chat messages too.
+    match room.topics.kind_of(&message.topic).unwrap_or(RoomTopicKind::Chat) {
+        RoomTopicKind::Chat => match SealedPayload::from_json(&payload) {
+            Ok(sealed) => {
+                let held = HeldMessage { author, id: id.clone(), topic: message.topic.clone(), sealed, envelope: message.data.clone(), status: MessageStatus::Received };
+                if let Some(held) = open_message(&ctx.event_bus, &ctx.services, room, held) {
+                    // The author has the key for sure, whoever relayed the message likely too.
+                    for peer in HashSet::from([author_swarm, peer_id]) {
+                        ctx.key_requests.request(swarm, peer, room.key_request(Some(held.sealed.epoch)), Instant::now());
+                    }
+                    room.hold(held);
+                }
+            }
+            Err(e) => {
+                log::warn!("Rejecting unsealed message {id} from {author}: {:?}", e);
+                return gossipsub::MessageAcceptance::Reject;
+            }
+        },
+        RoomTopicKind::Control => match RoomControl::from_json(&payload) {
+            Ok(control) => handle_room_control(&ctx.event_bus, &ctx.services, room, swarm, author, control),
+            Err(e) => log::warn!("Dropping malformed control message {id} from {peer_id}: {:?}", e),
+        },
+        RoomTopicKind::Signaling => {
+            log::debug!("Ignoring signaling message {id}");
+        }
+    }
+
+    gossipsub::MessageAcceptance::Accept
+}
+
+/// Counts one use of the invite and lets the joiner in, telling the other members about it.
+fn redeem_invite(ctx: &mut NodeContext, swarm: &mut Swarm<AppBehaviour>, request: InviteRequest) -> Result<InviteResponse> {
+    let room = room(ctx, &request.room_id)?;
+    let public_key = identity::PublicKey::try_decode_protobuf(&request.public_key)?;
+    if public_key.to_peer_id().to_string() != request.peer_id {
+        return Err(ErrorKind::InviteRejected("the public key does not belong to the joiner".to_string()).into());
+    }
+    ctx.services.invite_service.redeem(&request.invite_id, &request.room_id, now_millis())?;
+
+    let added_at = now_millis();
+    let member = Member::from((
+        room.room_id.clone(),
+        request.peer_id,
+        MemberRole::Member.as_str().to_string(),
+        room.local_peer().to_string(),
+        added_at,
+        Some(public_key.encode_protobuf()),
+    ));
+    ctx.services.member_service.add_member(&member)?;
+    ctx.event_bus.emit(CallbackPayload::Member(MemberPayload::added(&room.room_id, &member.peer_id, MemberRole::Member, &member.added_by)));
+
+    let control = MemberControl::Added { peer_id: member.peer_id, role: MemberRole::Member, added_at, public_key: member.public_key };
+    if let Err(e) = publish_control(room, swarm, RoomControl::Member(control)) {
+        log::warn!("Could not tell room {} about its new member: {}", room.room_id, e.chain_message());
+    }
+
+    let members = ctx.services.member_service.get_members(&room.room_id)?;
+    let group_key = ctx.services.group_key_service.current_key(&room.room_id)?
+        .map(|(epoch, key)| SealedKey::seal(&public_key, &room.room_id, epoch, &key))
+        .transpose()?;
+    Ok(InviteResponse::from((None, members, group_key)))
+}
+
+/// Stores and emits the message once it is opened; hands it back when the key of its epoch is missing.
+fn open_message(event_bus: &EventBus, services: &NodeServices, room: &RoomContext, held: HeldMessage) -> Option<HeldMessage> {
+    let key = match services.group_key_service.get_key(&room.room_id, held.sealed.epoch) {
+        Ok(Some(key)) => key,
+        Ok(None) => return Some(held),
+        Err(e) => {
+            log::error!("Could not load the group keys of room {}: {}", room.room_id, e.chain_message());
+            return None;
+        }
+    };
+
+    let opened = held.sealed.open(&key, &held.topic)
+        .and_then(|json| Ok(RoomMessage::from_json(&json)?))
+        .and_then(|room_message| {
+            room_message.file.as_ref().map(check_offer).transpose()?;
+            Ok(room_message)
+        });
+    let payload = match opened {
+        Ok(room_message) => MessagePayload::from((
+            room.room_id.clone(),
+            held.author.to_string(),
+            held.id.to_string(),
+            held.topic.to_string(),
+            room_message.payload,
+            room_message.sent_at,
+            held.status == MessageStatus::Synced,
+            room_message.file,
+        )),
+        Err(e) => {
+            log::warn!("Dropping message {} from {}: {}", held.id, held.author, e.chain_message());
+            return None;
+        }
+    };
+
+    remember_offer(services, &payload);
+    match services.message_service.store_message(&Message::stored(&payload, held.status, now_millis(), Some(held.envelope))) {
+        Ok(true) => event_bus.emit(CallbackPayload::Message(payload)),
+        Ok(false) => log::info!("Message {} was already delivered", payload.message_id),
+        Err(e) if held.status == MessageStatus::Synced => log::error!("Could not store synced message {}: {}", payload.message_id, e.chain_message()),
+        Err(e) => {
+            log::error!("Could not store message {}: {}", payload.message_id, e.chain_message());
+            event_bus.emit(CallbackPayload::Message(payload));
+        }
+    }
+    None
+}
 
+/// Opens the held messages whose group key arrived since.
+fn release_held(event_bus: &EventBus, services: &NodeServices, room: &mut RoomContext) {
+    for held in std::mem::take(&mut room.held) {
+        if let Some(held) = open_message(event_bus, services, room, held) {
+            room.held.push(held);
+        }
+    }
+}
+
+/// Asks every connected peer for the room's current group key.
+fn request_current_key(ctx: &mut NodeContext, swarm: &mut Swarm<AppBehaviour>, room_id: &str) {
+    let Some(room) = ctx.rooms.get(room_id) else { return };
+    for peer in swarm.connected_peers().copied().collect::<Vec<_>>() {
+        ctx.key_requests.request(swarm, peer, room.key_request(None), Instant::now());
+    }
+}
+
+/// Seals the group key a member asked for to its room identity.
+fn hand_out_key(ctx: &NodeContext, request: &GroupKeyRequest) -> Result<SealedKey> {
+    let room = room(ctx, &request.room_id)?;
+    let requester = identity::PublicKey::try_decode_protobuf(&request.public_key)?;
+    let requester_id = requester.to_peer_id().to_string();
+    if !ctx.services.member_service.is_member(&room.room_id, &requester_id)? {
+        return Err(ErrorKind::PermissionDenied(format!("{requester_id} is not a member of room {}", room.room_id)).into());
+    }
+
+    let missing = || Error::from(ErrorKind::GroupKeyMissing(room.room_id.clone()));
+    let group_keys = &ctx.services.group_key_service;
+    let (epoch, key) = match request.epoch {
+        Some(epoch) => (epoch, group_keys.get_key(&room.room_id, epoch)?.ok_or_else(missing)?),
+        None => group_keys.current_key(&room.room_id)?.ok_or_else(missing)?,
+    };
+    SealedKey::seal(&requester, &room.room_id, epoch, &key)
+}
+
+fn receive_key(ctx: &mut NodeContext, room_id: &str, response: GroupKeyResponse) {
+    let Some(room) = ctx.rooms.get_mut(room_id) else { return };
+    let Some(sealed) = response.key else {
+        log::info!("Got no group key for room {}: {:?}", room_id, response.rejection);
+        return;
+    };
+
+    let stored = sealed.open(&room.keypair, room_id)
+        .and_then(|key| ctx.services.group_key_service.add_key(room_id, sealed.epoch, &key));
+    match stored {
+        Ok(_) => release_held(&ctx.event_bus, &ctx.services, room),
+        Err(e) => log::warn!("Could not store the group key of room {}: {}", room_id, e.chain_message()),
+    }
+}
+
+/// Remembers the file a message offers, so it can be downloaded later.
+fn remember_offer(services: &NodeServices, payload: &MessagePayload) {
+    let Some(offer) = &payload.file else { return };
+    let file = SharedFile::offered(&payload.room_id, offer, &payload.peer_id, None, now_millis());
+    if let Err(e) = services.file_service.add_file(&file) {
+        log::error!("Could not store the offer of file {}: {}", offer.file_id, e.chain_message());
+    }
+}
+
+/// Reads the answer to a file request, off the swarm loop.
+type FileRead = Box<dyn FnOnce() -> Result<ChunkResponse> + Send>;
+
+/// Serves a page of the manifest or a chunk of a file this node holds, as long as it was offered in the room.
+///
+/// What is checked here is cheap; the disk is only read by what it hands back.
+fn serve_file(ctx: &NodeContext, request: ChunkRequest) -> Result<FileRead> {
+    let (ChunkRequest::Manifest { room_id, file_id, .. } | ChunkRequest::Chunk { room_id, file_id, .. }) = &request;
+    let room = room(ctx, room_id)?;
+    let file = ctx.services.file_service.get_file(&room.room_id, file_id)?;
+    // Offers from before the blob store are served from where they were offered.
+    let path = ctx.services.blob_service.held(file_id)?
+        .or_else(|| file.path.map(PathBuf::from))
+        .ok_or_else(|| Error::from(ErrorKind::FileNotFound(file_id.clone())))?;
+
+    match request {
+        ChunkRequest::Manifest { room_id, file_id, from } => {
+            if let Some(manifest) = ctx.services.file_service.get_manifest(&room_id, &file_id)? {
+                return Ok(Box::new(move || Ok(ChunkResponse::Manifest(manifest_page(&manifest, from)?))));
+            }
+
+            // Downloaded files, and offers from before manifests were kept, are hashed on the first request.
+            let files = ctx.services.file_service.clone();
+            Ok(Box::new(move || {
+                let (offer, manifest) = hash_file(&path, file.chunk_size)?;
+                if offer.file_id != file_id {
+                    return Err(ErrorKind::TransferFailed(format!("{} changed since it was offered", path.display())).into());
+                }
+                let manifest = manifest.concat();
+                files.set_manifest(&room_id, &file_id, &manifest)?;
+                Ok(ChunkResponse::Manifest(manifest_page(&manifest, from)?))
+            }))
+        }
+        ChunkRequest::Chunk { room_id, file_id, index } => {
+            let (epoch, key) = ctx.services.group_key_service.current_key(&room_id)?
+                .ok_or_else(|| Error::from(ErrorKind::GroupKeyMissing(room_id.clone())))?;
+            let scope = chunk_scope(&room_id, &file_id, index);
+            Ok(Box::new(move || {
+                let chunk = read_chunk(&path, file.chunk_size, index)?;
+                Ok(ChunkResponse::Chunk(SealedPayload::seal_bytes(&key, epoch, &scope, &chunk)?))
+            }))
+        }
+    }
+}
+
+fn answer_file_request(swarm: &mut Swarm<AppBehaviour>, peer: PeerId, channel: request_response::ResponseChannel<ChunkResponse>, response: ChunkResponse) {
+    if swarm.behaviour_mut().file_transfer_mut().send_response(channel, response).is_err() {
+        log::warn!("Could not answer the file request of {peer}");
+    }
+}
+
+/// Answers the file requests and moves the downloads along that waited on the disk.
+fn handle_transfer_done(ctx: &mut NodeContext, swarm: &mut Swarm<AppBehaviour>, done: TransferDone) {
+    let progress = match done {
+        TransferDone::Served { peer, channel, response } => {
+            answer_file_request(swarm, peer, channel, response);
+            None
+        }
+        TransferDone::Checked { key, checked } => ctx.downloads.on_checked(swarm, key, checked),
+        TransferDone::Finished { key, finished } => ctx.downloads.on_finished(key, finished),
+    };
+    if let Some(progress) = progress {
+        report_download(ctx, progress);
+    }
+}
+
+fn open_chunk(ctx: &NodeContext, request_id: &request_response::OutboundRequestId, sealed: &SealedPayload) -> Result<Vec<u8>> {
+    let (room_id, file_id, index) = ctx.downloads.request_of(request_id)
+        .ok_or_else(|| Error::from(ErrorKind::TransferFailed("unexpected chunk".to_string())))?;
+    let key = ctx.services.group_key_service.get_key(&room_id, sealed.epoch)?
+        .ok_or(ErrorKind::GroupKeyMissing(room_id.clone()))?;
+
+    sealed.open_bytes(&key, &chunk_scope(&room_id, &file_id, index.unwrap_or_default()))
+}
+
+fn report_download(ctx: &NodeContext, progress: FilePayload) {
+    if let Some(path) = progress.path.as_deref().filter(|_| progress.event == "completed") {
+        if let Err(e) = ctx.services.file_service.set_path(&progress.room_id, &progress.file_id, Some(path)) {
+            log::error!("Could not store where file {} landed: {}", progress.file_id, e.chain_message());
+        }
+    }
+    ctx.event_bus.emit(CallbackPayload::File(progress));
+}
+
+/// Asks `peer` for the room's messages sent after the latest one held from each sender.
+fn request_sync(ctx: &mut NodeContext, swarm: &mut Swarm<AppBehaviour>, room_id: &str, peer: PeerId) {
+    let Some(room) = ctx.rooms.get(room_id) else { return };
+    // A shared swarm cannot tell the peers of its rooms apart, the others answer they do not host the room.
+    let dedicated = *swarm.local_peer_id() == room.local_peer();
+    if dedicated && !ctx.services.member_service.is_member(room_id, &peer.to_string()).unwrap_or(false) {
+        return;
+    }
+
+    match ctx.services.message_service.high_water_marks(room_id) {
+        Ok(marks) => ctx.syncs.request(swarm, peer, SyncRequest::from((room_id.to_string(), marks, None))),
+        Err(e) => log::warn!("Could not sync room {}: {}", room_id, e.chain_message()),
+    }
+}
+
+/// The messages the requester is missing in the envelopes their authors signed, sealed with the group key so only members can read them.
+fn serve_sync(ctx: &NodeContext, request: &SyncRequest) -> Result<SyncResponse> {
+    let room = room(ctx, &request.room_id)?;
+    let (epoch, key) = ctx.services.group_key_service.current_key(&room.room_id)?
+        .ok_or_else(|| Error::from(ErrorKind::GroupKeyMissing(room.room_id.clone())))?;
+
+    let (missing, next) = ctx.services.message_service.messages_after(&room.room_id, &request.marks, request.after.as_ref(), SYNC_BATCH_SIZE)?;
+    let batch = missing.into_iter()
+        .filter_map(|message| {
+            let envelope = SignedEnvelope::from_bytes(message.envelope.as_deref()?);
+            Some(envelope.map(|envelope| SyncedMessage::from((message.id, message.sender_peer_id, message.topic, envelope))))
+        })
+        .collect::<serde_json::Result<Vec<_>>>()?;
+    let sealed = SealedPayload::seal(&key, epoch, &sync_scope(&room.room_id), &serde_json::to_string(&batch)?)?;
+
+    Ok(SyncResponse::from((Some(sealed), next, None)))
+}
+
+/// Takes in a batch and asks for the next one from where it ended, as long as the batch brought anything new.
+fn receive_sync(ctx: &mut NodeContext, swarm: &mut Swarm<AppBehaviour>, request: SyncRequest, peer: PeerId, response: SyncResponse) {
+    let room_id = request.room_id.as_str();
+    let Some(room) = ctx.rooms.get_mut(room_id) else { return };
+    let Some(sealed) = response.batch else {
+        log::info!("{peer} did not sync room {}: {:?}", room_id, response.rejection);
+        return;
+    };
+
+    let key = match ctx.services.group_key_service.get_key(room_id, sealed.epoch) {
+        Ok(Some(key)) => key,
+        Ok(None) => {
+            // The next connection syncs again, with the key by then.
+            ctx.key_requests.request(swarm, peer, room.key_request(Some(sealed.epoch)), Instant::now());
+            return;
+        }
+        Err(e) => {
+            log::error!("Could not load the group keys of room {}: {}", room_id, e.chain_message());
+            return;
+        }
+    };
+
+    let batch = sealed.open(&key, &sync_scope(room_id))
+        .and_then(|json| Ok(serde_json::from_str::<Vec<SyncedMessage>>(&json)?));
+    let batch = match batch {
+        Ok(batch) => batch,
+        Err(e) => {
+            log::warn!("Dropping the sync batch of {peer}: {}", e.chain_message());
+            return;
+        }
+    };
+
+    log::info!("Got {} missed messages of room {} from {peer}", batch.len(), room_id);
+    let mut added = 0;
+    for message in batch {
+        let message_id = message.id.clone();
+        let known = room.held.iter().any(|held| held.id.to_string() == message_id)
+            || ctx.services.message_service.has_message(room_id, &message_id).unwrap_or(false);
+        if known {
+            continue;
+        }
+
+        let held = match check_synced(&ctx.services, room, message) {
+            Ok(held) => held,
+            Err(e) => {
+                log::warn!("Dropping synced message {message_id} from {peer}: {}", e.chain_message());
+                continue;
+            }
+        };
+        added += 1;
+        // Messages of older epochs than the batch may still need their key.
+        if let Some(held) = open_message(&ctx.event_bus, &ctx.services, room, held) {
+            ctx.key_requests.request(swarm, peer, room.key_request(Some(held.sealed.epoch)), Instant::now());
+            room.hold(held);
+        }
+    }
+
+    // A peer that keeps sending what is held already has nothing more to give.
+    match response.next {
+        Some(next) if added > 0 => ctx.syncs.request(swarm, peer, SyncRequest { after: Some(next), ..request }),
+        Some(_) => log::info!("Stopping the sync of room {} with {peer}, its last batch brought nothing new", room_id),
+        None => {}
+    }
+}
+
+/// Whoever serves a sync could claim any author, the message counts only in the envelope a member signed it in.
+fn check_synced(services: &NodeServices, room: &RoomContext, message: SyncedMessage) -> Result<HeldMessage> {
+    let topic = TopicHash::from_raw(&message.topic);
+    if !room.owns(&topic) {
+        return Err(ErrorKind::PermissionDenied(format!("{topic} is not a topic of room {}", room.room_id)).into());
+    }
+
+    let author = message.author(&topic)?;
+    let envelope = message.envelope.to_bytes()?;
+    let id = gossip_message_id(&topic, &envelope);
+    if id.to_string() != message.id {
+        return Err(ErrorKind::InvalidSignature(format!("the envelope is that of message {id}")).into());
+    }
+    if !services.member_service.is_member(&room.room_id, &message.sender_peer_id)? {
+        return Err(ErrorKind::PermissionDenied(format!("{author} is not a member of room {}", room.room_id)).into());
+    }
+
+    let sealed = SealedPayload::from_json(&message.envelope.payload)?;
+    Ok(HeldMessage { author, id, topic, sealed, envelope, status: MessageStatus::Synced })
+}
+
+/// The room a rendezvous event is about, told apart by the namespace it registered under.
+fn rendezvous_room<'a>(rooms: &'a mut HashMap<String, RoomContext>, namespace: Option<&Namespace>) -> Option<&'a mut RoomContext> {
+    let room = namespace.and_then(|namespace| rooms.values_mut().find(|room| room.rendezvous.owns(namespace)));
+    if room.is_none() {
+        log::debug!("Ignoring rendezvous event for {:?}", namespace);
+    }
+    room
+}
+
+fn handle_room_control(event_bus: &EventBus, services: &NodeServices, room: &mut RoomContext, swarm: &mut Swarm<AppBehaviour>, source: PeerId, control: RoomControl) {
+    match control {
+        RoomControl::Call(call) => match room.calls.on_control(&source.to_string(), call, Instant::now()) {
+            Ok(Some(update)) => {
+                apply_call_update(event_bus, room, swarm, update);
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Could not apply call control from {source}: {:?}", e),
+        },
+        RoomControl::Member(change) => {
+            if let Err(e) = apply_member_control(event_bus, &services.member_service, room, source, change) {
+                log::warn!("Could not apply member change from {source}: {}", e.chain_message());
+            }
+        }
+        RoomControl::Key(control) => match apply_key_control(services, room, source, control) {
+            Ok(()) => release_held(event_bus, services, room),
+            Err(e) => log::warn!("Could not apply the group key from {source}: {}", e.chain_message()),
+        },
+    }
+}
+
+fn apply_key_control(services: &NodeServices, room: &RoomContext, source: PeerId, control: GroupKeyControl) -> Result<()> {
+    if !services.member_service.is_owner(&room.room_id, &source.to_string())? {
+        return Err(ErrorKind::PermissionDenied(format!("{source} does not own room {}", room.room_id)).into());
+    }
+
+    let local = room.local_peer().to_string();
+    let Some(sealed) = control.keys.iter().find(|key| key.peer_id == local) else {
+        // Members left out ask for the key when the first message of the epoch arrives.
+        log::info!("Epoch {} of room {} was not sealed to us", control.epoch, room.room_id);
+        return Ok(());
+    };
+
+    let key = sealed.open(&room.keypair, &room.room_id)?;
+    services.group_key_service.add_key(&room.room_id, sealed.epoch, &key)?;
     Ok(())
 }
 
+fn apply_member_control(event_bus: &EventBus, member_service: &MemberService, room: &RoomContext, source: PeerId, change: MemberControl) -> Result<()> {
+    if !member_service.is_owner(&room.room_id, &source.to_string())? {
+        return Err(ErrorKind::PermissionDenied(format!("{source} does not own room {}", room.room_id)).into());
+    }
+
+    match change {
+        MemberControl::Added { peer_id, role, added_at, public_key } => {
+            let member = Member::from((room.room_id.clone(), peer_id.clone(), role.as_str().to_string(), source.to_string(), added_at, public_key));
+            member_service.add_member(&member)?;
+            event_bus.emit(CallbackPayload::Member(MemberPayload::added(&room.room_id, &peer_id, role, &member.added_by)));
+        }
+        MemberControl::Removed { peer_id } => {
+            if member_service.remove_member(&room.room_id, &peer_id)? {
+                event_bus.emit(CallbackPayload::Member(MemberPayload::removed(&room.room_id, &peer_id, &source.to_string())));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn emit_rendezvous(event_bus: &EventBus, room_id: &str, status: &str, peer_id: PeerId) {
+    event_bus.emit(CallbackPayload::Rendezvous(RendezvousPayload::from((
+        room_id.to_string(), status.to_string(), peer_id.to_string(),
+    ))));
+}
@@ -4,19 +4,26 @@ use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::sqlite::SqliteConnection;
 use libp2p::identity;
 use libp2p::identity::ecdsa;
+use rand::RngCore;
 
 use crate::entities::noise::NoiseModel;
 use crate::models::error::*;
 use crate::schema::noise_keys::dsl::*;
+use crate::services::key_vault::KeyVault;
 
+/// Length of a room's pre-shared key.
+pub const PSK_SIZE: usize = 32;
+
+/// The identities of the rooms; their private and pre-shared keys are sealed with the master key once the vault is set up.
 #[derive(Debug)]
 pub struct NoiseKeyService {
     db_pool: Pool<ConnectionManager<SqliteConnection>>,
+    vault: KeyVault,
 }
 
 impl NoiseKeyService {
-    pub fn new(db_pool: Pool<ConnectionManager<SqliteConnection>>) -> Self {
-        NoiseKeyService { db_pool }
+    pub fn new(db_pool: Pool<ConnectionManager<SqliteConnection>>, vault: KeyVault) -> Self {
+        NoiseKeyService { db_pool, vault }
     }
 
     fn generate_ecdsa_keypair(room_id: &str) -> Result<NoiseModel> {
@@ -24,16 +31,44 @@ impl NoiseKeyService {
         let keypair = identity::Keypair::generate_ecdsa();
         let kp = keypair.try_into_ecdsa()?;
 
-        let entity = NoiseModel::from((room_id.to_string(), kp.secret().to_bytes(), kp.public().to_bytes()));
+        let entity = NoiseModel::from((room_id.to_string(), kp.secret().to_bytes(), kp.public().to_bytes(), None, false, false));
 
         log::info!("Generated ECDSA keypair for room {}", room_id);
         Ok(entity)
     }
 
-    #[inline]
-    fn from_entity(entity: NoiseModel) -> Result<identity::Keypair> {
+    /// What a sealed private key is bound to, so it cannot pass for another room's.
+    fn sealed_for(key_id: &str) -> Vec<u8> {
+        format!("noise_keys/{key_id}").into_bytes()
+    }
+
+    fn psk_sealed_for(key_id: &str) -> Vec<u8> {
+        format!("noise_keys/{key_id}/psk").into_bytes()
+    }
+
+    /// Seals the private and pre-shared keys, unless the vault was never set up.
+    pub fn seal(&self, mut entity: NoiseModel) -> Result<NoiseModel> {
+        if !self.vault.is_set_up()? {
+            return Ok(entity);
+        }
+        if !entity.encrypted {
+            entity.private = self.vault.seal(&Self::sealed_for(&entity.id), &entity.private)?;
+            entity.encrypted = true;
+        }
+        if let (Some(key), false) = (&entity.psk, entity.psk_encrypted) {
+            entity.psk = Some(self.vault.seal(&Self::psk_sealed_for(&entity.id), key)?);
+            entity.psk_encrypted = true;
+        }
+        Ok(entity)
+    }
+
+    fn to_keypair(&self, entity: NoiseModel) -> Result<identity::Keypair> {
         log::info!("Converting NoiseModel to identity::Keypair");
-        let secret = ecdsa::SecretKey::try_from_bytes(&entity.private)?;
+        let private_key = match entity.encrypted {
+            true => self.vault.open(&Self::sealed_for(&entity.id), &entity.private)?,
+            false => zeroize::Zeroizing::new(entity.private),
+        };
+        let secret = ecdsa::SecretKey::try_from_bytes(private_key.as_slice())?;
         let keypair = identity::ecdsa::Keypair::from(secret);
 
         log::info!("Converted NoiseModel to identity::Keypair");
@@ -42,7 +77,7 @@ impl NoiseKeyService {
 
     pub fn create_key(&self, room_id: &str) -> Result<()> {
         log::info!("Creating ECDSA keypair for room {}", room_id);
-        let entity = Self::generate_ecdsa_keypair(room_id)?;
+        let entity = self.seal(Self::generate_ecdsa_keypair(room_id)?)?;
 
         let mut conn = self.db_pool.get()?;
 
@@ -64,12 +99,121 @@ impl NoiseKeyService {
         match result {
             Ok(entity) => {
                 log::info!("Got ECDSA keypair for room {}", room_id);
-                Self::from_entity(entity)
+                self.to_keypair(entity)
+            }
+            Err(diesel::result::Error::NotFound) => Err(ErrorKind::KeyNotFound.into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The keypair stored under `key_id`, generated on first use.
+    pub fn get_or_create_key(&self, key_id: &str) -> Result<identity::Keypair> {
+        match self.get_key(key_id) {
+            Err(Error(ErrorKind::KeyNotFound, _)) => {
+                self.create_key(key_id)?;
+                self.get_key(key_id)
             }
+            result => result,
+        }
+    }
+
+    /// The room's key with its private and pre-shared keys in the clear, as a backup holds it.
+    pub fn export_key(&self, room_id: &str) -> Result<NoiseModel> {
+        let mut entity: NoiseModel = noise_keys
+            .filter(id.eq(room_id))
+            .first(&mut self.db_pool.get()?)
+            .optional()?
+            .ok_or(ErrorKind::KeyNotFound)?;
+
+        if entity.encrypted {
+            entity.private = self.vault.open(&Self::sealed_for(&entity.id), &entity.private)?.to_vec();
+            entity.encrypted = false;
+        }
+        entity.psk = entity.psk.take()
+            .map(|key| self.open_psk(&entity.id, key, entity.psk_encrypted))
+            .transpose()?;
+        entity.psk_encrypted = false;
+        Ok(entity)
+    }
+
+    /// Seals the keys written before the vault was set up, or before pre-shared keys were sealed; returns how many rooms had some.
+    pub fn seal_plain_keys(&self) -> Result<usize> {
+        let plain: Vec<NoiseModel> = noise_keys
+            .filter(encrypted.eq(false).or(psk.is_not_null().and(psk_encrypted.eq(false))))
+            .load(&mut self.db_pool.get()?)?;
+        if plain.is_empty() {
+            return Ok(0);
+        }
+
+        log::info!("Sealing the keys of {} rooms", plain.len());
+        let sealed = plain.into_iter().map(|entity| self.seal(entity)).collect::<Result<Vec<_>>>()?;
+        let mut conn = self.db_pool.get()?;
+        conn.transaction(|conn| {
+            for entity in &sealed {
+                diesel::update(noise_keys.filter(id.eq(&entity.id)))
+                    .set((
+                        private.eq(&entity.private),
+                        encrypted.eq(entity.encrypted),
+                        psk.eq(&entity.psk),
+                        psk_encrypted.eq(entity.psk_encrypted),
+                    ))
+                    .execute(conn)?;
+            }
+            Ok(sealed.len())
+        })
+    }
+
+    /// A random pre-shared key for a room that is a private network.
+    pub fn generate_psk() -> [u8; PSK_SIZE] {
+        let mut key = [0u8; PSK_SIZE];
+        rand::thread_rng().fill_bytes(&mut key);
+        key
+    }
+
+    pub fn set_psk(&self, room_id: &str, key: Option<[u8; PSK_SIZE]>) -> Result<()> {
+        log::info!("Setting the pre-shared key of room {}", room_id);
+        let sealing = self.vault.is_set_up()?;
+        let stored = match (key, sealing) {
+            (Some(key), true) => Some(self.vault.seal(&Self::psk_sealed_for(room_id), &key)?),
+            (key, _) => key.map(Vec::from),
+        };
+
+        let mut conn = self.db_pool.get()?;
+        let updated = diesel::update(noise_keys.filter(id.eq(room_id)))
+            .set((psk.eq(stored), psk_encrypted.eq(sealing)))
+            .execute(&mut conn)?;
+
+        if updated == 0 {
+            return Err(ErrorKind::KeyNotFound.into());
+        }
+        Ok(())
+    }
+
+    /// The room's pre-shared key; `None` when anyone may connect to its swarm.
+    pub fn get_psk(&self, room_id: &str) -> Result<Option<[u8; PSK_SIZE]>> {
+        let conn = &mut self.db_pool.get()?;
+        let result: QueryResult<(Option<Vec<u8>>, bool)> = noise_keys
+            .filter(id.eq(room_id))
+            .select((psk, psk_encrypted))
+            .first(conn);
+
+        match result {
+            Ok((Some(key), sealed)) => self.open_psk(room_id, key, sealed)?.try_into()
+                .map(Some)
+                .map_err(|_| ErrorKind::InvalidKey(format!("the pre-shared key of room {room_id} is malformed")).into()),
+            Ok((None, _)) => Ok(None),
+            Err(diesel::result::Error::NotFound) => Err(ErrorKind::KeyNotFound.into()),
             Err(e) => Err(e.into()),
         }
     }
 
+    fn open_psk(&self, room_id: &str, stored: Vec<u8>, sealed: bool) -> Result<Vec<u8>> {
+        match sealed {
+            true => Ok(self.vault.open(&Self::psk_sealed_for(room_id), &stored)?.to_vec()),
+            false => Ok(stored),
+        }
+    }
+
     pub fn delete_key(&self, room_id: &str) -> Result<()> {
         log::info!("Deleting ECDSA keypair for room {}", room_id);
         let mut conn = self.db_pool.get()?;
@@ -85,13 +229,18 @@ mod tests {
     use diesel::r2d2::ConnectionManager;
     use diesel::r2d2::Pool;
 
+    use crate::models::master_key::MasterSecret;
     use crate::services::connection::establish_connection;
 
     use super::*;
 
     fn setup_test_db() -> Pool<ConnectionManager<SqliteConnection>> {
         let database_url = ":memory:"; // SQLite in-memory database
-        establish_connection(Some(database_url.to_string()))
+        establish_connection(Some(database_url.to_string())).unwrap()
+    }
+
+    fn service(db_pool: Pool<ConnectionManager<SqliteConnection>>) -> NoiseKeyService {
+        NoiseKeyService::new(db_pool.clone(), KeyVault::new(db_pool))
     }
 
     #[test]
@@ -104,7 +253,7 @@ mod tests {
     #[test]
     fn test_create_and_get_key() {
         let db_pool = setup_test_db();
-        let service = NoiseKeyService::new(db_pool);
+        let service = service(db_pool);
         let room_id = "test_room_1";
 
         let create_result = service.create_key(room_id);
@@ -114,10 +263,35 @@ mod tests {
         assert!(get_key_result.is_ok());
     }
 
+    #[test]
+    fn test_get_or_create_key_is_stable() {
+        let db_pool = setup_test_db();
+        let service = service(db_pool);
+
+        let first = service.get_or_create_key("node").unwrap();
+        let second = service.get_or_create_key("node").unwrap();
+
+        assert_eq!(first.public(), second.public());
+    }
+
+    #[test]
+    fn test_psk_round_trip() {
+        let db_pool = setup_test_db();
+        let service = service(db_pool);
+        service.create_key("room").unwrap();
+        assert_eq!(service.get_psk("room").unwrap(), None);
+
+        let key = NoiseKeyService::generate_psk();
+        service.set_psk("room", Some(key)).unwrap();
+        assert_eq!(service.get_psk("room").unwrap(), Some(key));
+
+        assert!(service.set_psk("unknown", Some(key)).is_err());
+    }
+
     #[test]
     fn test_delete_key() {
         let db_pool = setup_test_db();
-        let service = NoiseKeyService::new(db_pool);
+        let service = service(db_pool);
         let room_id = "test_room_2";
 
         let create_result = service.create_key(room_id);
@@ -129,4 +303,51 @@ mod tests {
         let get_key_after_delete = service.get_key(room_id);
         assert!(get_key_after_delete.is_err()); // Expect an error after deleting the key
     }
+
+    #[test]
+    fn test_plain_keys_are_sealed_on_unlock() {
+        let db_pool = setup_test_db();
+        let vault = KeyVault::new(db_pool.clone());
+        let service = NoiseKeyService::new(db_pool.clone(), vault.clone());
+        let plain = service.get_or_create_key("room").unwrap();
+
+        vault.unlock(MasterSecret::host(&base64::Engine::encode(&base64::engine::general_purpose::STANDARD, [7u8; 32])).unwrap()).unwrap();
+        assert_eq!(service.seal_plain_keys().unwrap(), 1);
+        service.create_key("other").unwrap();
+        assert_eq!(service.seal_plain_keys().unwrap(), 0);
+
+        let stored: NoiseModel = noise_keys.filter(id.eq("room")).first(&mut db_pool.get().unwrap()).unwrap();
+        assert!(stored.encrypted);
+        assert_eq!(service.get_key("room").unwrap().public(), plain.public());
+
+        vault.lock();
+        assert_eq!(service.get_key("room").unwrap_err().code(), "SDK_LOCKED");
+    }
+
+    #[test]
+    fn test_psk_is_sealed_at_rest() {
+        let db_pool = setup_test_db();
+        let vault = KeyVault::new(db_pool.clone());
+        let service = NoiseKeyService::new(db_pool.clone(), vault.clone());
+        let stored = |room_id: &str| -> NoiseModel { noise_keys.filter(id.eq(room_id)).first(&mut db_pool.get().unwrap()).unwrap() };
+        let key = NoiseKeyService::generate_psk();
+
+        // Written in the clear before the vault, then sealed on unlock like the private key.
+        service.create_key("room").unwrap();
+        service.set_psk("room", Some(key)).unwrap();
+        assert_eq!(stored("room").psk, Some(key.to_vec()));
+
+        vault.unlock(MasterSecret::Passphrase("correct horse".to_string().into())).unwrap();
+        assert_eq!(service.seal_plain_keys().unwrap(), 1);
+        service.create_key("other").unwrap();
+        service.set_psk("other", Some(key)).unwrap();
+
+        for room_id in ["room", "other"] {
+            let stored = stored(room_id);
+            assert!(stored.psk_encrypted);
+            assert_ne!(stored.psk, Some(key.to_vec()));
+            assert_eq!(service.get_psk(room_id).unwrap(), Some(key));
+            assert_eq!(service.export_key(room_id).unwrap().psk, Some(key.to_vec()));
+        }
+    }
 }
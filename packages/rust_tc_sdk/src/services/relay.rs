@@ -0,0 +1,179 @@
+use std::collections::{HashMap, HashSet};
+
+use libp2p::core::ConnectedPoint;
+use libp2p::core::transport::ListenerId;
+use libp2p::multiaddr::Protocol;
+use libp2p::{relay, Multiaddr, PeerId, StreamProtocol, Swarm};
+
+use crate::models::behaviour::AppBehaviour;
+
+/// Whether a connection to `address` goes through a relay circuit.
+pub fn is_relayed(address: &Multiaddr) -> bool {
+    address.iter().any(|protocol| protocol == Protocol::P2pCircuit)
+}
+
+/// Keeps a `/p2p-circuit` reservation on the bootstrap nodes of the rooms, so that members
+/// behind a NAT can still be reached and then hole-punched.
+///
+/// Any peer may claim to relay; only the configured ones are trusted with our circuits.
+#[derive(Default)]
+pub struct RelayReservations {
+    /// Where we reached each directly connected peer.
+    addresses: HashMap<PeerId, Multiaddr>,
+    /// The rendezvous nodes of the rooms on the swarm.
+    allowed: HashSet<PeerId>,
+    /// Connected peers that speak the relay hop protocol, trusted or not.
+    offers: HashSet<PeerId>,
+    /// Trusted relays and our reservation on each.
+    relays: HashMap<PeerId, Option<ListenerId>>,
+}
+
+impl RelayReservations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_connected(&mut self, peer: PeerId, endpoint: &ConnectedPoint) {
+        if let ConnectedPoint::Dialer { address, .. } = endpoint {
+            if !is_relayed(address) {
+                let mut address = address.clone();
+                if let Some(Protocol::P2p(_)) = address.iter().last() {
+                    address.pop();
+                }
+                self.addresses.insert(peer, address);
+            }
+        }
+    }
+
+    /// The reservation listener closes on its own once the relay is gone.
+    pub fn on_disconnected(&mut self, peer: PeerId) {
+        self.addresses.remove(&peer);
+        self.offers.remove(&peer);
+        self.relays.remove(&peer);
+    }
+
+    pub fn on_identified(&mut self, swarm: &mut Swarm<AppBehaviour>, peer: PeerId, protocols: &[StreamProtocol]) {
+        if protocols.contains(&relay::HOP_PROTOCOL_NAME) {
+            self.offers.insert(peer);
+            self.trust_offers();
+            self.reserve(swarm, peer);
+        }
+    }
+
+    /// Trusts the relays in `allowed` from now on, and drops the reservations held on any other.
+    pub fn set_allowed(&mut self, swarm: &mut Swarm<AppBehaviour>, allowed: HashSet<PeerId>) {
+        self.allowed = allowed;
+        let allowed = &self.allowed;
+        self.relays.retain(|peer, listener| {
+            if allowed.contains(peer) {
+                return true;
+            }
+            log::info!("Dropping the relay reservation on {peer}, no room uses it anymore");
+            if let Some(listener) = listener {
+                swarm.remove_listener(*listener);
+            }
+            false
+        });
+        self.trust_offers();
+        self.tick(swarm);
+    }
+
+    fn trust_offers(&mut self) {
+        for peer in &self.offers {
+            if self.allowed.contains(peer) {
+                self.relays.entry(*peer).or_default();
+            } else if !self.relays.contains_key(peer) {
+                log::debug!("Not reserving a circuit on {peer}, which is no rendezvous node of ours");
+            }
+        }
+    }
+
+    pub fn on_reservation_accepted(&self, relay_peer_id: PeerId, renewal: bool) {
+        if !renewal {
+            log::info!("Reachable through relay {relay_peer_id}");
+        }
+    }
+
+    /// Lets the next tick ask again when a relay dropped our reservation.
+    pub fn on_listener_closed(&mut self, listener_id: ListenerId) {
+        for listener in self.relays.values_mut() {
+            if *listener == Some(listener_id) {
+                *listener = None;
+            }
+        }
+    }
+
+    pub fn tick(&mut self, swarm: &mut Swarm<AppBehaviour>) {
+        let pending: Vec<PeerId> = self.relays.iter()
+            .filter(|(_, listener)| listener.is_none())
+            .map(|(peer, _)| *peer)
+            .collect();
+
+        for peer in pending {
+            self.reserve(swarm, peer);
+        }
+    }
+
+    fn reserve(&mut self, swarm: &mut Swarm<AppBehaviour>, peer: PeerId) {
+        let Some(address) = self.circuit_address(peer) else { return };
+        let Some(listener) = self.relays.get_mut(&peer) else { return };
+        if listener.is_some() {
+            return;
+        }
+
+        log::info!("Requesting a relay reservation on {address}");
+        match swarm.listen_on(address.clone()) {
+            Ok(id) => *listener = Some(id),
+            Err(e) => log::warn!("Could not listen on {address}: {:?}", e),
+        }
+    }
+
+    fn circuit_address(&self, peer: PeerId) -> Option<Multiaddr> {
+        let address = self.addresses.get(&peer)?;
+        Some(address.clone().with(Protocol::P2p(peer)).with(Protocol::P2pCircuit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libp2p::core::Endpoint;
+
+    use super::*;
+
+    #[test]
+    fn test_only_rendezvous_nodes_are_trusted_to_relay() {
+        let (trusted, stranger) = (PeerId::random(), PeerId::random());
+        let mut reservations = RelayReservations::new();
+        reservations.allowed.insert(trusted);
+
+        reservations.offers.extend([trusted, stranger]);
+        reservations.trust_offers();
+
+        assert!(reservations.relays.contains_key(&trusted));
+        assert!(!reservations.relays.contains_key(&stranger));
+    }
+
+    #[test]
+    fn test_is_relayed() {
+        let relay = PeerId::random();
+        let direct: Multiaddr = "/ip4/1.2.3.4/tcp/4000".parse().unwrap();
+        let relayed = direct.clone().with(Protocol::P2p(relay)).with(Protocol::P2pCircuit);
+
+        assert!(!is_relayed(&direct));
+        assert!(is_relayed(&relayed));
+    }
+
+    #[test]
+    fn test_circuit_address_uses_dialed_address() {
+        let relay = PeerId::random();
+        let dialed: Multiaddr = format!("/ip4/1.2.3.4/tcp/4000/p2p/{relay}").parse().unwrap();
+        let mut reservations = RelayReservations::new();
+
+        reservations.on_connected(relay, &ConnectedPoint::Dialer {
+            address: dialed.clone(),
+            role_override: Endpoint::Dialer,
+        });
+
+        assert_eq!(reservations.circuit_address(relay), Some(dialed.with(Protocol::P2pCircuit)));
+    }
+}
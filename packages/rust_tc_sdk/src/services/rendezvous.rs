@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use libp2p::multiaddr::Protocol;
+use libp2p::rendezvous::{Cookie, Namespace, Registration, Ttl};
+use libp2p::swarm::dial_opts::DialOpts;
+use libp2p::{Multiaddr, PeerId, Swarm};
+use uuid::Uuid;
+
+use crate::models::behaviour::AppBehaviour;
+
+/// What we ask the rendezvous nodes for; the bootstrap server caps it at five minutes.
+const REGISTRATION_TTL: Ttl = 5 * 60;
+
+/// How often the members registered under the room's namespace are fetched again.
+const DISCOVER_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long to wait before retrying a failed registration or dial.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+struct RendezvousNode {
+    address: Multiaddr,
+    cookie: Option<Cookie>,
+    next_register: Option<Instant>,
+    next_discover: Option<Instant>,
+    next_dial: Instant,
+}
+
+/// Keeps one room registered on its rendezvous nodes and dials the members found there.
+pub struct RendezvousClient {
+    namespace: Namespace,
+    nodes: HashMap<PeerId, RendezvousNode>,
+}
+
+impl RendezvousClient {
+    /// `addresses` must end with the `/p2p/<peer id>` of the rendezvous node.
+    pub fn new(room_id: &str, addresses: &[Multiaddr]) -> Self {
+        let now = Instant::now();
+        let nodes = addresses.iter()
+            .filter_map(|address| match address.iter().last() {
+                Some(Protocol::P2p(peer_id)) => Some((peer_id, address.clone())),
+                _ => {
+                    log::warn!("Ignoring rendezvous node without a peer id: {address}");
+                    None
+                }
+            })
+            .map(|(peer_id, address)| (peer_id, RendezvousNode {
+                address,
+                cookie: None,
+                next_register: None,
+                next_discover: None,
+                next_dial: now,
+            }))
+            .collect();
+
+        Self { namespace: Self::namespace(room_id), nodes }
+    }
+
+    /// The namespace members of `room_id` meet under, without revealing the room ID itself.
+    pub fn namespace(room_id: &str) -> Namespace {
+        let hidden = Uuid::new_v5(&Uuid::NAMESPACE_OID, room_id.as_bytes());
+        Namespace::new(format!("vichiz/{hidden}")).expect("a uuid namespace is always short enough")
+    }
+
+    /// The rendezvous nodes this room registers on, as they were given.
+    pub fn addresses(&self) -> Vec<Multiaddr> {
+        self.nodes.values().map(|node| node.address.clone()).collect()
+    }
+
+    /// The rendezvous nodes, which are the only relays the room trusts too.
+    pub fn peers(&self) -> impl Iterator<Item = PeerId> + '_ {
+        self.nodes.keys().copied()
+    }
+
+    /// Whether rendezvous events about `namespace` belong to this room.
+    pub fn owns(&self, namespace: &Namespace) -> bool {
+        &self.namespace == namespace
+    }
+
+    /// Registers and discovers as soon as a rendezvous node is connected.
+    pub fn on_connected(&mut self, peer: PeerId, now: Instant) {
+        if let Some(node) = self.nodes.get_mut(&peer) {
+            node.next_register = Some(now);
+            node.next_discover = Some(now);
+        }
+    }
+
+    pub fn on_disconnected(&mut self, peer: PeerId, now: Instant) {
+        if let Some(node) = self.nodes.get_mut(&peer) {
+            node.next_register = None;
+            node.next_discover = None;
+            node.next_dial = now + RETRY_DELAY;
+        }
+    }
+
+    /// Registration needs an external address, so a newly confirmed one is worth registering right away.
+    pub fn on_external_address(&mut self, swarm: &mut Swarm<AppBehaviour>, now: Instant) {
+        let connected: Vec<PeerId> = self.nodes.keys()
+            .filter(|peer| swarm.is_connected(peer))
+            .copied()
+            .collect();
+
+        for peer in connected {
+            self.register(swarm, peer, now);
+        }
+    }
+
+    /// Schedules the re-registration well before the registration expires.
+    pub fn on_registered(&mut self, peer: PeerId, ttl: Ttl, now: Instant) {
+        if let Some(node) = self.nodes.get_mut(&peer) {
+            node.next_register = Some(now + Duration::from_secs(ttl * 3 / 4));
+        }
+    }
+
+    pub fn on_register_failed(&mut self, peer: PeerId, now: Instant) {
+        if let Some(node) = self.nodes.get_mut(&peer) {
+            node.next_register = Some(now + RETRY_DELAY);
+        }
+    }
+
+    /// Dials every member the rendezvous node knows about that we are not connected to yet.
+    pub fn on_discovered(&mut self, swarm: &mut Swarm<AppBehaviour>, peer: PeerId, registrations: Vec<Registration>, cookie: Cookie) {
+        if let Some(node) = self.nodes.get_mut(&peer) {
+            node.cookie = Some(cookie);
+        }
+
+        for registration in registrations {
+            let member = registration.record.peer_id();
+            if member == *swarm.local_peer_id() || swarm.is_connected(&member) {
+                continue;
+            }
+
+            log::info!("Dialing room member {member} found through {peer}");
+            let opts = DialOpts::peer_id(member)
+                .addresses(registration.record.addresses().to_vec())
+                .build();
+            if let Err(e) = swarm.dial(opts) {
+                log::warn!("Could not dial room member {member}: {:?}", e);
+            }
+        }
+    }
+
+    /// Dials, registers and discovers whatever is due.
+    pub fn tick(&mut self, swarm: &mut Swarm<AppBehaviour>, now: Instant) {
+        let peers: Vec<PeerId> = self.nodes.keys().copied().collect();
+        for peer in peers {
+            if !swarm.is_connected(&peer) {
+                self.dial(swarm, peer, now);
+                continue;
+            }
+
+            let Some(node) = self.nodes.get_mut(&peer) else { continue };
+            if node.next_register.is_some_and(|at| at <= now) {
+                self.register(swarm, peer, now);
+            }
+
+            let Some(node) = self.nodes.get_mut(&peer) else { continue };
+            if node.next_discover.is_some_and(|at| at <= now) {
+                node.next_discover = Some(now + DISCOVER_INTERVAL);
+                swarm.behaviour_mut().rendezvous_mut().discover(
+                    Some(self.namespace.clone()),
+                    node.cookie.clone(),
+                    None,
+                    peer,
+                );
+            }
+        }
+    }
+
+    fn dial(&mut self, swarm: &mut Swarm<AppBehaviour>, peer: PeerId, now: Instant) {
+        let Some(node) = self.nodes.get_mut(&peer) else { return };
+        if node.next_dial > now {
+            return;
+        }
+
+        node.next_dial = now + RETRY_DELAY;
+        log::info!("Dialing rendezvous node {}", node.address);
+        if let Err(e) = swarm.dial(node.address.clone()) {
+            log::warn!("Could not dial rendezvous node {}: {:?}", node.address, e);
+        }
+    }
+
+    fn register(&mut self, swarm: &mut Swarm<AppBehaviour>, peer: PeerId, now: Instant) {
+        let Some(node) = self.nodes.get_mut(&peer) else { return };
+
+        // Until `on_registered` or `on_register_failed` reschedules it.
+        node.next_register = None;
+        let result = swarm.behaviour_mut().rendezvous_mut()
+            .register(self.namespace.clone(), peer, Some(REGISTRATION_TTL));
+
+        if let Err(e) = result {
+            log::info!("Could not register with {peer} yet: {:?}", e);
+            node.next_register = Some(now + RETRY_DELAY);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_namespace_is_stable_and_hides_room_id() {
+        let namespace = RendezvousClient::namespace("my-room");
+
+        assert_eq!(namespace, RendezvousClient::namespace("my-room"));
+        assert_ne!(namespace, RendezvousClient::namespace("other-room"));
+        assert!(!namespace.to_string().contains("my-room"));
+    }
+
+    #[test]
+    fn test_only_addresses_with_peer_id_are_nodes() {
+        let peer = PeerId::random();
+        let with_peer: Multiaddr = format!("/ip4/127.0.0.1/tcp/4000/p2p/{peer}").parse().unwrap();
+        let without_peer: Multiaddr = "/ip4/127.0.0.1/tcp/4000".parse().unwrap();
+
+        let client = RendezvousClient::new("room", &[with_peer, without_peer]);
+
+        assert!(client.nodes.contains_key(&peer));
+        assert_eq!(client.nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_registration_is_renewed_before_ttl() {
+        let peer = PeerId::random();
+        let address: Multiaddr = format!("/ip4/127.0.0.1/tcp/4000/p2p/{peer}").parse().unwrap();
+        let mut client = RendezvousClient::new("room", &[address]);
+        let now = Instant::now();
+
+        client.on_registered(peer, REGISTRATION_TTL, now);
+
+        let next = client.nodes[&peer].next_register.unwrap();
+        assert!(next > now);
+        assert!(next < now + Duration::from_secs(REGISTRATION_TTL));
+    }
+}
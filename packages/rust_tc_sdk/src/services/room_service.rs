@@ -36,19 +36,14 @@ impl RoomService {
             .filter(id.eq(room_id))
             .first(conn);
 
-        log::info!("Got room {}", room_id);
-        Ok(result.unwrap())
-    }
-
-    pub fn update_room(&self, room_id: &str, room_name: &str) -> Result<()> {
-        log::info!("Updating room {}", room_id);
-        let mut conn = self.db_pool.get()?;
-        diesel::update(rooms.filter(id.eq(room_id)))
-            .set(name.eq(room_name))
-            .execute(&mut conn)?;
-
-        log::info!("Updated room {}", room_id);
-        Ok(())
+        match result {
+            Ok(room) => {
+                log::info!("Got room {}", room_id);
+                Ok(room)
+            }
+            Err(diesel::result::Error::NotFound) => Err(ErrorKind::RoomNotFound(room_id.to_string()).into()),
+            Err(e) => Err(e.into()),
+        }
     }
 
     pub fn delete_room(&self, room_id: &str) -> Result<()> {
@@ -81,7 +76,7 @@ mod tests {
 
     fn setup_database() -> Pool<ConnectionManager<SqliteConnection>> {
         let database_url = ":memory:"; // SQLite in-memory database
-        establish_connection(Some(database_url.to_string()))
+        establish_connection(Some(database_url.to_string())).unwrap()
     }
 
     #[test]
@@ -105,7 +100,16 @@ mod tests {
         assert_eq!(fetched_room.unwrap().name(), "Test Room");
     }
 
-    // Similarly, you can add tests for update_room, delete_room, and get_rooms.
+    #[test]
+    fn test_get_missing_room() {
+        let pool = setup_database();
+        let service = RoomService::new(pool);
+
+        let error = service.get_room("missing").unwrap_err();
+        assert_eq!(error.code(), "ROOM_NOT_FOUND");
+    }
+
+    // Similarly, you can add tests for delete_room and get_rooms.
 
     // Note: The tests are very basic and do not cover all edge cases.
     // It's recommended to add more tests and assertions to cover various scenarios.
@@ -1,138 +1,843 @@
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::thread::spawn;
+use std::future::Future;
+use std::path::PathBuf;
 
-use libp2p::Swarm;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+
+use libp2p::pnet::PreSharedKey;
+use libp2p::{identity, Multiaddr, PeerId};
 use neon::prelude::*;
 use tokio::sync::mpsc;
-use tokio::sync::Mutex;
 use uuid::Uuid;
 
+use crate::entities::file::SharedFile;
+use crate::entities::member::Member;
+use crate::entities::message::Message;
 use crate::entities::room::Room;
-use crate::models::behaviour::AppBehaviour;
+use crate::models::backup::{Backup, BackupConflict, BackupData, RestoredBackup};
+use crate::models::call_data::{CallId, StartCallData};
+use crate::models::callback_payload::{CallbackPayload, CallPayload, FilePayload, MemberPayload, MessagePayload};
 use crate::models::connection_data::ConnectionData;
 use crate::models::error::*;
+use crate::models::file_transfer::{DownloadFileData, OfferFileData};
+use crate::models::group_key::SealedKey;
+use crate::models::invitation::{Invitation, InviteOptions, InviteRequest, InviteResponse};
+use crate::models::master_key::{MasterSecret, UnlockData};
+use crate::models::member::MemberData;
+use crate::models::message::MessageQuery;
+use crate::models::network_status::NetworkStatus;
+use crate::models::peer_data::{AddressData, PeerData, PeerInfo, TopicData};
+use crate::models::rekey_data::RekeyData;
+use crate::models::room_control::{GroupKeyControl, MemberControl, RoomControl};
+use crate::models::room_message::now_millis;
 use crate::models::room_option::RoomOption;
 use crate::models::rust_sdk_options::*;
-use crate::services::connection::establish_connection;
-use crate::services::network::{create_private_network, run_swarm};
-use crate::services::noise_key_service::NoiseKeyService;
+use crate::models::send_message_data::SendMessageData;
+use crate::models::signal::SendSignalData;
+use crate::models::transport::Transport;
+use crate::services::backup_service::BackupService;
+use crate::services::call_service::CallService;
+use crate::services::invite_service::InviteService;
+use crate::services::member_service::MemberService;
+use crate::services::message_service::MessageService;
+use crate::services::connection::{open_database, rekey_database};
+use crate::services::blob_service::BlobService;
+use crate::services::database_url::{get_blob_dir, get_download_dir};
+use crate::services::event_bus::EventBus;
+use crate::services::file_service::FileService;
+use crate::services::file_transfers::{hash_file, CHUNK_SIZE};
+use crate::services::group_key_service::GroupKeyService;
+use crate::services::key_vault::KeyVault;
+use crate::services::network::{create_private_network, listen_on_defaults, run_swarm, NodeServices, RoomLaunch};
+use crate::services::rendezvous::RendezvousClient;
+use crate::services::noise_key_service::{NoiseKeyService, PSK_SIZE};
 use crate::services::room_service::RoomService;
-use crate::services::swarm_controller::SwarmController;
+use crate::services::swarm_controller::{CallCommand, SwarmController};
+
+/// Where the identity of the process-wide swarm is kept in `noise_keys`.
+const SHARED_SWARM_KEY: &str = "vichiz:shared-swarm";
 
 pub struct RustSDK {
+    db_url: Option<String>,
+    db_pool: Pool<ConnectionManager<SqliteConnection>>,
     room_service: RoomService,
     noise_key_service: NoiseKeyService,
-    callbacks: Vec<Root<JsFunction>>,
-    room_swarms: HashMap<String, Arc<Mutex<Swarm<AppBehaviour>>>>,
+    key_vault: KeyVault,
+    call_service: CallService,
+    invite_service: InviteService,
+    member_service: MemberService,
+    group_key_service: GroupKeyService,
+    message_service: MessageService,
+    file_service: FileService,
+    blob_service: BlobService,
+    backup_service: BackupService,
+    download_dir: PathBuf,
+    event_bus: EventBus,
+    shared_swarm: bool,
+    /// The process-wide swarm, started with the first room when `shared_swarm` is set.
+    shared: Option<SwarmController>,
     room_swarm_controller: HashMap<String, SwarmController>,
 }
 
 impl RustSDK {
-    pub fn new(options: RustSDKOptions) -> Self {
+    pub fn new(options: RustSDKOptions) -> Result<Self> {
         log::info!("Initializing Rust SDK");
-        let db_pool = establish_connection(options.db_url);
+        let blob_dir = get_blob_dir(options.db_url.as_deref());
+        let db_pool = open_database(options.db_url.clone(), options.db_key.as_deref())?;
 
         // Initialize the NoiseKeyService with the connection pool.
-        let noise_key_service = NoiseKeyService::new(db_pool.clone());
+        let key_vault = KeyVault::new(db_pool.clone());
+        let noise_key_service = NoiseKeyService::new(db_pool.clone(), key_vault.clone());
         let room_service = RoomService::new(db_pool.clone());
+        let call_service = CallService::new(db_pool.clone());
+        let invite_service = InviteService::new(db_pool.clone());
+        let member_service = MemberService::new(db_pool.clone());
+        let group_key_service = GroupKeyService::new(db_pool.clone(), key_vault.clone());
+        let message_service = MessageService::new(db_pool.clone());
+        let file_service = FileService::new(db_pool.clone());
+        let blob_service = BlobService::new(db_pool.clone(), blob_dir);
+        let backup_service = BackupService::new(db_pool.clone(), key_vault.clone());
+
+        // A host keeping the master key itself unlocks right away.
+        if let Some(master_key) = &options.master_key {
+            key_vault.unlock(MasterSecret::host(master_key)?)?;
+            noise_key_service.seal_plain_keys()?;
+            group_key_service.seal_plain_keys()?;
+        }
 
-        Self {
+        // Nothing can still be ringing or in progress from a previous run.
+        if let Err(e) = call_service.close_stale_calls() {
+            log::error!("Failed to close stale calls: {}", e);
+        }
+
+        let sdk = Self {
+            db_url: options.db_url,
+            db_pool,
             noise_key_service,
+            key_vault,
             room_service,
-            callbacks: Vec::new(),
-            room_swarms: HashMap::new(),
+            call_service,
+            invite_service,
+            member_service,
+            group_key_service,
+            message_service,
+            file_service,
+            blob_service,
+            backup_service,
+            download_dir: options.download_dir.map(PathBuf::from).unwrap_or_else(get_download_dir),
+            event_bus: EventBus::new(),
+            shared_swarm: options.shared_swarm,
+            shared: None,
             room_swarm_controller: HashMap::new(),
+        };
+
+        if sdk.key_vault.is_locked()? {
+            log::info!("The SDK is locked until unlockSdk");
+        } else if let Err(e) = sdk.upgrade_rooms() {
+            log::error!("Failed to upgrade rooms: {}", e);
+        }
+        if let Err(e) = sdk.blob_service.collect_garbage() {
+            log::error!("Failed to collect unreferenced blobs: {}", e);
         }
+
+        Ok(sdk)
+    }
+
+    /// Unlocks the rooms' keys, sealing those still stored in the clear.
+    ///
+    /// The first unlock sets the passphrase or master key every later one must match.
+    pub async fn unlock(&self, data: UnlockData) -> Result<()> {
+        log::info!("Unlocking the SDK");
+        let secret = MasterSecret::try_from(data)?;
+        let vault = self.key_vault.clone();
+        tokio::task::spawn_blocking(move || vault.unlock(secret)).await
+            .map_err(|e| Error::from(format!("could not unlock: {e}")))??;
+
+        let sealed = self.noise_key_service.seal_plain_keys()?;
+        if sealed > 0 {
+            log::info!("Sealed the keys of {} rooms stored in the clear", sealed);
+        }
+        let sealed = self.group_key_service.seal_plain_keys()?;
+        if sealed > 0 {
+            log::info!("Sealed {} group keys stored in the clear", sealed);
+        }
+        if let Err(e) = self.upgrade_rooms() {
+            log::error!("Failed to upgrade rooms: {}", e);
+        }
+
+        log::info!("Unlocked the SDK");
+        Ok(())
+    }
+
+    /// Forgets the master key and quits every room, as their swarms hold the unlocked keys.
+    pub async fn lock(&mut self) -> Result<()> {
+        log::info!("Locking the SDK");
+        if !self.key_vault.is_set_up()? {
+            return Err(ErrorKind::InvalidArgument("the SDK has no passphrase or master key to lock with yet".to_string()).into());
+        }
+        self.key_vault.lock();
+
+        let room_ids: Vec<String> = self.room_swarm_controller.keys().cloned().collect();
+        for room_id in room_ids {
+            self.quit_room(&room_id).await?;
+        }
+        if let Some(shared) = self.shared.take() {
+            shared.stop().await;
+        }
+
+        log::info!("Locked the SDK");
+        Ok(())
+    }
+
+    /// Re-encrypts the database under a new key; no room may be running meanwhile.
+    pub async fn rekey_database(&mut self, data: RekeyData) -> Result<()> {
+        log::info!("Rekeying the database");
+        if !self.room_swarm_controller.is_empty() {
+            return Err(ErrorKind::InvalidArgument("quit every room before rekeying the database".to_string()).into());
+        }
+        // The shared swarm works with the database too.
+        if let Some(shared) = self.shared.take() {
+            shared.stop().await;
+        }
+
+        let db_pool = rekey_database(self.db_pool.clone(), self.db_url.clone(), &data.key)?;
+        self.reopen(db_pool);
+
+        log::info!("Rekeyed the database");
+        Ok(())
+    }
+
+    /// Moves every service over to a new pool of the same database.
+    fn reopen(&mut self, db_pool: Pool<ConnectionManager<SqliteConnection>>) {
+        self.key_vault = self.key_vault.with_pool(db_pool.clone());
+        self.noise_key_service = NoiseKeyService::new(db_pool.clone(), self.key_vault.clone());
+        self.room_service = RoomService::new(db_pool.clone());
+        self.call_service = CallService::new(db_pool.clone());
+        self.invite_service = InviteService::new(db_pool.clone());
+        self.member_service = MemberService::new(db_pool.clone());
+        self.group_key_service = GroupKeyService::new(db_pool.clone(), self.key_vault.clone());
+        self.message_service = MessageService::new(db_pool.clone());
+        self.file_service = FileService::new(db_pool.clone());
+        self.blob_service = self.blob_service.with_pool(db_pool.clone());
+        self.backup_service = BackupService::new(db_pool.clone(), self.key_vault.clone());
+        self.db_pool = db_pool;
+    }
+
+    /// Writes every room, identities and history included, to an archive sealed under the passphrase.
+    pub async fn export_backup(&self, data: BackupData) -> Result<()> {
+        log::info!("Exporting a backup to {}", data.path);
+        let backup = self.backup_service.export()?;
+
+        let path = data.path.clone();
+        tokio::task::spawn_blocking(move || {
+            let archive = backup.seal(&data.passphrase)?;
+            // A crash halfway must not leave a truncated archive where an older one was.
+            let staged = format!("{path}.partial");
+            std::fs::write(&staged, archive)?;
+            std::fs::rename(&staged, &path)?;
+            Ok::<_, Error>(())
+        }).await.map_err(|e| Error::from(format!("could not export the backup: {e}")))??;
+
+        log::info!("Exported a backup to {}", data.path);
+        Ok(())
+    }
+
+    /// Restores the rooms of an archive; `data.conflict` says what happens to the rooms already here.
+    pub async fn import_backup(&self, data: BackupData) -> Result<RestoredBackup> {
+        log::info!("Importing the backup {}", data.path);
+        let path = data.path.clone();
+        let backup = tokio::task::spawn_blocking(move || Backup::open(&std::fs::read(&path)?, &data.passphrase))
+            .await.map_err(|e| Error::from(format!("could not import the backup: {e}")))??;
+
+        // A running swarm keeps the identity it was started with.
+        if data.conflict == BackupConflict::Overwrite {
+            if let Some(running) = backup.rooms.iter().find(|room| self.room_swarm_controller.contains_key(&room.room.id)) {
+                return Err(ErrorKind::InvalidArgument(format!("quit room {} before overwriting it", running.room.id)).into());
+            }
+        }
+
+        let restored = self.backup_service.restore(backup, data.conflict)?;
+        if let Err(e) = self.upgrade_rooms() {
+            log::error!("Failed to upgrade rooms: {}", e);
+        }
+        // Overwritten rooms may have been the last to offer some blobs.
+        if let Err(e) = self.blob_service.collect_garbage() {
+            log::error!("Failed to collect unreferenced blobs: {}", e);
+        }
+
+        log::info!("Imported the backup {}", data.path);
+        Ok(restored)
     }
 
     pub async fn create_room(&self, options: RoomOption) -> Result<Room> {
         log::info!("Creating room");
-        // Create noise keys for the room.
         let room_id = match options.id {
-            Some(x) if x.len() > 0 => x,
+            Some(x) if !x.is_empty() => x,
             _ => Uuid::new_v5(&Uuid::NAMESPACE_DNS, b"tc.ssegning.com").to_string()
         };
+        let psk = options.private.then(NoiseKeyService::generate_psk);
+        let room = self.store_room(room_id, options.name, psk)?;
+
+        // Whoever creates the room owns it, and makes its first group key.
+        let owner = self.noise_key_service.get_key(&room.id)?.public();
+        self.member_service.claim_room(&room.id, &owner, now_millis())?;
+        self.group_key_service.create_epoch(&room.id)?;
+
+        log::info!("Created room {}", room.id);
+        Ok(room)
+    }
+
+    /// Persists the room and its identity, without any member yet.
+    fn store_room(&self, room_id: String, name: String, psk: Option<[u8; PSK_SIZE]>) -> Result<Room> {
+        // Create noise keys for the room.
         self.noise_key_service.create_key(&room_id)?;
+        if psk.is_some() {
+            self.noise_key_service.set_psk(&room_id, psk)?;
+        }
 
         // Create a Room and persist it.
-        let room = Room::from((room_id, options.name));
+        let room = Room::from((room_id, name));
         self.room_service.create_room(&room)?;
-
-        log::info!("Created room {}", room.id);
         Ok(room)
     }
 
-    pub async fn start_room(&mut self, data: ConnectionData) -> Result<()> {
-        let swarm_arc = self.room_swarms.contains_key(&data.room_id);
-        if swarm_arc {
+    /// Brings rooms created by older versions up to date.
+    fn upgrade_rooms(&self) -> Result<()> {
+        for room in self.room_service.get_rooms()? {
+            // Rooms created before members were tracked belong to whoever holds them.
+            let local = self.noise_key_service.get_key(&room.id)?.public();
+            if self.member_service.claim_room(&room.id, &local, now_millis())? {
+                log::info!("Claimed room {} for {}", room.id, local.to_peer_id());
+            }
+
+            // Rooms created before messages were sealed get their first group key from an owner.
+            let owned = self.member_service.is_owner(&room.id, &local.to_peer_id().to_string())?;
+            if owned && self.group_key_service.current_key(&room.id)?.is_none() {
+                self.group_key_service.create_epoch(&room.id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The identity the room signs what it publishes with.
+    fn local_identity(&self, room_id: &str) -> Result<PeerId> {
+        Ok(self.noise_key_service.get_key(room_id)?.public().to_peer_id())
+    }
+
+    /// The local room identity, as long as it owns the room.
+    fn owner_identity(&self, room_id: &str) -> Result<PeerId> {
+        let local = self.local_identity(room_id)?;
+        if !self.member_service.is_owner(room_id, &local.to_string())? {
+            return Err(ErrorKind::PermissionDenied(format!("only owners manage the members of room {room_id}")).into());
+        }
+        Ok(local)
+    }
+
+    /// Launches the room and resolves with the addresses it ended up listening on.
+    pub async fn start_room(&mut self, data: ConnectionData) -> Result<Vec<String>> {
+        if self.room_swarm_controller.contains_key(&data.room_id) {
             log::info!("Swarm for room {} already exists", data.room_id);
-            return Ok(());
+            return self.controller(&data.room_id)?.listen_addresses().await;
         }
 
         log::info!("Starting room {}", data.room_id);
         // Start the room.
-        let room = match self.room_service.get_room(&data.room_id) {
-            Ok(r) => r,
-            Err(_) => panic!("Room not found")
+        self.room_service.get_room(&data.room_id)?;
+        let keypair = self.noise_key_service.get_key(&data.room_id)?;
+        let psk = self.noise_key_service.get_psk(&data.room_id)?.map(PreSharedKey::new);
+
+        let rendezvous_nodes = data.rendezvous_nodes.iter()
+            .map(|addr| addr.parse())
+            .collect::<std::result::Result<Vec<Multiaddr>, _>>()?;
+        let rendezvous = RendezvousClient::new(&data.room_id, &rendezvous_nodes);
+
+        // A private network is a property of the whole swarm, so private rooms never share one.
+        let controller = if self.shared_swarm && psk.is_none() {
+            self.shared_network()?
+        } else {
+            log::info!("Starting swarm for room {}", data.room_id);
+            let mut transports = data.enabled_transports();
+            if psk.is_some() {
+                transports.retain(|transport| *transport != Transport::Quic);
+            }
+            // The room's own listen addresses replace the defaults on a swarm of its own.
+            self.spawn_network(keypair.clone(), &transports, psk, data.room_listen_on.is_empty())?
         };
 
-        let keypair = match self.noise_key_service.get_key(&data.room_id) {
-            Ok(k) => k,
-            Err(_) => panic!("Key not found")
+        let room_id = data.room_id.clone();
+        let launch = RoomLaunch { room_id: room_id.clone(), keypair, config: data, rendezvous };
+        let addresses = match controller.join(launch).await {
+            Ok(addresses) => addresses,
+            Err(e) => {
+                if !self.is_shared(&controller) {
+                    controller.stop().await;
+                }
+                return Err(e);
+            }
         };
 
-        log::info!("Starting swarm for room {}", data.room_id);
-        let swarm: Swarm<AppBehaviour> = create_private_network(room, &data, keypair).await?;
-        self.room_swarms.insert(data.clone().room_id, Arc::new(Mutex::new(swarm)));
+        self.room_swarm_controller.insert(room_id.clone(), controller);
+        log::info!("Started room {} listening on {:?}", room_id, addresses);
+        Ok(addresses)
+    }
 
-        log::info!("Starting swarm controller for room {}", data.room_id);
-        let (sender, receiver) = mpsc::channel(8196);
-        let swarm_arc = self.room_swarms.get(&data.room_id).unwrap().clone();
-        spawn(|| run_swarm(swarm_arc, receiver));
-        log::info!("Started swarm controller for room {}", data.room_id);
+    /// The process-wide swarm, started on first use with an identity of its own.
+    fn shared_network(&mut self) -> Result<SwarmController> {
+        if let Some(shared) = &self.shared {
+            return Ok(shared.clone());
+        }
 
-        let controller = SwarmController { sender };
-        self.room_swarm_controller.insert(data.clone().room_id, controller);
-        log::info!("Started swarm for room {}", data.room_id);
+        log::info!("Starting the shared swarm");
+        let keypair = self.noise_key_service.get_or_create_key(SHARED_SWARM_KEY)?;
+        let shared = self.spawn_network(keypair, &Transport::ALL, None, true)?;
+        self.shared = Some(shared.clone());
+        Ok(shared)
+    }
 
-        log::info!("Started room {}", data.room_id);
-        Ok(())
+    fn is_shared(&self, controller: &SwarmController) -> bool {
+        self.shared.as_ref().is_some_and(|shared| shared.sender.same_channel(&controller.sender))
     }
 
-    async fn handle_swarm_event(&self) {}
+    fn spawn_network(&self, keypair: identity::Keypair, transports: &[Transport], psk: Option<PreSharedKey>, listen: bool) -> Result<SwarmController> {
+        let mut swarm = create_private_network(keypair, transports, psk)?;
+        let listeners = if listen { listen_on_defaults(&mut swarm, transports)? } else { Vec::new() };
+
+        log::info!("Starting swarm controller");
+        let (sender, receiver) = mpsc::channel(8196);
+        tokio::spawn(run_swarm(swarm, receiver, self.event_bus.clone(), self.services(), listeners));
+        log::info!("Started swarm controller");
+
+        Ok(SwarmController { sender })
+    }
+
+    fn services(&self) -> NodeServices {
+        NodeServices {
+            call_service: self.call_service.clone(),
+            invite_service: self.invite_service.clone(),
+            member_service: self.member_service.clone(),
+            group_key_service: self.group_key_service.clone(),
+            message_service: self.message_service.clone(),
+            file_service: self.file_service.clone(),
+            blob_service: self.blob_service.clone(),
+            download_dir: self.download_dir.clone(),
+        }
+    }
 
     pub async fn quit_room(&mut self, room_id: &str) -> Result<()> {
         log::info!("Quitting room {}", room_id);
         // Quit the room.
-        let _room = self.room_service.get_room(&room_id)
-            .expect("Room not found");
+        self.room_service.get_room(room_id)?;
 
         log::info!("Stopping swarm for room {}", room_id);
-        if let Some(controller) = self.room_swarm_controller.get_mut(&room_id.to_string()) {
-            controller.stop().await;
+        if let Some(controller) = self.room_swarm_controller.remove(room_id) {
+            if let Err(e) = controller.leave(room_id.to_string()).await {
+                log::warn!("Could not leave room {}: {}", room_id, e);
+            }
+            // The shared swarm outlives its rooms.
+            if !self.is_shared(&controller) {
+                controller.stop().await;
+            }
         } else {
             log::info!("Swarm for room {} not found", room_id);
         }
 
-        log::info!("Stopping swarm for room {}", room_id);
-        if let Some(mutex) = self.room_swarms.get_mut(&room_id.to_string()) {
-            let swarm = mutex.lock().await;
-            drop(swarm);
-        } else {
-            log::info!("Swarm for room {} not found", room_id);
+        log::info!("Quit room {}", room_id);
+        Ok(())
+    }
+
+    pub async fn send_message(&self, data: SendMessageData) -> Result<MessagePayload> {
+        log::info!("Sending message to room {}", data.room_id);
+        let message = self.controller(&data.room_id)?.publish(data.room_id.clone(), data.topic, data.payload, None).await?;
+
+        log::info!("Sent message {} to room {}", message.message_id, data.room_id);
+        Ok(message)
+    }
+
+    /// Hashes the file into the blob store and offers it to the room in a message; this node serves it from there.
+    pub async fn offer_file(&self, data: OfferFileData) -> Result<MessagePayload> {
+        log::info!("Offering {} in room {}", data.path, data.room_id);
+        let controller = self.controller(&data.room_id)?;
+        let path = std::fs::canonicalize(&data.path)?;
+
+        let (hashed, blobs) = (path.clone(), self.blob_service.clone());
+        let (offer, manifest) = tokio::task::spawn_blocking(move || {
+            let (offer, manifest) = hash_file(&hashed, CHUNK_SIZE)?;
+            blobs.import(&hashed, &offer.file_id)?;
+            Ok::<_, Error>((offer, manifest.concat()))
+        }).await.map_err(|e| ErrorKind::TransferFailed(e.to_string()))??;
+        let file = SharedFile::offered(
+            &data.room_id,
+            &offer,
+            &self.local_identity(&data.room_id)?.to_string(),
+            Some(path.to_string_lossy().to_string()),
+            now_millis(),
+        );
+        // A file offered before keeps its row, whatever happens to this offer.
+        let offered_before = self.file_service.get_file(&data.room_id, &file.file_id).is_ok();
+        self.file_service.add_file(&file)?;
+        // Members ask for the manifest before any chunk, it is worked out once and kept.
+        self.file_service.set_manifest(&data.room_id, &file.file_id, &manifest)?;
+
+        // Publishing stores the message, and with it the blob reference; the blob itself goes with the next collection.
+        let message = match controller.publish(data.room_id.clone(), None, offer.name.clone(), Some(offer)).await {
+            Ok(message) => message,
+            Err(e) => {
+                if !offered_before {
+                    if let Err(e) = self.file_service.delete_file(&data.room_id, &file.file_id) {
+                        log::warn!("Could not forget the unsent offer of file {}: {}", file.file_id, e.chain_message());
+                    }
+                }
+                return Err(e);
+            }
+        };
+
+        log::info!("Offered file {} in room {}", file.file_id, data.room_id);
+        Ok(message)
+    }
+
+    /// Starts pulling an offered file; `file` events report how it goes.
+    ///
+    /// A file the blob store already holds, shared in another room, completes without the network.
+    pub async fn download_file(&self, data: DownloadFileData) -> Result<FilePayload> {
+        log::info!("Downloading file {} of room {}", data.file_id, data.room_id);
+        let file = self.file_service.get_file(&data.room_id, &data.file_id)?;
+        if file.path.as_deref().is_some_and(|path| std::path::Path::new(path).exists()) {
+            return Err(ErrorKind::InvalidArgument(format!("file {} is already on this device", data.file_id)).into());
         }
 
-        log::info!("Removing swarm for room {}", room_id);
-        self.room_swarms.remove(&room_id.to_string());
+        if self.blob_service.held(&file.file_id)?.is_some() {
+            let (blobs, dir, blob, name) = (self.blob_service.clone(), self.download_dir.clone(), file.file_id.clone(), file.name.clone());
+            let path = tokio::task::spawn_blocking(move || blobs.export(&blob, &dir, &name))
+                .await
+                .map_err(|e| ErrorKind::TransferFailed(e.to_string()))??
+                .to_string_lossy()
+                .to_string();
+            self.file_service.set_path(&file.room_id, &file.file_id, Some(&path))?;
+
+            let payload = FilePayload::from((
+                file.room_id.clone(), file.file_id.clone(), file.name.clone(), "completed".to_string(), file.size, file.size, Some(path), None,
+            ));
+            self.event_bus.emit(CallbackPayload::File(payload.clone()));
+            return Ok(payload);
+        }
 
-        log::info!("Quit room {}", room_id);
+        let holder = match data.peer_id {
+            Some(peer) if !self.member_service.is_member(&data.room_id, &peer)? => {
+                return Err(ErrorKind::InvalidArgument(format!("{peer} is not a member of room {}", data.room_id)).into());
+            }
+            Some(peer) => peer,
+            None => file.offered_by.clone(),
+        };
+        self.controller(&data.room_id)?.download(data.room_id.clone(), file.offer(), holder.parse()?).await
+    }
+
+    pub async fn get_files(&self, room_id: &str) -> Result<Vec<SharedFile>> {
+        log::info!("Getting files of room {}", room_id);
+        self.room_service.get_room(room_id)?;
+        self.file_service.get_files(room_id)
+    }
+
+    /// A page of the room's history, oldest first.
+    pub async fn get_messages(&self, room_id: &str, query: MessageQuery) -> Result<Vec<Message>> {
+        log::info!("Getting messages of room {}", room_id);
+        self.room_service.get_room(room_id)?;
+        self.message_service.get_messages(room_id, query.before, query.limit)
+    }
+
+    /// Hands the signal to the room's swarm; the returned future waits for its delivery without the SDK,
+    /// as a signal can stay queued for a while.
+    pub fn send_signal(&self, data: SendSignalData) -> Result<impl Future<Output = Result<()>>> {
+        log::info!("Sending signal to {} in room {}", data.to_peer, data.room_id);
+        let controller = self.controller(&data.room_id)?.clone();
+        let to_peer: PeerId = data.to_peer.parse()?;
+
+        Ok(async move {
+            controller.send_signal(data.room_id.clone(), to_peer, data.signal).await?;
+
+            log::info!("Sent signal to {} in room {}", to_peer, data.room_id);
+            Ok(())
+        })
+    }
+
+    pub async fn start_call(&self, data: StartCallData) -> Result<CallPayload> {
+        log::info!("Starting call in room {}", data.room_id);
+        let call = self.controller(&data.room_id)?.call(data.room_id.clone(), CallCommand::Start).await?;
+
+        log::info!("Started call {} in room {}", call.call_id, data.room_id);
+        Ok(call)
+    }
+
+    pub async fn accept_call(&self, data: CallId) -> Result<CallPayload> {
+        log::info!("Accepting call {}", data.call_id);
+        self.controller(&data.room_id)?.call(data.room_id.clone(), CallCommand::Accept(data.call_id)).await
+    }
+
+    pub async fn reject_call(&self, data: CallId) -> Result<CallPayload> {
+        log::info!("Rejecting call {}", data.call_id);
+        self.controller(&data.room_id)?.call(data.room_id.clone(), CallCommand::Reject(data.call_id)).await
+    }
+
+    pub async fn hang_up(&self, data: CallId) -> Result<CallPayload> {
+        log::info!("Hanging up call {}", data.call_id);
+        self.controller(&data.room_id)?.call(data.room_id.clone(), CallCommand::HangUp(data.call_id)).await
+    }
+
+    pub async fn get_listen_addresses(&self, room_id: &str) -> Result<Vec<String>> {
+        log::info!("Getting listen addresses of room {}", room_id);
+        self.controller(room_id)?.listen_addresses().await
+    }
+
+    pub async fn get_network_status(&self, room_id: &str) -> Result<NetworkStatus> {
+        log::info!("Getting network status of room {}", room_id);
+        self.controller(room_id)?.network_status(room_id.to_string()).await
+    }
+
+    /// Dials `address` from the room's swarm; the returned future resolves with the peer that answered,
+    /// without holding the SDK until the connection is up.
+    pub fn dial_peer(&self, data: AddressData) -> Result<impl Future<Output = Result<String>>> {
+        log::info!("Dialing {} from room {}", data.address, data.room_id);
+        let controller = self.controller(&data.room_id)?.clone();
+        let address: Multiaddr = data.address.parse()?;
+
+        Ok(async move {
+            let peer_id = controller.dial(address).await?;
+
+            log::info!("Connected to {} from room {}", peer_id, data.room_id);
+            Ok(peer_id.to_string())
+        })
+    }
+
+    pub async fn disconnect_peer(&self, data: PeerData) -> Result<()> {
+        log::info!("Disconnecting {} from room {}", data.peer_id, data.room_id);
+        self.controller(&data.room_id)?.disconnect(data.room_id.clone(), data.peer_id.parse()?).await
+    }
+
+    pub async fn get_peers(&self, room_id: &str) -> Result<Vec<PeerInfo>> {
+        log::info!("Getting peers of room {}", room_id);
+        self.controller(room_id)?.peers(room_id.to_string()).await
+    }
+
+    pub async fn subscribe(&self, data: TopicData) -> Result<()> {
+        log::info!("Subscribing room {} to {}", data.room_id, data.topic);
+        self.controller(&data.room_id)?.subscribe(data.room_id.clone(), data.topic).await
+    }
+
+    pub async fn unsubscribe(&self, data: TopicData) -> Result<()> {
+        log::info!("Unsubscribing room {} from {}", data.room_id, data.topic);
+        self.controller(&data.room_id)?.unsubscribe(data.room_id.clone(), data.topic).await
+    }
+
+    pub async fn add_external_address(&self, data: AddressData) -> Result<()> {
+        log::info!("Adding external address {} to room {}", data.address, data.room_id);
+        self.controller(&data.room_id)?.add_external_address(data.address.parse()?).await
+    }
+
+    /// A signed invite link to the running room, pointing at its current addresses.
+    pub async fn create_invite(&self, room_id: &str, options: InviteOptions) -> Result<String> {
+        log::info!("Creating an invite to room {}", room_id);
+        let room = self.room_service.get_room(room_id)?;
+        // Whoever redeems the invite becomes a member, which only owners can grant.
+        self.owner_identity(room_id)?;
+        let keypair = self.noise_key_service.get_key(room_id)?;
+        let psk = self.noise_key_service.get_psk(room_id)?;
+        let shared = self.controller(room_id)?.share_room(room_id.to_string()).await?;
+
+        let max_uses = options.max_uses
+            .map(i32::try_from)
+            .transpose()
+            .map_err(|_| ErrorKind::InvalidArgument("max_uses is too large".to_string()))?;
+        let invite = self.invite_service.create_invite(room_id, options.expires, max_uses)?;
+
+        let invitation = Invitation::from((
+            invite.id,
+            room.id,
+            room.name,
+            shared.peer_id.to_string(),
+            shared.addresses,
+            shared.rendezvous_nodes,
+            psk.as_ref().map(Invitation::encode_secret),
+            options.expires,
+        ));
+
+        log::info!("Created an invite to room {}", room_id);
+        invitation.encode(&keypair)
+    }
+
+    /// Creates the room `token` invites to and launches it, ready to redeem the invite; a room
+    /// already here is only launched.
+    ///
+    /// Redeeming goes over the network, so `accept_invite` does it without the SDK, see `PendingInvite`.
+    pub async fn prepare_invite(&mut self, token: &str) -> Result<InviteJoin> {
+        let invitation = Invitation::decode(token, now_millis())?;
+        let psk = invitation.psk()?;
+        let room_id = invitation.room_id.clone();
+        log::info!("Accepting an invite to room {}", room_id);
+
+        let data = ConnectionData {
+            room_id: room_id.clone(),
+            room_multi_address: invitation.addresses,
+            room_listen_on: vec![],
+            rendezvous_nodes: invitation.rendezvous_nodes,
+            transports: vec![],
+        };
+
+        match self.room_service.get_room(&room_id) {
+            Ok(room) => {
+                log::info!("Already a member of room {}, launching it", room_id);
+                self.start_room(data).await?;
+                return Ok(InviteJoin::Member(room));
+            }
+            Err(Error(ErrorKind::RoomNotFound(_), _)) => {}
+            Err(e) => return Err(e),
+        }
+
+        let inviter = invitation.inviter.parse()?;
+        // Without the room's key the inviter would not even complete the handshake.
+        let room = self.store_room(room_id.clone(), invitation.room_name, psk)?;
+
+        let launched: Result<PendingInvite> = async {
+            let keypair = self.noise_key_service.get_key(&room_id)?;
+            let request = InviteRequest::from((
+                room_id.clone(),
+                invitation.invite_id,
+                keypair.public().to_peer_id().to_string(),
+                keypair.public().encode_protobuf(),
+            ));
+            self.start_room(data).await?;
+            let controller = self.controller(&room_id)?.clone();
+            Ok(PendingInvite { room: room.clone(), keypair, inviter, request, controller })
+        }.await;
+
+        match launched {
+            Ok(pending) => Ok(InviteJoin::Pending(Box::new(pending))),
+            Err(e) => Err(self.forget_joined_room(&room_id, e).await),
+        }
+    }
+
+    /// Keeps what the inviter answered: the room's members and its group key.
+    pub async fn finish_invite(&mut self, pending: PendingInvite, response: Result<InviteResponse>) -> Result<Room> {
+        let room_id = pending.room.id.clone();
+        let joined: Result<()> = (|| {
+            let response = response?;
+            for member in &response.members {
+                self.member_service.add_member(member)?;
+            }
+            // Without it the room's messages stay sealed until a member hands the key out.
+            if let Some(sealed) = response.group_key {
+                let key = sealed.open(&pending.keypair, &room_id)?;
+                self.group_key_service.add_key(&room_id, sealed.epoch, &key)?;
+            }
+            Ok(())
+        })();
+
+        if let Err(e) = joined {
+            return Err(self.forget_joined_room(&room_id, e).await);
+        }
+
+        log::info!("Accepted the invite to room {}", room_id);
+        Ok(pending.room)
+    }
+
+    /// Undoes a join that failed halfway, and hands the failure back.
+    async fn forget_joined_room(&mut self, room_id: &str, e: Error) -> Error {
+        log::warn!("Could not join room {}, forgetting it: {}", room_id, e.chain_message());
+        if let Err(e) = self.quit_room(room_id).await {
+            log::warn!("Could not quit room {}: {}", room_id, e);
+        }
+        if let Err(e) = self.remove_room(room_id).await {
+            log::warn!("Could not remove room {}: {}", room_id, e);
+        }
+        e
+    }
+
+    /// Lets `peer_id` publish in the room and tells the online members about it.
+    pub async fn add_member(&self, data: MemberData) -> Result<Member> {
+        log::info!("Adding {} to room {}", data.peer_id, data.room_id);
+        let owner = self.owner_identity(&data.room_id)?;
+        let peer_id: PeerId = data.peer_id.parse()?;
+        let controller = self.controller(&data.room_id)?;
+
+        let member = Member::from((
+            data.room_id.clone(),
+            peer_id.to_string(),
+            data.role.as_str().to_string(),
+            owner.to_string(),
+            now_millis(),
+            None,
+        ));
+        self.member_service.add_member(&member)?;
+        self.event_bus.emit(CallbackPayload::Member(MemberPayload::added(&member.room_id, &member.peer_id, data.role, &member.added_by)));
+
+        let control = MemberControl::Added { peer_id: member.peer_id.clone(), role: data.role, added_at: member.added_at, public_key: None };
+        if let Err(e) = controller.announce(data.room_id.clone(), RoomControl::Member(control)).await {
+            log::warn!("Could not tell room {} about its new member: {}", data.room_id, e.chain_message());
+        }
+
+        log::info!("Added {} to room {}", member.peer_id, data.room_id);
+        Ok(member)
+    }
+
+    pub async fn remove_member(&self, data: PeerData) -> Result<()> {
+        log::info!("Removing {} from room {}", data.peer_id, data.room_id);
+        let owner = self.owner_identity(&data.room_id)?;
+        if data.peer_id == owner.to_string() {
+            return Err(ErrorKind::InvalidArgument("an owner cannot remove itself, remove the room instead".to_string()).into());
+        }
+        let controller = self.controller(&data.room_id)?;
+
+        if !self.member_service.remove_member(&data.room_id, &data.peer_id)? {
+            return Err(ErrorKind::InvalidArgument(format!("{} is not a member of room {}", data.peer_id, data.room_id)).into());
+        }
+        self.event_bus.emit(CallbackPayload::Member(MemberPayload::removed(&data.room_id, &data.peer_id, &owner.to_string())));
+
+        let control = MemberControl::Removed { peer_id: data.peer_id.clone() };
+        if let Err(e) = controller.announce(data.room_id.clone(), RoomControl::Member(control)).await {
+            log::warn!("Could not tell room {} about the removal of {}: {}", data.room_id, data.peer_id, e.chain_message());
+        }
+        self.rotate_group_key(&data.room_id, &owner, controller).await?;
+
+        log::info!("Removed {} from room {}", data.peer_id, data.room_id);
         Ok(())
     }
 
-    pub async fn register_listener(&mut self, cb: Root<JsFunction>) {
+    /// Starts a new epoch sealed to the remaining members, so a removed member cannot read what follows.
+    async fn rotate_group_key(&self, room_id: &str, owner: &PeerId, controller: &SwarmController) -> Result<()> {
+        let (epoch, key) = self.group_key_service.create_epoch(room_id)?;
+
+        // Members whose public key is not known yet ask for the key once they see the new epoch.
+        let keys = self.member_service.get_members(room_id)?
+            .iter()
+            .filter(|member| member.peer_id != owner.to_string())
+            .filter_map(|member| member.public_key.as_deref())
+            .map(|public_key| SealedKey::seal(&identity::PublicKey::try_decode_protobuf(public_key)?, room_id, epoch, &key))
+            .collect::<Result<Vec<_>>>()?;
+
+        if let Err(e) = controller.announce(room_id.to_string(), RoomControl::Key(GroupKeyControl { epoch, keys })).await {
+            log::warn!("Could not hand out group key epoch {} of room {}: {}", epoch, room_id, e.chain_message());
+        }
+        Ok(())
+    }
+
+    pub async fn get_members(&self, room_id: &str) -> Result<Vec<Member>> {
+        log::info!("Getting members of room {}", room_id);
+        self.room_service.get_room(room_id)?;
+        self.member_service.get_members(room_id)
+    }
+
+    fn controller(&self, room_id: &str) -> Result<&SwarmController> {
+        self.room_swarm_controller.get(room_id)
+            .ok_or_else(|| Error::from(ErrorKind::RoomNotRunning(room_id.to_string())))
+    }
+
+    pub async fn register_listener(&self, channel: Channel, cb: Root<JsFunction>) -> String {
         log::info!("Registering listener");
-        self.callbacks.push(cb);
+        self.event_bus.register(channel, cb)
+    }
+
+    pub async fn unregister_listener(&self, listener_id: &str) -> bool {
+        log::info!("Unregistering listener {}", listener_id);
+        self.event_bus.unregister(listener_id)
     }
 
     pub async fn clean_up(&self) -> Result<()> {
@@ -150,10 +855,25 @@ impl RustSDK {
     pub async fn remove_room(&self, room_id: &str) -> Result<()> {
         log::info!("Removing room {}", room_id);
         // Delete the room entity from the database using the RoomService.
-        self.room_service.delete_room(&room_id)?;
+        self.room_service.delete_room(room_id)?;
 
         // Delete the associated noise keys using the NoiseKeyService.
-        self.noise_key_service.delete_key(&room_id)?;
+        self.noise_key_service.delete_key(room_id)?;
+
+        // Forget the call history of the room.
+        self.call_service.delete_room_calls(room_id)?;
+
+        // Invites to a room we left cannot be redeemed anymore.
+        self.invite_service.delete_room_invites(room_id)?;
+
+        self.member_service.delete_room_members(room_id)?;
+        self.group_key_service.delete_room_keys(room_id)?;
+        self.message_service.delete_room_messages(room_id)?;
+        self.file_service.delete_room_files(room_id)?;
+        // Blobs other rooms still offer stay.
+        if let Err(e) = self.blob_service.collect_garbage() {
+            log::error!("Failed to collect unreferenced blobs: {}", e);
+        }
 
         log::info!("Removed room {}", room_id);
         Ok(())
@@ -171,10 +891,33 @@ impl RustSDK {
     pub async fn get_room(&self, room_id: &str) -> Result<Room> {
         log::info!("Getting room {}", room_id);
         // Use the RoomService to fetch a room from the database.
-        let room = self.room_service.get_room(&room_id)?;
+        let room = self.room_service.get_room(room_id)?;
 
         log::info!("Got room {}", room_id);
         Ok(room)
     }
 }
 
+
+/// What `prepare_invite` left to do.
+pub enum InviteJoin {
+    /// The room was here already, and is running now.
+    Member(Room),
+    Pending(Box<PendingInvite>),
+}
+
+/// A launched room whose invite is still to be redeemed with the inviter.
+pub struct PendingInvite {
+    room: Room,
+    keypair: identity::Keypair,
+    inviter: PeerId,
+    request: InviteRequest,
+    controller: SwarmController,
+}
+
+impl PendingInvite {
+    /// Dials the inviter and waits for its answer; needs nothing but the room's swarm.
+    pub async fn redeem(&self) -> Result<InviteResponse> {
+        self.controller.redeem_invite(self.inviter, self.request.clone()).await
+    }
+}
@@ -1,27 +1,96 @@
 use neon::prelude::*;
+use neon::types::Deferred;
 use neon_serde3::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use tokio::sync::Mutex;
 
+use crate::models::backup::BackupData;
+use crate::models::call_data::{CallId, StartCallData};
 use crate::models::connection_data::ConnectionData;
+use crate::models::error::*;
+use crate::models::file_transfer::{DownloadFileData, OfferFileData};
+use crate::models::invitation::InviteOptions;
+use crate::models::listener_id::ListenerId;
+use crate::models::master_key::UnlockData;
+use crate::models::member::MemberData;
+use crate::models::message::MessageQuery;
+use crate::models::peer_data::{AddressData, PeerData, TopicData};
+use crate::models::rekey_data::RekeyData;
 use crate::models::room_id::RoomId;
 use crate::models::room_option::RoomOption;
 use crate::models::rust_sdk_options::RustSDKOptions;
-use crate::services::sdk::RustSDK;
+use crate::models::send_message_data::SendMessageData;
+use crate::models::signal::SendSignalData;
+use crate::services::sdk::{InviteJoin, RustSDK};
 use crate::services::state::{CONFIG, get_sdk, rt};
 
+/// Turns the value a task produced into what its promise resolves with.
+type Resolver<T> = for<'a> fn(&mut TaskContext<'a>, T) -> JsResult<'a, JsValue>;
+
+fn to_js<'a, T: Serialize>(cx: &mut TaskContext<'a>, value: T) -> JsResult<'a, JsValue> {
+    to_value(cx, &value).or_else(|e| cx.throw_error(e.to_string()))
+}
+
+fn to_undefined<'a>(cx: &mut TaskContext<'a>, _: ()) -> JsResult<'a, JsValue> {
+    Ok(cx.undefined().upcast())
+}
+
+/// Deserializes argument `index`; a missing or malformed one is an `INVALID_ARGUMENT` error.
+fn argument<T: DeserializeOwned>(cx: &mut FunctionContext, index: i32) -> Result<T> {
+    let value = cx.argument_opt(index)
+        .ok_or_else(|| ErrorKind::InvalidArgument(format!("argument {} is missing", index)))?;
+
+    from_value(cx, value).map_err(|e| ErrorKind::InvalidArgument(e.to_string()).into())
+}
+
+/// Like `argument`, but a missing, `undefined` or `null` argument is the default value.
+fn optional_argument<T: DeserializeOwned + Default>(cx: &mut FunctionContext, index: i32) -> Result<T> {
+    match cx.argument_opt(index) {
+        Some(value) if !value.is_a::<JsUndefined, _>(cx) && !value.is_a::<JsNull, _>(cx) => argument(cx, index),
+        _ => Ok(T::default()),
+    }
+}
+
+/// A JS `Error` whose `code` is stable and whose message carries the whole error chain.
+fn js_error<'a>(cx: &mut TaskContext<'a>, error: &Error) -> JsResult<'a, JsError> {
+    let js_error = cx.error(error.chain_message())?;
+    let code = cx.string(error.code());
+    js_error.set(cx, "code", code)?;
+    Ok(js_error)
+}
+
+/// Resolves the promise with `result`, or rejects it with a coded `Error`.
+fn settle<T: Send + 'static>(def: Deferred, channel: &Channel, action: &'static str, result: Result<T>, resolve: Resolver<T>) {
+    if let Err(e) = &result {
+        log::error!("Failed to {}: {}", action, e.chain_message());
+    }
+
+    def.settle_with(channel, move |mut cx| match result {
+        Ok(value) => resolve(&mut cx, value),
+        Err(e) => {
+            let error = js_error(&mut cx, &e)?;
+            cx.throw(error)
+        }
+    });
+}
+
 pub(crate) fn stop_sdk(mut cx: FunctionContext) -> JsResult<JsPromise> {
-    let clean_up = cx.argument::<JsBoolean>(0)?.value(&mut cx);
     let (def, prom) = cx.promise();
     let channel = cx.channel();
+    let clean_up: Result<bool> = argument(&mut cx, 0);
 
     rt().spawn(async move {
-        let sdk = get_sdk().await;
-        if clean_up {
-            sdk.clean_up().await.unwrap();
-        }
-        def.settle_with(&channel, move |mut cx| {
-            Ok(cx.undefined())
-        })
+        let result = async {
+            let clean_up = clean_up?;
+            let sdk = get_sdk().await?;
+            if clean_up {
+                sdk.clean_up().await?;
+            }
+            Ok(())
+        }.await;
+
+        settle(def, &channel, "stop SDK", result, to_undefined);
     });
 
     Ok(prom)
@@ -29,84 +98,118 @@ pub(crate) fn stop_sdk(mut cx: FunctionContext) -> JsResult<JsPromise> {
 
 pub(crate) fn start_sdk(mut cx: FunctionContext) -> JsResult<JsPromise> {
     log::info!("Starting SDK");
-    let arg0 = cx.argument::<JsValue>(0)?;
     let channel = cx.channel();
     let (def, prom) = cx.promise();
+    let options: Result<RustSDKOptions> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = options.and_then(|options| {
+            if CONFIG.try_get().is_none() {
+                log::info!("Starting SDK");
+                let sdk = RustSDK::new(options)?;
+                CONFIG.set(Mutex::new(sdk));
+                log::info!("SDK started");
+            }
+            Ok(())
+        });
 
-    let options: RustSDKOptions = from_value(&mut cx, arg0)
-        .or_else(|e| cx.throw_error(e.to_string()))
-        .unwrap();
+        settle(def, &channel, "start SDK", result, to_undefined);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn unlock_sdk(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Unlocking SDK");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<UnlockData> = argument(&mut cx, 0);
 
     rt().spawn(async move {
-        CONFIG.get_or_init(|| {
-            log::info!("Starting SDK");
+        let result = async { get_sdk().await?.unlock(data?).await }.await;
+        settle(def, &channel, "unlock SDK", result, to_undefined);
+    });
 
-            let sdk = RustSDK::new(options);
+    Ok(prom)
+}
 
-            log::info!("SDK started");
-            Mutex::new(sdk)
-        });
+pub(crate) fn lock_sdk(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Locking SDK");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
 
-        def.settle_with(&channel, move |mut cx| {
-            Ok(cx.undefined())
-        })
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.lock().await }.await;
+        settle(def, &channel, "lock SDK", result, to_undefined);
     });
 
     Ok(prom)
 }
 
-pub(crate) fn create_room(mut cx: FunctionContext) -> JsResult<JsPromise> {
-    log::info!("Creating room");
+pub(crate) fn rekey_database(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Rekeying database");
     let (def, prom) = cx.promise();
     let channel = cx.channel();
-    let arg0 = cx.argument::<JsValue>(0)?;
+    let data: Result<RekeyData> = argument(&mut cx, 0);
 
-    let room_option: RoomOption = from_value(&mut cx, arg0)
-        .or_else(|e| cx.throw_error(e.to_string()))
-        .unwrap();
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.rekey_database(data?).await }.await;
+        settle(def, &channel, "rekey database", result, to_undefined);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn export_backup(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Exporting backup");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<BackupData> = argument(&mut cx, 0);
 
     rt().spawn(async move {
-        let sdk = get_sdk().await;
-        let value = sdk.create_room(room_option).await.unwrap_or_else(|e| {
-            log::error!("Failed to create room: {}", e);
-            panic!("Failed to create room: {}", e);
-        });
+        let result = async { get_sdk().await?.export_backup(data?).await }.await;
+        settle(def, &channel, "export backup", result, to_undefined);
+    });
 
-        log::info!("Room created");
+    Ok(prom)
+}
 
-        def.settle_with(&channel, move |mut cx| {
-            let js_value = to_value(&mut cx, &value)
-                .or_else(|e| cx.throw_error(e.to_string()))
-                .unwrap();
+pub(crate) fn import_backup(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Importing backup");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<BackupData> = argument(&mut cx, 0);
 
-            Ok(js_value)
-        })
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.import_backup(data?).await }.await;
+        settle(def, &channel, "import backup", result, to_js);
     });
 
     Ok(prom)
 }
 
-pub(crate) fn get_rooms(mut cx: FunctionContext) -> JsResult<JsPromise> {
-    log::info!("Getting rooms");
+pub(crate) fn create_room(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Creating room");
     let (def, prom) = cx.promise();
     let channel = cx.channel();
+    let room_option: Result<RoomOption> = argument(&mut cx, 0);
 
     rt().spawn(async move {
-        let sdk = get_sdk().await;
-        let value = sdk.get_rooms().await.unwrap_or_else(|e| {
-            log::error!("Failed to get rooms: {}", e);
-            panic!("Failed to get rooms: {}", e);
-        });
+        let result = async { get_sdk().await?.create_room(room_option?).await }.await;
+        settle(def, &channel, "create room", result, to_js);
+    });
 
-        log::info!("Rooms retrieved");
+    Ok(prom)
+}
 
-        def.settle_with(&channel, move |mut cx| {
-            let js_value = to_value(&mut cx, &value)
-                .or_else(|e| cx.throw_error(e.to_string()))
-                .unwrap();
+pub(crate) fn get_rooms(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Getting rooms");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
 
-            Ok(js_value)
-        })
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.get_rooms().await }.await;
+        settle(def, &channel, "get rooms", result, to_js);
     });
 
     Ok(prom)
@@ -116,102 +219,198 @@ pub(crate) fn get_room(mut cx: FunctionContext) -> JsResult<JsPromise> {
     log::info!("Getting room");
     let (def, prom) = cx.promise();
     let channel = cx.channel();
-    let arg0 = cx.argument::<JsValue>(0)?;
+    let room_id: Result<RoomId> = argument(&mut cx, 0);
 
-    let room_id: RoomId = from_value(&mut cx, arg0)
-        .or_else(|e| cx.throw_error(e.to_string()))
-        .unwrap();
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.get_room(&room_id?.id).await }.await;
+        settle(def, &channel, "get room", result, to_js);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn remove_room(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Remove room");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let room_id: Result<RoomId> = argument(&mut cx, 0);
 
     rt().spawn(async move {
-        let sdk = get_sdk().await;
-        let value = sdk.get_room(room_id.id.as_str()).await.unwrap_or_else(|e| {
-            log::error!("Failed to get room: {}", e);
-            panic!("Failed to get room: {}", e);
-        });
+        let result = async { get_sdk().await?.remove_room(&room_id?.id).await }.await;
+        settle(def, &channel, "remove room", result, to_undefined);
+    });
 
-        log::info!("Room created");
+    Ok(prom)
+}
 
+pub(crate) fn launch_room(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Launching room");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<ConnectionData> = argument(&mut cx, 0);
 
-        def.settle_with(&channel, move |mut cx| {
-            let js_value = to_value(&mut cx, &value)
-                .or_else(|e| cx.throw_error(e.to_string()))
-                .unwrap();
-            Ok(js_value)
-        })
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.start_room(data?).await }.await;
+        settle(def, &channel, "launch room", result, to_js);
     });
 
     Ok(prom)
 }
 
-pub(crate) fn remove_room(mut cx: FunctionContext) -> JsResult<JsPromise> {
-    log::info!("Remove room");
+pub(crate) fn quit_room(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Quitting room");
     let (def, prom) = cx.promise();
     let channel = cx.channel();
-    let arg0 = cx.argument::<JsValue>(0)?;
+    let room_id: Result<RoomId> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.quit_room(&room_id?.id).await }.await;
+        settle(def, &channel, "quit room", result, to_undefined);
+    });
 
-    let room_id: RoomId = from_value(&mut cx, arg0)
-        .or_else(|e| cx.throw_error(e.to_string()))
-        .unwrap();
+    Ok(prom)
+}
+
+pub(crate) fn send_message(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Sending message");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<SendMessageData> = argument(&mut cx, 0);
 
     rt().spawn(async move {
-        let sdk = get_sdk().await;
-        sdk.remove_room(room_id.id.as_str()).await.unwrap();
+        let result = async { get_sdk().await?.send_message(data?).await }.await;
+        settle(def, &channel, "send message", result, to_js);
+    });
+
+    Ok(prom)
+}
 
-        log::info!("Room removed");
+pub(crate) fn get_messages(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Getting messages");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let room_id: Result<RoomId> = argument(&mut cx, 0);
+    let query: Result<MessageQuery> = optional_argument(&mut cx, 1);
 
-        def.settle_with(&channel, move |mut cx| {
-            Ok(cx.undefined())
-        })
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.get_messages(&room_id?.id, query?).await }.await;
+        settle(def, &channel, "get messages", result, to_js);
     });
 
     Ok(prom)
 }
 
-pub(crate) fn launch_room(mut cx: FunctionContext) -> JsResult<JsPromise> {
-    log::info!("Launching room");
+pub(crate) fn offer_file(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Offering file");
     let (def, prom) = cx.promise();
     let channel = cx.channel();
-    let arg0 = cx.argument::<JsValue>(0)?;
+    let data: Result<OfferFileData> = argument(&mut cx, 0);
 
-    let data: ConnectionData = from_value(&mut cx, arg0)
-        .or_else(|e| cx.throw_error(e.to_string()))?;
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.offer_file(data?).await }.await;
+        settle(def, &channel, "offer file", result, to_js);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn download_file(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Downloading file");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<DownloadFileData> = argument(&mut cx, 0);
 
     rt().spawn(async move {
-        let mut sdk = get_sdk().await;
-        if let Err(e) = sdk.start_room(data).await {
-            log::error!("Failed to launch room: {}", e);
-            panic!("Failed to launch room");
-        }
+        let result = async { get_sdk().await?.download_file(data?).await }.await;
+        settle(def, &channel, "download file", result, to_js);
+    });
+
+    Ok(prom)
+}
 
-        log::info!("Room launched");
+pub(crate) fn get_files(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Getting files");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<RoomId> = argument(&mut cx, 0);
 
-        def.settle_with(&channel, move |mut cx| {
-            Ok(cx.undefined())
-        })
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.get_files(&data?.id).await }.await;
+        settle(def, &channel, "get files", result, to_js);
     });
 
     Ok(prom)
 }
 
-pub(crate) fn quit_room(mut cx: FunctionContext) -> JsResult<JsPromise> {
-    log::info!("Quitting room");
+pub(crate) fn send_signal(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Sending signal");
     let (def, prom) = cx.promise();
     let channel = cx.channel();
-    let arg0 = cx.argument::<JsValue>(0).unwrap();
-    let room_id: RoomId = from_value(&mut cx, arg0)
-        .or_else(|e| cx.throw_error(e.to_string()))
-        .unwrap();
+    let data: Result<SendSignalData> = argument(&mut cx, 0);
 
     rt().spawn(async move {
-        let mut sdk = get_sdk().await;
-        if let Err(e) = sdk.quit_room(room_id.id.as_str()).await {
-            log::error!("Failed to quit room: {}", e);
-            panic!("Failed to quit room: {}", e);
-        }
+        let result = async {
+            // Released before the signal is delivered, other calls must not wait behind it.
+            let delivery = get_sdk().await?.send_signal(data?)?;
+            delivery.await
+        }.await;
+        settle(def, &channel, "send signal", result, to_undefined);
+    });
 
-        def.settle_with(&channel, move |mut cx| {
-            Ok(cx.undefined())
-        })
+    Ok(prom)
+}
+
+pub(crate) fn start_call(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Starting call");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<StartCallData> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.start_call(data?).await }.await;
+        settle(def, &channel, "start call", result, to_js);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn accept_call(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Accepting call");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<CallId> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.accept_call(data?).await }.await;
+        settle(def, &channel, "accept call", result, to_js);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn reject_call(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Rejecting call");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<CallId> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.reject_call(data?).await }.await;
+        settle(def, &channel, "reject call", result, to_js);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn hang_up(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Hanging up call");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<CallId> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.hang_up(data?).await }.await;
+        settle(def, &channel, "hang up call", result, to_js);
     });
 
     Ok(prom)
@@ -221,18 +420,235 @@ pub(crate) fn register_listener(mut cx: FunctionContext) -> JsResult<JsPromise>
     log::info!("Registering listener");
     let (def, prom) = cx.promise();
     let channel = cx.channel();
-    let cb = cx.argument::<JsFunction>(0).unwrap();
-    let root = cb.root(&mut cx);
+    let root = cx.argument_opt(0)
+        .and_then(|cb| cb.downcast::<JsFunction, _>(&mut cx).ok())
+        .map(|cb| cb.root(&mut cx))
+        .ok_or_else(|| Error::from(ErrorKind::InvalidArgument("the listener must be a function".to_string())));
+
+    // Listeners must not keep the node process alive on their own.
+    let mut listener_channel = cx.channel();
+    listener_channel.unref(&mut cx);
+
+    rt().spawn(async move {
+        let result = async {
+            let root = root?;
+            let listener_id = get_sdk().await?.register_listener(listener_channel, root).await;
+            Ok(ListenerId::from(listener_id))
+        }.await;
+
+        settle(def, &channel, "register listener", result, to_js);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn unregister_listener(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Unregistering listener");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let listener_id: Result<ListenerId> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async {
+            Ok(get_sdk().await?.unregister_listener(&listener_id?.id).await)
+        }.await;
+
+        settle(def, &channel, "unregister listener", result, to_js);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn get_network_status(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Getting network status");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<RoomId> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.get_network_status(&data?.id).await }.await;
+        settle(def, &channel, "get network status", result, to_js);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn get_listen_addresses(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Getting listen addresses");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<RoomId> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.get_listen_addresses(&data?.id).await }.await;
+        settle(def, &channel, "get listen addresses", result, to_js);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn dial_peer(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Dialing peer");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<AddressData> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async {
+            // Released before the dial completes, other calls must not wait behind it.
+            let connection = get_sdk().await?.dial_peer(data?)?;
+            connection.await
+        }.await;
+        settle(def, &channel, "dial peer", result, to_js);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn disconnect_peer(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Disconnecting peer");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<PeerData> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.disconnect_peer(data?).await }.await;
+        settle(def, &channel, "disconnect peer", result, to_undefined);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn get_peers(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Getting peers");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<RoomId> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.get_peers(&data?.id).await }.await;
+        settle(def, &channel, "get peers", result, to_js);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn subscribe(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Subscribing to topic");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<TopicData> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.subscribe(data?).await }.await;
+        settle(def, &channel, "subscribe", result, to_undefined);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn unsubscribe(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Unsubscribing from topic");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<TopicData> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.unsubscribe(data?).await }.await;
+        settle(def, &channel, "unsubscribe", result, to_undefined);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn add_external_address(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Adding external address");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<AddressData> = argument(&mut cx, 0);
 
     rt().spawn(async move {
-        let mut sdk = get_sdk().await;
-        sdk.register_listener(root).await;
+        let result = async { get_sdk().await?.add_external_address(data?).await }.await;
+        settle(def, &channel, "add external address", result, to_undefined);
+    });
 
-        log::info!("Listener registered");
+    Ok(prom)
+}
 
-        def.settle_with(&channel, move |mut cx| {
-            Ok(cx.undefined())
-        })
+pub(crate) fn create_invite(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Creating invite");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let room_id: Result<RoomId> = argument(&mut cx, 0);
+    let options: Result<InviteOptions> = optional_argument(&mut cx, 1);
+
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.create_invite(&room_id?.id, options?).await }.await;
+        settle(def, &channel, "create invite", result, to_js);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn accept_invite(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Accepting invite");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let token: Result<String> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async {
+            let pending = match get_sdk().await?.prepare_invite(&token?).await? {
+                InviteJoin::Member(room) => return Ok(room),
+                InviteJoin::Pending(pending) => pending,
+            };
+            // The inviter may take a while to answer, other calls must not wait behind it.
+            let response = pending.redeem().await;
+            get_sdk().await?.finish_invite(*pending, response).await
+        }.await;
+        settle(def, &channel, "accept invite", result, to_js);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn add_member(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Adding member");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<MemberData> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.add_member(data?).await }.await;
+        settle(def, &channel, "add member", result, to_js);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn remove_member(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Removing member");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<PeerData> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.remove_member(data?).await }.await;
+        settle(def, &channel, "remove member", result, to_undefined);
+    });
+
+    Ok(prom)
+}
+
+pub(crate) fn get_members(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    log::info!("Getting members");
+    let (def, prom) = cx.promise();
+    let channel = cx.channel();
+    let data: Result<RoomId> = argument(&mut cx, 0);
+
+    rt().spawn(async move {
+        let result = async { get_sdk().await?.get_members(&data?.id).await }.await;
+        settle(def, &channel, "get members", result, to_js);
     });
 
     Ok(prom)
@@ -0,0 +1,141 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use libp2p::request_response::{OutboundFailure, OutboundRequestId};
+use libp2p::{PeerId, Swarm};
+use tokio::sync::oneshot;
+
+use crate::models::behaviour::AppBehaviour;
+use crate::models::error::*;
+use crate::models::signal::{SignalRequest, SignalResponse};
+
+pub const SIGNAL_PROTOCOL: &str = "/vichiz/signal/1";
+
+/// How many times a signal is sent before giving up.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// How long a signal may wait for its target peer to connect.
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub type SignalReply = oneshot::Sender<Result<()>>;
+
+struct PendingSignal {
+    request: SignalRequest,
+    attempts: u32,
+    queued_at: Instant,
+    reply: SignalReply,
+}
+
+/// Point-to-point delivery of WebRTC signals for one room.
+///
+/// Signals for peers that are not connected yet wait here until the connection
+/// comes up; failed deliveries are retried up to `MAX_ATTEMPTS` times.
+#[derive(Default)]
+pub struct SignalQueue {
+    waiting: HashMap<PeerId, VecDeque<PendingSignal>>,
+    in_flight: HashMap<OutboundRequestId, (PeerId, PendingSignal)>,
+}
+
+impl SignalQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&mut self, swarm: &mut Swarm<AppBehaviour>, peer: PeerId, request: SignalRequest, reply: SignalReply) {
+        let pending = PendingSignal { request, attempts: 0, queued_at: Instant::now(), reply };
+        self.dispatch(swarm, peer, pending);
+    }
+
+    /// Flushes everything that was waiting for `peer`.
+    pub fn on_connected(&mut self, swarm: &mut Swarm<AppBehaviour>, peer: PeerId) {
+        if let Some(queue) = self.waiting.remove(&peer) {
+            log::info!("Flushing {} queued signal(s) to {}", queue.len(), peer);
+            for pending in queue {
+                self.dispatch(swarm, peer, pending);
+            }
+        }
+    }
+
+    pub fn on_response(&mut self, request_id: OutboundRequestId, response: SignalResponse) {
+        if let Some((peer, pending)) = self.in_flight.remove(&request_id) {
+            let result = if response.accepted {
+                Ok(())
+            } else {
+                Err(ErrorKind::SignalFailed(peer.to_string(), "rejected by peer".to_string()).into())
+            };
+            let _ = pending.reply.send(result);
+        }
+    }
+
+    pub fn on_failure(&mut self, swarm: &mut Swarm<AppBehaviour>, request_id: OutboundRequestId, error: OutboundFailure) {
+        let Some((peer, pending)) = self.in_flight.remove(&request_id) else {
+            return;
+        };
+
+        if pending.attempts >= MAX_ATTEMPTS {
+            log::warn!("Giving up on signal to {} after {} attempts: {}", peer, pending.attempts, error);
+            let _ = pending.reply.send(Err(ErrorKind::SignalFailed(peer.to_string(), error.to_string()).into()));
+            return;
+        }
+
+        log::info!("Retrying signal to {} after: {}", peer, error);
+        self.dispatch(swarm, peer, pending);
+    }
+
+    /// Fails the signals that waited too long for their peer.
+    pub fn expire(&mut self, now: Instant) {
+        for (peer, queue) in self.waiting.iter_mut() {
+            while queue.front().is_some_and(|p| now.duration_since(p.queued_at) >= QUEUE_TIMEOUT) {
+                if let Some(pending) = queue.pop_front() {
+                    log::warn!("Signal to {} expired before the peer connected", peer);
+                    let reason = "peer did not connect in time".to_string();
+                    let _ = pending.reply.send(Err(ErrorKind::SignalFailed(peer.to_string(), reason).into()));
+                }
+            }
+        }
+        self.waiting.retain(|_, queue| !queue.is_empty());
+    }
+
+    fn dispatch(&mut self, swarm: &mut Swarm<AppBehaviour>, peer: PeerId, mut pending: PendingSignal) {
+        if !swarm.is_connected(&peer) {
+            log::info!("Queueing signal until {} connects", peer);
+            if let Err(e) = swarm.dial(peer) {
+                log::debug!("Could not dial {} yet: {:?}", peer, e);
+            }
+            self.waiting.entry(peer).or_default().push_back(pending);
+            return;
+        }
+
+        pending.attempts += 1;
+        let request_id = swarm.behaviour_mut().signaling_mut().send_request(&peer, pending.request.clone());
+        self.in_flight.insert(request_id, (peer, pending));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expire_fails_stale_signals() {
+        let mut queue = SignalQueue::new();
+        let peer = PeerId::random();
+        let (reply, mut response) = oneshot::channel();
+
+        let request = SignalRequest::from(("room".to_string(), serde_json::json!({ "type": "offer" })));
+        queue.waiting.entry(peer).or_default().push_back(PendingSignal {
+            request,
+            attempts: 0,
+            queued_at: Instant::now(),
+            reply,
+        });
+
+        queue.expire(Instant::now());
+        assert!(response.try_recv().is_err());
+        assert!(queue.waiting.contains_key(&peer));
+
+        queue.expire(Instant::now() + QUEUE_TIMEOUT);
+        assert!(response.try_recv().unwrap().is_err());
+        assert!(queue.waiting.is_empty());
+    }
+}
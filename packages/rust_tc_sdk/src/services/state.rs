@@ -2,6 +2,7 @@ use state::InitCell;
 use tokio::runtime::Runtime;
 use tokio::sync::{Mutex, MutexGuard};
 
+use crate::models::error::*;
 use crate::services::sdk::RustSDK;
 
 static TOKIO_RUNTIME: InitCell<Mutex<Runtime>> = InitCell::new();
@@ -13,12 +14,15 @@ pub(crate) fn rt() -> MutexGuard<'static, Runtime> {
     TOKIO_RUNTIME
         .get_or_init(|| {
             let runtime = Runtime::new().unwrap_or_else(|_| { panic!("Cannot create runtime") });
-            return Mutex::new(runtime);
+            Mutex::new(runtime)
         })
         // .clone()
         .blocking_lock()
 }
 
-pub(crate) async fn get_sdk() -> MutexGuard<'static, RustSDK> {
-    CONFIG.get().lock().await
-}
\ No newline at end of file
+pub(crate) async fn get_sdk() -> Result<MutexGuard<'static, RustSDK>> {
+    match CONFIG.try_get() {
+        Some(sdk) => Ok(sdk.lock().await),
+        None => Err(ErrorKind::SdkNotStarted.into()),
+    }
+}
@@ -1,19 +1,205 @@
-use tokio::sync::mpsc;
+use libp2p::{Multiaddr, PeerId};
+use tokio::sync::{mpsc, oneshot};
 
-#[derive(PartialEq)]
+use crate::models::callback_payload::{CallPayload, FilePayload, MessagePayload};
+use crate::models::error::*;
+use crate::models::file_transfer::FileOffer;
+use crate::models::network_status::NetworkStatus;
+use crate::models::peer_data::PeerInfo;
+use crate::models::invitation::{InviteRequest, InviteResponse};
+use crate::models::room_control::RoomControl;
+use crate::services::network::{RoomAddresses, RoomLaunch};
+
+/// Everything the swarm loop can be asked to do; each command carries the sender its reply goes to.
 pub enum ControlMessage {
-    Stop,
-    // Add more control commands if needed.
+    Stop {
+        reply: oneshot::Sender<()>,
+    },
+    JoinRoom {
+        room: Box<RoomLaunch>,
+        reply: oneshot::Sender<Result<Vec<String>>>,
+    },
+    LeaveRoom {
+        room_id: String,
+        reply: oneshot::Sender<()>,
+    },
+    Publish {
+        room_id: String,
+        topic: Option<String>,
+        payload: String,
+        file: Option<FileOffer>,
+        reply: oneshot::Sender<Result<MessagePayload>>,
+    },
+    SendSignal {
+        room_id: String,
+        to_peer: PeerId,
+        signal: serde_json::Value,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    Call {
+        room_id: String,
+        command: CallCommand,
+        reply: oneshot::Sender<Result<CallPayload>>,
+    },
+    ListListenAddrs {
+        reply: oneshot::Sender<Vec<String>>,
+    },
+    NetworkStatus {
+        room_id: String,
+        reply: oneshot::Sender<NetworkStatus>,
+    },
+    /// Resolves with the peer ID once the connection is up.
+    Dial {
+        address: Multiaddr,
+        reply: oneshot::Sender<Result<PeerId>>,
+    },
+    Disconnect {
+        room_id: String,
+        peer_id: PeerId,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    ListPeers {
+        room_id: String,
+        reply: oneshot::Sender<Result<Vec<PeerInfo>>>,
+    },
+    Subscribe {
+        room_id: String,
+        topic: String,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    Unsubscribe {
+        room_id: String,
+        topic: String,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    AddExternalAddress {
+        address: Multiaddr,
+        reply: oneshot::Sender<()>,
+    },
+    ShareRoom {
+        room_id: String,
+        reply: oneshot::Sender<Result<RoomAddresses>>,
+    },
+    /// Resolves with the room's members and group key once the inviter counted the use of the invite.
+    RedeemInvite {
+        inviter: PeerId,
+        request: InviteRequest,
+        reply: oneshot::Sender<Result<InviteResponse>>,
+    },
+    /// Publishes a control message, signed with the room's identity, to the room's members.
+    Announce {
+        room_id: String,
+        control: RoomControl,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    /// Starts pulling a file from a member holding it; progress is reported as events.
+    Download {
+        room_id: String,
+        offer: FileOffer,
+        /// The room identity of the member to pull from.
+        holder: PeerId,
+        reply: oneshot::Sender<Result<FilePayload>>,
+    },
+}
+
+pub enum CallCommand {
+    Start,
+    Accept(String),
+    Reject(String),
+    HangUp(String),
 }
 
+/// Talks to a swarm loop; rooms sharing a swarm share clones of its controller.
+#[derive(Clone)]
 pub struct SwarmController {
     pub(crate) sender: mpsc::Sender<ControlMessage>,
 }
 
 impl SwarmController {
+    /// Stops the swarm loop and waits until the swarm is dropped.
     pub async fn stop(&self) {
-        if let Err(e) = self.sender.send(ControlMessage::Stop).await {
+        if let Err(e) = self.request(|reply| ControlMessage::Stop { reply }).await {
             log::error!("Swarm controller error:: {:?}", e);
         }
     }
+
+    /// Runs the room on this swarm and resolves with the addresses the swarm listens on.
+    pub async fn join(&self, room: RoomLaunch) -> Result<Vec<String>> {
+        self.request(|reply| ControlMessage::JoinRoom { room: Box::new(room), reply }).await?
+    }
+
+    pub async fn leave(&self, room_id: String) -> Result<()> {
+        self.request(|reply| ControlMessage::LeaveRoom { room_id, reply }).await
+    }
+
+    pub async fn publish(&self, room_id: String, topic: Option<String>, payload: String, file: Option<FileOffer>) -> Result<MessagePayload> {
+        self.request(|reply| ControlMessage::Publish { room_id, topic, payload, file, reply }).await?
+    }
+
+    pub async fn send_signal(&self, room_id: String, to_peer: PeerId, signal: serde_json::Value) -> Result<()> {
+        self.request(|reply| ControlMessage::SendSignal { room_id, to_peer, signal, reply }).await?
+    }
+
+    pub async fn call(&self, room_id: String, command: CallCommand) -> Result<CallPayload> {
+        self.request(|reply| ControlMessage::Call { room_id, command, reply }).await?
+    }
+
+    pub async fn listen_addresses(&self) -> Result<Vec<String>> {
+        self.request(|reply| ControlMessage::ListListenAddrs { reply }).await
+    }
+
+    pub async fn network_status(&self, room_id: String) -> Result<NetworkStatus> {
+        self.request(|reply| ControlMessage::NetworkStatus { room_id, reply }).await
+    }
+
+    pub async fn dial(&self, address: Multiaddr) -> Result<PeerId> {
+        self.request(|reply| ControlMessage::Dial { address, reply }).await?
+    }
+
+    pub async fn disconnect(&self, room_id: String, peer_id: PeerId) -> Result<()> {
+        self.request(|reply| ControlMessage::Disconnect { room_id, peer_id, reply }).await?
+    }
+
+    pub async fn peers(&self, room_id: String) -> Result<Vec<PeerInfo>> {
+        self.request(|reply| ControlMessage::ListPeers { room_id, reply }).await?
+    }
+
+    pub async fn subscribe(&self, room_id: String, topic: String) -> Result<()> {
+        self.request(|reply| ControlMessage::Subscribe { room_id, topic, reply }).await?
+    }
+
+    pub async fn unsubscribe(&self, room_id: String, topic: String) -> Result<()> {
+        self.request(|reply| ControlMessage::Unsubscribe { room_id, topic, reply }).await?
+    }
+
+    pub async fn add_external_address(&self, address: Multiaddr) -> Result<()> {
+        self.request(|reply| ControlMessage::AddExternalAddress { address, reply }).await
+    }
+
+    pub async fn share_room(&self, room_id: String) -> Result<RoomAddresses> {
+        self.request(|reply| ControlMessage::ShareRoom { room_id, reply }).await?
+    }
+
+    pub async fn redeem_invite(&self, inviter: PeerId, request: InviteRequest) -> Result<InviteResponse> {
+        self.request(|reply| ControlMessage::RedeemInvite { inviter, request, reply }).await?
+    }
+
+    pub async fn announce(&self, room_id: String, control: RoomControl) -> Result<()> {
+        self.request(|reply| ControlMessage::Announce { room_id, control, reply }).await?
+    }
+
+    pub async fn download(&self, room_id: String, offer: FileOffer, holder: PeerId) -> Result<FilePayload> {
+        self.request(|reply| ControlMessage::Download { room_id, offer, holder, reply }).await?
+    }
+
+    /// Sends `command` to the swarm loop and waits for its reply.
+    async fn request<T>(&self, command: impl FnOnce(oneshot::Sender<T>) -> ControlMessage) -> Result<T> {
+        let (reply, response) = oneshot::channel();
+        self.sender
+            .send(command(reply))
+            .await
+            .map_err(|_| Error::from(ErrorKind::SwarmStopped))?;
+
+        response.await.map_err(|_| Error::from(ErrorKind::SwarmStopped))
+    }
 }
@@ -3,11 +3,32 @@ declare module 'rust-tc-sdk' {
 
   export function stopSdk(cleanUp: boolean): Promise<void>;
 
+  /**
+   * Unlocks the rooms' private, pre-shared and group keys, which are sealed at rest with a master key.
+   * The first call sets the passphrase or master key; until then keys are stored as older versions did.
+   */
+  export function unlockSdk(data: UnlockData): Promise<void>;
+
+  /** Forgets the master key and quits every room; calls needing a room's keys fail with `SDK_LOCKED`. */
+  export function lockSdk(): Promise<void>;
+
+  /** Re-encrypts the database under a new key; quit every room first. Needs a database started with `db_key`. */
+  export function rekeyDatabase(data: RekeyData): Promise<void>;
+
+  /**
+   * Writes every room with its identity, members, group keys, messages and file offers to an archive
+   * sealed under the passphrase. Needs the SDK unlocked; the shared files themselves are not included.
+   */
+  export function exportBackup(data: BackupData): Promise<void>;
+
+  /** Restores the rooms of an archive written by `exportBackup`; a room running here cannot be overwritten. */
+  export function importBackup(data: BackupData): Promise<RestoredBackup>;
+
   export function createRoom(option: RoomOption): Promise<Room>;
 
   export function removeRoom(data: RoomId): Promise<void>;
 
-  export function launchRoom(data: ConnectionData): Promise<void>;
+  export function launchRoom(data: ConnectionData): Promise<string[]>;
 
   export function getRoom(data: RoomId): Promise<Room>;
 
@@ -15,27 +36,393 @@ declare module 'rust-tc-sdk' {
 
   export function getRooms(): Promise<Room[]>;
 
-  export function registerListener(callback: Callback): Promise<void>;
+  export function sendMessage(data: SendMessageData): Promise<MessagePayload>;
+
+  /** The latest messages of the room sent before `query.before`, oldest first. */
+  export function getMessages(data: RoomId, query?: MessageQuery): Promise<StoredMessage[]>;
+
+  /** Offers a local file to the room in a message; it is copied to the blob store next to the database and served from there. */
+  export function offerFile(data: OfferFileData): Promise<MessagePayload>;
+
+  /**
+   * Starts pulling an offered file into the download directory; `file` events report progress.
+   * A file already in the blob store, shared in another room, completes right away.
+   */
+  export function downloadFile(data: DownloadFileData): Promise<FilePayload>;
+
+  export function getFiles(data: RoomId): Promise<SharedFile[]>;
+
+  export function sendSignal(data: SendSignalData): Promise<void>;
+
+  export function startCall(data: StartCallData): Promise<CallPayload>;
+
+  export function acceptCall(data: CallId): Promise<CallPayload>;
+
+  export function rejectCall(data: CallId): Promise<CallPayload>;
+
+  export function hangUp(data: CallId): Promise<CallPayload>;
+
+  export function getNetworkStatus(data: RoomId): Promise<NetworkStatus>;
+
+  export function getListenAddresses(data: RoomId): Promise<string[]>;
+
+  /** Resolves with the peer ID once the connection is up. */
+  export function dialPeer(data: AddressData): Promise<string>;
+
+  export function disconnectPeer(data: PeerData): Promise<void>;
+
+  export function getPeers(data: RoomId): Promise<PeerInfo[]>;
+
+  export function subscribe(data: TopicData): Promise<void>;
+
+  export function unsubscribe(data: TopicData): Promise<void>;
+
+  export function addExternalAddress(data: AddressData): Promise<void>;
+
+  /** A signed `vichiz://join/...` link to a running room. */
+  export function createInvite(data: RoomId, options?: InviteOptions): Promise<string>;
+
+  /** Creates the room the link invites to, launches it and redeems the invite with the inviter. */
+  export function acceptInvite(token: string): Promise<Room>;
+
+  /** Only the room's owners can add members; members that are offline miss the change. */
+  export function addMember(data: MemberData): Promise<Member>;
+
+  export function removeMember(data: PeerData): Promise<void>;
+
+  export function getMembers(data: RoomId): Promise<Member[]>;
+
+  export function registerListener(callback: Callback): Promise<ListenerId>;
+
+  export function unregisterListener(data: ListenerId): Promise<boolean>;
+
+  /** Every rejected promise carries one of these as `error.code`. */
+  export type SdkErrorCode =
+    | 'ROOM_NOT_FOUND'
+    | 'KEY_NOT_FOUND'
+    | 'ROOM_NOT_RUNNING'
+    | 'SWARM_STOPPED'
+    | 'SDK_NOT_STARTED'
+    | 'CALL_NOT_FOUND'
+    | 'CALL_FAILED'
+    | 'SIGNAL_FAILED'
+    | 'PEER_NOT_CONNECTED'
+    | 'INVALID_INVITE'
+    | 'INVITE_REJECTED'
+    | 'PERMISSION_DENIED'
+    | 'FILE_NOT_FOUND'
+    | 'TRANSFER_FAILED'
+    | 'SDK_LOCKED'
+    /** The passphrase or master key is not the one the SDK was first unlocked with. */
+    | 'WRONG_MASTER_KEY'
+    /** The room's messages cannot be sealed until a member hands out its group key. */
+    | 'GROUP_KEY_MISSING'
+    /** The `db_key` does not open the database. */
+    | 'WRONG_DATABASE_KEY'
+    /** Not an archive, one of an unsupported version, a wrong passphrase or an altered archive. */
+    | 'INVALID_BACKUP'
+    | 'INVALID_ARGUMENT'
+    | 'DB_ERROR'
+    | 'DIAL_ERROR'
+    | 'PUBLISH_ERROR'
+    | 'SUBSCRIPTION_ERROR'
+    | 'CRYPTO_ERROR'
+    | 'IO_ERROR'
+    | 'INTERNAL_ERROR';
+
+  export interface SdkError extends Error {
+    code: SdkErrorCode;
+  }
+
+  export type Callback = <T extends CallbackType>(type: T, data: CallbackPayloads[T]) => void;
+
+  export type CallbackType = keyof CallbackPayloads;
 
-  export type Callback = (type: string, data: CallbackPayload) => void;
+  export interface CallbackPayloads {
+    'peer-discovered': PeerPayload;
+    'peer-expired': PeerPayload;
+    'connection-opened': ConnectionPayload;
+    'connection-closed': ConnectionPayload;
+    ping: PingPayload;
+    message: MessagePayload;
+    rendezvous: RendezvousPayload;
+    signal: SignalPayload;
+    call: CallPayload;
+    reachability: NetworkStatus;
+    'hole-punch': HolePunchPayload;
+    member: MemberPayload;
+    file: FilePayload;
+  }
+
+  export interface PeerPayload {
+    room_id: string;
+    peer_id: string;
+    address: string;
+  }
+
+  export interface ConnectionPayload {
+    room_id: string;
+    peer_id: string;
+    address: string;
+    relayed: boolean;
+    transport: Transport | null;
+    cause: string | null;
+  }
+
+  /** How upgrading a relayed connection to a direct one went; `error` is `null` once it is direct. */
+  export interface HolePunchPayload {
+    room_id: string;
+    peer_id: string;
+    error: string | null;
+  }
+
+  export interface PingPayload {
+    room_id: string;
+    peer_id: string;
+    rtt_ms: number | null;
+    error: string | null;
+  }
+
+  export interface MessagePayload {
+    room_id: string;
+    peer_id: string;
+    message_id: string;
+    topic: string;
+    payload: string;
+    timestamp: number;
+    /** Fetched from another member on reconnect, so it may arrive well after `timestamp`. */
+    synced: boolean;
+    file: FileOffer | null;
+  }
+
+  export interface FileOffer {
+    /** The hex SHA-256 of the whole file. */
+    file_id: string;
+    name: string;
+    size: number;
+    chunk_size: number;
+    /** The hex SHA-256 of the concatenated chunk hashes. */
+    chunk_root: string;
+  }
+
+  export interface OfferFileData {
+    room_id: string;
+    path: string;
+  }
 
-  export interface CallbackPayload {
-    data: string;
+  export interface DownloadFileData {
+    room_id: string;
+    file_id: string;
+    /** Any member holding the file; the one that offered it by default. */
+    peer_id?: string;
+  }
+
+  export interface SharedFile {
+    room_id: string;
+    file_id: string;
+    name: string;
+    size: number;
+    chunk_size: number;
+    chunk_root: string;
+    offered_by: string;
+    /** Where the file is on this device, `null` until it is downloaded. */
+    path: string | null;
+    created_at: number;
+  }
+
+  export interface FilePayload {
+    room_id: string;
+    file_id: string;
+    name: string;
+    /** `paused` downloads resume when the serving peer reconnects, or on another `downloadFile`. */
+    event: 'started' | 'progress' | 'completed' | 'paused' | 'failed';
+    received: number;
+    size: number;
+    path: string | null;
+    error: string | null;
+  }
+
+  export interface SendMessageData {
+    room_id: string;
+    payload: string;
+    /** One of the room's custom topics; the room's chat when missing. */
+    topic?: string;
+  }
+
+  export interface MessageQuery {
+    /** Milliseconds since the unix epoch; pass the `sent_at` of the oldest message to get the page before it. */
+    before?: number;
+    /** Between 1 and 500, 50 by default. */
+    limit?: number;
+  }
+
+  export type MessageStatus = 'sent' | 'received' | 'synced';
+
+  export interface StoredMessage {
+    id: string;
+    room_id: string;
+    sender_peer_id: string;
+    topic: string;
+    payload: string;
+    sent_at: number;
+    received_at: number;
+    status: MessageStatus;
+    file_id: string | null;
+  }
+
+  export interface AddressData {
+    room_id: string;
+    address: string;
+  }
+
+  export interface PeerData {
+    room_id: string;
+    peer_id: string;
+  }
+
+  export interface TopicData {
+    room_id: string;
+    topic: string;
+  }
+
+  export interface InviteOptions {
+    /** Milliseconds since the unix epoch after which the invite is refused. */
+    expires?: number;
+    max_uses?: number;
+  }
+
+  export interface PeerInfo {
+    peer_id: string;
+    /** `false` for relays, rendezvous nodes and members of other rooms. */
+    member: boolean;
+  }
+
+  export interface RendezvousPayload {
+    room_id: string;
+    status: 'registered' | 'register-failed' | 'discovered' | 'discover-failed' | 'expired';
+    peer_id: string;
+  }
+
+  export interface SignalPayload {
+    room_id: string;
+    peer_id: string;
+    signal: SimplePeerSignal;
+  }
+
+  export interface SendSignalData {
+    room_id: string;
+    to_peer: string;
+    signal: SimplePeerSignal;
+  }
+
+  /** Whatever simple-peer emits from its `signal` event. */
+  export type SimplePeerSignal = Record<string, unknown>;
+
+  export type CallState = 'ringing' | 'active' | 'ended' | 'rejected' | 'missed' | 'busy';
+
+  export type CallEvent =
+    | 'invited'
+    | 'ringing'
+    | 'accepted'
+    | 'joined'
+    | 'left'
+    | 'rejected'
+    | 'busy'
+    | 'missed'
+    | 'ended';
+
+  export interface CallPayload {
+    room_id: string;
+    call_id: string;
+    event: CallEvent;
+    state: CallState;
+    direction: 'outgoing' | 'incoming';
+    initiator: string;
+    participants: string[];
+    peer_id: string | null;
+  }
+
+  export type Reachability = 'public' | 'private' | 'unknown';
+
+  export interface NetworkStatus {
+    room_id: string;
+    reachability: Reachability;
+    public_address: string | null;
+    external_addresses: string[];
+  }
+
+  export interface StartCallData {
+    room_id: string;
+  }
+
+  export interface CallId {
+    room_id: string;
+    call_id: string;
+  }
+
+  export interface ListenerId {
+    id: string;
   }
 
   export interface RustSDKOptions {
+    /** Shared files are kept once, in a `blobs` folder next to the database. */
     db_url?: string;
+    /** Run every room on one process-wide swarm instead of one swarm per room; private rooms keep their own. */
+    shared_swarm?: boolean;
+    /** Where downloaded files land; the user's download directory by default. */
+    download_dir?: string;
+    /** Unlocks the SDK on start with a master key the host keeps, e.g. with `safeStorage`; 32 bytes in base64. */
+    master_key?: string;
+    /**
+     * Encrypts the whole database with SQLCipher under this key; a plaintext database is encrypted on start.
+     * Needs the SDK built with its `sqlcipher` feature (`npm run build-sqlcipher`), otherwise starting fails with `DB_ERROR`.
+     */
+    db_key?: string;
+  }
+
+  export interface RekeyData {
+    key: string;
+  }
+
+  export interface BackupData {
+    path: string;
+    passphrase: string;
+    /**
+     * What an import does with a room this device already has: leave it (`skip`, the default), replace it
+     * with the backed up one (`overwrite`), or keep its name and identity and add what it lacks (`merge`).
+     */
+    conflict?: 'skip' | 'overwrite' | 'merge';
+  }
+
+  export interface RestoredBackup {
+    /** Rooms that were not here, and those overwritten. */
+    restored: string[];
+    merged: string[];
+    skipped: string[];
+  }
+
+  /** Either a passphrase, stretched with Argon2id, or the master key itself as 32 bytes in base64. */
+  export interface UnlockData {
+    passphrase?: string;
+    master_key?: string;
   }
 
   export interface ConnectionData {
     room_id: string;
     room_multi_address?: string[];
     room_listen_on?: string[];
+    /** Each ending with `/p2p/<peer id>`; the only relays the room reserves circuits on. */
+    rendezvous_nodes?: string[];
+    transports?: Transport[];
   }
 
+  export type Transport = 'tcp' | 'quic' | 'websocket';
+
   export interface RoomOption {
     id?: string;
     name: string;
+    /** Only peers holding the room's pre-shared key, handed out in invites, can connect to it. */
+    private?: boolean;
   }
 
   export interface Room {
@@ -46,4 +433,29 @@ declare module 'rust-tc-sdk' {
   export interface RoomId {
     id: string;
   }
+
+  export type MemberRole = 'owner' | 'member';
+
+  export interface MemberData {
+    room_id: string;
+    /** The member's room identity, as reported by message events. */
+    peer_id: string;
+    role?: MemberRole;
+  }
+
+  export interface Member {
+    room_id: string;
+    peer_id: string;
+    role: MemberRole;
+    added_by: string;
+    added_at: number;
+  }
+
+  export interface MemberPayload {
+    room_id: string;
+    peer_id: string;
+    event: 'added' | 'removed';
+    role: MemberRole | null;
+    changed_by: string;
+  }
 }